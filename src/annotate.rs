@@ -0,0 +1,248 @@
+use std::fmt::{self, Display, Formatter};
+use std::sync::atomic::AtomicBool;
+use crate::game_state::{GameState, Move};
+use crate::search::{get_score, Searcher};
+use crate::session::{GameRecord, Variation};
+
+pub const DEFAULT_DEPTH: usize = 4;
+/// Won positions are counted as this much better than even, so that a single lost game
+/// doesn't swamp the average loss
+const WIN_SCORE: isize = 1000;
+/// Evaluation lost by a move for it to be judged each way
+const INACCURACY_LOSS: isize = 50;
+const MISTAKE_LOSS: isize = 100;
+const BLUNDER_LOSS: isize = 200;
+/// Moves of the engine's line shown in place of a mistake
+const VARIATION_LENGTH: usize = 6;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Judgement {
+    Inaccuracy,
+    Mistake,
+    Blunder,
+}
+
+impl Judgement {
+    pub fn from_loss(loss: isize) -> Option<Judgement> {
+        Some(match loss {
+            l if l >= BLUNDER_LOSS => Judgement::Blunder,
+            l if l >= MISTAKE_LOSS => Judgement::Mistake,
+            l if l >= INACCURACY_LOSS => Judgement::Inaccuracy,
+            _ => return None,
+        })
+    }
+
+    /// Mark written after the move in annotated records
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            Judgement::Inaccuracy => "?!",
+            Judgement::Mistake => "?",
+            Judgement::Blunder => "??",
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Judgement::Inaccuracy => "Inaccuracy",
+            Judgement::Mistake => "Mistake",
+            Judgement::Blunder => "Blunder",
+        }
+    }
+}
+
+/// The engine's view of one move of a game. Scores are from Cyan's perspective as in the search.
+#[derive(Clone, Debug)]
+pub struct MoveAnnotation {
+    /// Index of the move in the record, not counting passes
+    pub ply: usize,
+    pub team: bool,
+    pub played: Move,
+    /// The engine's choice followed by its expected continuation
+    pub best_line: Vec<Move>,
+    /// Score of the position before the move, assuming best play
+    pub before: isize,
+    /// Score of the position after the move
+    pub after: isize,
+}
+
+impl MoveAnnotation {
+    /// Evaluation given up by the move, from the mover's perspective
+    pub fn loss(&self) -> isize {
+        if self.best_line.first() == Some(&self.played) {
+            return 0;
+        }
+        let (before, after) = (self.before.clamp(-WIN_SCORE, WIN_SCORE), self.after.clamp(-WIN_SCORE, WIN_SCORE));
+        let loss = if self.team { before - after } else { after - before };
+        loss.max(0)
+    }
+
+    pub fn judgement(&self) -> Option<Judgement> {
+        Judgement::from_loss(self.loss())
+    }
+}
+
+/// Totals for the moves of one player
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct PlayerSummary {
+    pub moves: usize,
+    /// Moves matching the engine's choice
+    pub best_moves: usize,
+    pub total_loss: isize,
+    pub inaccuracies: usize,
+    pub mistakes: usize,
+    pub blunders: usize,
+}
+
+impl PlayerSummary {
+    pub fn new(annotations: &[MoveAnnotation], team: bool) -> PlayerSummary {
+        let mut summary = PlayerSummary::default();
+        for annotation in annotations.iter().filter(|a| a.team == team) {
+            summary.moves += 1;
+            summary.total_loss += annotation.loss();
+            if annotation.best_line.first() == Some(&annotation.played) {
+                summary.best_moves += 1;
+            }
+            match annotation.judgement() {
+                Some(Judgement::Inaccuracy) => summary.inaccuracies += 1,
+                Some(Judgement::Mistake) => summary.mistakes += 1,
+                Some(Judgement::Blunder) => summary.blunders += 1,
+                None => {}
+            }
+        }
+        summary
+    }
+
+    /// Percentage of moves that weren't inaccuracies or worse
+    pub fn accuracy(&self) -> f64 {
+        if self.moves == 0 {
+            return 100.0;
+        }
+        let good = self.moves - self.inaccuracies - self.mistakes - self.blunders;
+        100.0 * good as f64 / self.moves as f64
+    }
+
+    pub fn average_loss(&self) -> f64 {
+        self.total_loss as f64 / self.moves.max(1) as f64
+    }
+}
+
+impl Display for PlayerSummary {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "accuracy {:.0}%, average loss {:.1}, {} of {} best moves, {} inaccuracies, {} mistakes, {} blunders",
+            self.accuracy(), self.average_loss(), self.best_moves, self.moves, self.inaccuracies, self.mistakes, self.blunders
+        )
+    }
+}
+
+/// Scores a position with the best line from it, searching to `depth`
+fn evaluate(searcher: &mut Searcher, game_state: &GameState, depth: usize) -> (isize, Vec<Move>) {
+    let (_, w, b) = game_state.score();
+    if w || b {
+        return (get_score(game_state.score()), Vec::new());
+    }
+    let mut game_state = game_state.clone();
+    if game_state.get_possible_moves().is_empty() {
+        game_state.pass();
+    }
+    match searcher.search(&mut game_state, depth, 1, &AtomicBool::new(false)).into_iter().next() {
+        Some(line) => (line.score, line.moves),
+        None => (get_score(game_state.score()), Vec::new()),
+    }
+}
+
+/// Replays a game from `start`, comparing each move with the engine's choice searching to
+/// `depth`. Positions after moves that differ from the engine's are searched one move less deep,
+/// so that both scores look equally far ahead. `progress` is called with each ply as it's reached.
+pub fn annotate_game(searcher: &mut Searcher, start: &GameState, record: &GameRecord, depth: usize, mut progress: impl FnMut(usize)) -> Result<Vec<MoveAnnotation>, String> {
+    start.clone().apply_moves(&record.moves)?;
+
+    let mut game = start.clone();
+    let mut annotations = Vec::new();
+    for (ply, m) in record.moves.iter().enumerate() {
+        progress(ply);
+        if game.get_possible_moves().is_empty() {
+            game.pass();
+        }
+        let team = game.turn();
+        let (before, best_line) = evaluate(searcher, &game, depth);
+        game.apply_move(m.clone());
+        let after = if best_line.first() == Some(m) { before } else { evaluate(searcher, &game, depth.saturating_sub(1)).0 };
+        annotations.push(MoveAnnotation { ply, team, played: m.clone(), best_line, before, after });
+    }
+    Ok(annotations)
+}
+
+/// Returns the record with the engine's line added as a variation in place of each mistake or
+/// blunder, written with each judged move marked
+pub fn annotated_record(record: &GameRecord, annotations: &[MoveAnnotation]) -> String {
+    let mut record = record.clone();
+    for annotation in annotations {
+        if annotation.judgement().is_some_and(|j| j >= Judgement::Mistake) {
+            let moves = annotation.best_line.iter().take(VARIATION_LENGTH).cloned().collect();
+            record.variations.push(Variation { ply: annotation.ply, moves });
+        }
+    }
+    record.variations.sort_by_key(|v| v.ply);
+
+    record.annotated(|ply| {
+        annotations.get(ply).and_then(|a| a.judgement()).map_or("", |j| j.symbol())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game_state::parse_move_list;
+    use crate::session::Outcome;
+
+    #[test]
+    fn judges_moves() {
+        let m = |s: &str| s.parse::<Move>().unwrap();
+        let annotation = |team, played: &str, before, after| MoveAnnotation {
+            ply: 0,
+            team,
+            played: m(played),
+            best_line: vec![m("A@1,1")],
+            before,
+            after,
+        };
+        let annotations = [
+            annotation(true, "A@1,1", 40, -500),
+            annotation(true, "A@2,2", 40, 0),
+            annotation(false, "A@2,2", 0, 150),
+            annotation(false, "A@2,2", 0, isize::MAX),
+            annotation(false, "A@2,2", 0, -30),
+        ];
+        assert_eq!(annotations.iter().map(|a| a.loss()).collect::<Vec<_>>(), [0, 40, 150, WIN_SCORE, 0]);
+        assert_eq!(annotations[2].judgement(), Some(Judgement::Mistake));
+        assert_eq!(annotations[3].judgement(), Some(Judgement::Blunder));
+
+        let cyan = PlayerSummary::new(&annotations, true);
+        assert_eq!((cyan.moves, cyan.best_moves, cyan.total_loss), (2, 1, 40));
+        assert_eq!(cyan.accuracy(), 100.0);
+        let green = PlayerSummary::new(&annotations, false);
+        assert_eq!((green.mistakes, green.blunders, green.inaccuracies), (1, 1, 0));
+        assert!((green.accuracy() - 100.0 / 3.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn annotates_game() {
+        let record = GameRecord { moves: parse_move_list("Q@0,0 Q@1,0 A@-1,0 A@2,0 A@0,-1").unwrap(), outcome: Outcome::Ended, variations: Vec::new() };
+        let mut searcher = Searcher::new();
+        searcher.set_threads(1);
+        let mut plies = Vec::new();
+        let annotations = annotate_game(&mut searcher, &GameState::new(), &record, 2, |ply| plies.push(ply)).unwrap();
+
+        assert_eq!(plies, [0, 1, 2, 3, 4]);
+        assert_eq!(annotations.iter().map(|a| a.team).collect::<Vec<_>>(), [true, false, true, false, true]);
+        assert!(annotations.iter().all(|a| !a.best_line.is_empty() && a.loss() >= 0));
+
+        let text = annotated_record(&record, &annotations);
+        assert_eq!(GameRecord::parse(&text).unwrap().moves, record.moves);
+
+        let illegal = GameRecord { moves: parse_move_list("Q@0,0 Q@5,5").unwrap(), ..record };
+        assert!(annotate_game(&mut searcher, &GameState::new(), &illegal, 2, |_| {}).is_err());
+    }
+}
@@ -0,0 +1,595 @@
+//! A local HTTP server exposing games and the engine as JSON, for tools that can't link the crate.
+//!
+//! Endpoints, with request and response bodies as JSON unless noted (also listed by `GET /`):
+//! - `POST /games` - create a game, optionally given `{"pieces": "Q1A6", "moves": ["Q@0,0", ...]}`
+//! - `GET /games` - the ids of all games
+//! - `GET /games/ID` - the game: its moves, whose turn it is, its status, the pieces on the
+//!   board with their stacks and the pieces left in each hand
+//! - `DELETE /games/ID` - forget the game
+//! - `POST /games/ID/moves` - play `{"move": "A@-1,0"}`, returning the game
+//! - `GET /games/ID/legal` - the legal moves of the team to move
+//! - `GET /games/ID/board.svg` - the board as an SVG image, with `?coordinates=true` to label cells
+//! - `POST /games/ID/engine` - the engine's move, searching for up to `{"time_ms": 1000}` and
+//!   optionally to `"depth"`. With `"play": true` the move is played as well.
+//!
+//! Teams are `"cyan"` (first to move) and `"green"`, and moves are written as elsewhere, e.g.
+//! `Q@0,0` or `0,0>1,-1`. Errors are returned as `{"error": "..."}` with a 4xx status. Players
+//! with no moves pass automatically, and a game where neither player can move is drawn.
+
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+use serde_json::{json, Value};
+use crate::game_state::{GameState, Move};
+use crate::piece_bag::PieceBag;
+use crate::rules;
+use crate::search::{score_winner, Searcher};
+use crate::session::{team_id, Outcome};
+use crate::svg::{self, SvgOptions};
+use crate::tile_types::TileType;
+
+pub const DEFAULT_PORT: u16 = 8080;
+const DEFAULT_ENGINE_TIME: Duration = Duration::from_millis(1000);
+/// Longest the engine may be asked to think for
+const MAX_ENGINE_TIME: Duration = Duration::from_secs(60);
+/// Connections that send nothing for this long are dropped, so they don't hold a thread forever
+const READ_TIMEOUT: Duration = Duration::from_secs(10);
+/// Request bodies larger than this are rejected
+const MAX_BODY: usize = 1 << 20;
+/// Request and header lines longer than this are rejected
+const MAX_LINE: usize = 8 << 10;
+/// Requests with more headers than this are rejected
+const MAX_HEADERS: usize = 100;
+/// Listed at `/` for discovery
+const ENDPOINTS: [&str; 8] = [
+    "POST /games",
+    "GET /games",
+    "GET /games/ID",
+    "DELETE /games/ID",
+    "POST /games/ID/moves",
+    "GET /games/ID/legal",
+    "GET /games/ID/board.svg",
+    "POST /games/ID/engine",
+];
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Request {
+    pub method: String,
+    pub path: String,
+    pub query: HashMap<String, String>,
+    pub body: String,
+}
+
+/// Reads a line of at most `MAX_LINE` bytes, so a client can't make the server buffer without
+/// limit. The line is empty at the end of the input.
+fn read_line(reader: &mut impl BufRead) -> Result<String, String> {
+    let mut line = String::new();
+    Read::take(&mut *reader, MAX_LINE as u64 + 1).read_line(&mut line).map_err(|e| e.to_string())?;
+    if line.len() > MAX_LINE {
+        return Err("Line too long in request".to_string());
+    }
+    Ok(line)
+}
+
+impl Request {
+    /// Reads a request, ignoring any headers other than the body's length
+    pub fn read(reader: &mut impl BufRead) -> Result<Request, String> {
+        let line = read_line(reader)?;
+        let mut parts = line.split_whitespace();
+        let (Some(method), Some(target)) = (parts.next(), parts.next()) else {
+            return Err(format!("Invalid request line '{}'", line.trim()));
+        };
+
+        let mut length = 0;
+        for count in 0.. {
+            let header = read_line(reader)?;
+            if header.trim().is_empty() {
+                break;
+            }
+            if count == MAX_HEADERS {
+                return Err("Too many headers".to_string());
+            }
+            if let Some((name, value)) = header.split_once(':') {
+                if name.trim().eq_ignore_ascii_case("content-length") {
+                    length = value.trim().parse().map_err(|_| format!("Invalid Content-Length '{}'", value.trim()))?;
+                }
+            }
+        }
+        if length > MAX_BODY {
+            return Err("Request body too large".to_string());
+        }
+        let mut body = vec![0; length];
+        reader.read_exact(&mut body).map_err(|e| e.to_string())?;
+
+        let (path, query) = target.split_once('?').unwrap_or((target, ""));
+        let query = query.split('&')
+            .filter(|p| !p.is_empty())
+            .map(|p| p.split_once('=').unwrap_or((p, "")))
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        Ok(Request {
+            method: method.to_string(),
+            path: path.to_string(),
+            query,
+            body: String::from_utf8(body).map_err(|_| "Request body isn't UTF-8".to_string())?,
+        })
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Response {
+    pub status: u16,
+    pub content_type: &'static str,
+    pub body: String,
+}
+
+impl Response {
+    fn json(status: u16, value: Value) -> Response {
+        Response { status, content_type: "application/json", body: value.to_string() }
+    }
+
+    fn error(status: u16, message: impl Into<String>) -> Response {
+        Response::json(status, json!({ "error": message.into() }))
+    }
+
+    fn empty() -> Response {
+        Response { status: 204, content_type: "text/plain", body: String::new() }
+    }
+
+    /// Writes the response, allowing pages served from `allow_origin`, such as a dashboard, to read it
+    fn write(&self, stream: &mut impl Write, allow_origin: Option<&str>) -> std::io::Result<()> {
+        let reason = match self.status {
+            200 => "OK",
+            201 => "Created",
+            204 => "No Content",
+            400 => "Bad Request",
+            404 => "Not Found",
+            405 => "Method Not Allowed",
+            422 => "Unprocessable Entity",
+            _ => "Error",
+        };
+        write!(stream, "HTTP/1.1 {} {reason}\r\nContent-Type: {}\r\nContent-Length: {}\r\n", self.status, self.content_type, self.body.len())?;
+        if let Some(origin) = allow_origin {
+            write!(
+                stream,
+                "Access-Control-Allow-Origin: {origin}\r\nAccess-Control-Allow-Methods: GET, POST, DELETE, OPTIONS\r\n\
+                Access-Control-Allow-Headers: Content-Type\r\n"
+            )?;
+        }
+        write!(stream, "Connection: close\r\n\r\n{}", self.body)
+    }
+}
+
+fn move_list(moves: &[Move]) -> Value {
+    moves.iter().map(|m| m.to_string()).collect()
+}
+
+/// A game being played through the API
+struct ApiGame {
+    pieces: PieceBag,
+    game: GameState,
+    moves: Vec<Move>,
+}
+
+impl ApiGame {
+    fn new(pieces: PieceBag, moves: &[Move]) -> Result<ApiGame, String> {
+        let mut game = ApiGame { game: GameState::with_pieces(pieces.clone()), pieces, moves: Vec::new() };
+        for m in moves {
+            game.play(m)?;
+        }
+        Ok(game)
+    }
+
+    /// The result once a queen is surrounded, or a draw if neither player can move. `play` has
+    /// already passed for a player without moves, so the side to move being stuck means both are.
+    fn outcome(&self) -> Option<Outcome> {
+        match self.game.score() {
+            (_, true, true) => Some(Outcome::Draw),
+            (_, w, b) if w || b => Some(Outcome::Win(w)),
+            _ if self.game.get_possible_moves().is_empty() => Some(Outcome::Draw),
+            _ => None,
+        }
+    }
+
+    /// Plays the move, passing for the next player if they're left without moves
+    fn play(&mut self, m: &Move) -> Result<(), String> {
+        if self.outcome().is_some() {
+            return Err("The game is over".to_string());
+        }
+        if let Some(reason) = rules::explain_illegal(&self.game, m) {
+            return Err(reason);
+        }
+        self.game.apply_move(m.clone());
+        self.moves.push(m.clone());
+        let (_, white_win, black_win) = self.game.score();
+        if !white_win && !black_win && self.game.get_possible_moves().is_empty() {
+            self.game.pass();
+        }
+        Ok(())
+    }
+
+    fn to_json(&self, id: u64) -> Value {
+        let status = match self.outcome() {
+            None => "playing",
+            Some(Outcome::Win(true)) => "cyan_won",
+            Some(Outcome::Win(false)) => "green_won",
+            Some(_) => "draw",
+        };
+        let mut cells = self.game.tiles().iter().collect::<Vec<_>>();
+        cells.sort_by_key(|(c, _)| **c);
+        let board = cells.into_iter().map(|(c, tile)| {
+            let mut stack = Vec::new();
+            let mut piece = Some(tile);
+            while let Some(p) = piece {
+                stack.push(json!({ "team": team_id(p.team()), "piece": p.tile_type().name(), "label": p.label() }));
+                piece = p.above();
+            }
+            json!({ "x": c.x, "y": c.y, "stack": stack })
+        }).collect::<Vec<_>>();
+        let hand = |team| {
+            (0..TileType::VARIANT_COUNT)
+                .map(|i| TileType::from_index(i).unwrap())
+                .map(|t| (t.name().to_string(), json!(self.game.piece_bag(team).count_of(t))))
+                .collect::<serde_json::Map<_, _>>()
+        };
+
+        json!({
+            "id": id,
+            "pieces": self.pieces.to_string(),
+            "moves": move_list(&self.moves),
+            "turn": team_id(self.game.turn()),
+            "turn_count": self.game.turn_count(),
+            "status": status,
+            "result": self.outcome().unwrap_or(Outcome::Ended).notation(),
+            "board": board,
+            "hands": { "cyan": hand(true), "green": hand(false) },
+        })
+    }
+}
+
+/// Parses a JSON object body, treating an empty body as an empty object
+fn parse_body(body: &str) -> Result<serde_json::Map<String, Value>, Response> {
+    if body.trim().is_empty() {
+        return Ok(serde_json::Map::new());
+    }
+    match serde_json::from_str(body) {
+        Ok(Value::Object(object)) => Ok(object),
+        Ok(_) => Err(Response::error(400, "Expected a JSON object")),
+        Err(e) => Err(Response::error(400, format!("Invalid JSON: {e}"))),
+    }
+}
+
+fn parse_move(value: Option<&Value>) -> Result<Move, Response> {
+    let Some(Value::String(m)) = value else {
+        return Err(Response::error(400, "Expected a move as a string, e.g. \"A@-1,0\""));
+    };
+    m.parse().map_err(|e: String| Response::error(400, e))
+}
+
+pub struct Api {
+    pieces: PieceBag,
+    games: Mutex<(u64, HashMap<u64, ApiGame>)>,
+    searcher: Mutex<Searcher>,
+    allow_origin: Option<String>,
+}
+
+impl Api {
+    /// Games are created with `pieces` unless given others. Browsers only let pages from
+    /// `allow_origin` (which may be `*`) call the API; without it, only tools outside a browser can.
+    pub fn new(pieces: PieceBag, threads: usize, allow_origin: Option<String>) -> Api {
+        let mut searcher = Searcher::new();
+        searcher.set_threads(threads);
+        Api { pieces, games: Mutex::new((0, HashMap::new())), searcher: Mutex::new(searcher), allow_origin }
+    }
+
+    pub fn handle(&self, request: &Request) -> Response {
+        let segments = request.path.trim_matches('/').split('/').collect::<Vec<_>>();
+        let method = request.method.as_str();
+        if method == "OPTIONS" {
+            return Response::empty();
+        }
+
+        let result = match segments.as_slice() {
+            [""] if method == "GET" => Ok(Response::json(200, json!({ "endpoints": ENDPOINTS }))),
+            ["games"] => match method {
+                "GET" => Ok(self.list_games()),
+                "POST" => self.create_game(&request.body),
+                _ => Err(Response::error(405, format!("{method} isn't supported for {}", request.path))),
+            },
+            ["games", id, rest @ ..] => {
+                let Ok(id) = id.parse::<u64>() else {
+                    return Response::error(404, format!("No game '{id}'"));
+                };
+                match (method, rest) {
+                    ("GET", []) => self.with_game(id, |game| Ok(Response::json(200, game.to_json(id)))),
+                    ("DELETE", []) => self.delete_game(id),
+                    ("POST", ["moves"]) => self.play_move(id, &request.body),
+                    ("GET", ["legal"]) => self.with_game(id, |game| {
+                        let mut moves = if game.outcome().is_some() { Vec::new() } else { game.game.get_possible_moves() };
+                        moves.sort();
+                        Ok(Response::json(200, json!({ "turn": team_id(game.game.turn()), "moves": move_list(&moves) })))
+                    }),
+                    ("GET", ["board.svg"]) => self.with_game(id, |game| {
+                        let options = SvgOptions {
+                            coordinates: request.query.get("coordinates").is_some_and(|v| v == "true" || v == "1"),
+                            highlight: game.moves.last().cloned(),
+                            line: Vec::new(),
+                        };
+                        let image = svg::render_svg(&game.game, &options).map_err(|e| Response::error(422, e))?;
+                        Ok(Response { status: 200, content_type: "image/svg+xml", body: image })
+                    }),
+                    ("POST", ["engine"]) => self.engine_move(id, &request.body),
+                    (_, [] | ["moves"] | ["legal"] | ["board.svg"] | ["engine"]) => {
+                        Err(Response::error(405, format!("{method} isn't supported for {}", request.path)))
+                    }
+                    _ => Err(Response::error(404, format!("Nothing at {}", request.path))),
+                }
+            }
+            _ => Err(Response::error(404, format!("Nothing at {}", request.path))),
+        };
+        result.unwrap_or_else(|response| response)
+    }
+
+    fn with_game<T>(&self, id: u64, f: impl FnOnce(&mut ApiGame) -> Result<T, Response>) -> Result<T, Response> {
+        let mut games = self.games.lock().unwrap();
+        let game = games.1.get_mut(&id).ok_or_else(|| Response::error(404, format!("No game {id}")))?;
+        f(game)
+    }
+
+    fn list_games(&self) -> Response {
+        let games = self.games.lock().unwrap();
+        let mut ids = games.1.keys().copied().collect::<Vec<_>>();
+        ids.sort();
+        Response::json(200, json!({ "games": ids }))
+    }
+
+    fn create_game(&self, body: &str) -> Result<Response, Response> {
+        let body = parse_body(body)?;
+        let pieces = match body.get("pieces") {
+            Some(Value::String(spec)) => PieceBag::parse(spec).map_err(|e| Response::error(400, e))?,
+            Some(_) => return Err(Response::error(400, "Expected pieces as a string, e.g. \"Q1A6\"")),
+            None => self.pieces.clone(),
+        };
+        let moves = match body.get("moves") {
+            Some(Value::Array(moves)) => moves.iter().map(|m| parse_move(Some(m))).collect::<Result<Vec<_>, _>>()?,
+            Some(_) => return Err(Response::error(400, "Expected moves as an array of strings")),
+            None => Vec::new(),
+        };
+        let game = ApiGame::new(pieces, &moves).map_err(|e| Response::error(422, e))?;
+
+        let mut games = self.games.lock().unwrap();
+        games.0 += 1;
+        let id = games.0;
+        let response = Response::json(201, game.to_json(id));
+        games.1.insert(id, game);
+        Ok(response)
+    }
+
+    fn delete_game(&self, id: u64) -> Result<Response, Response> {
+        match self.games.lock().unwrap().1.remove(&id) {
+            Some(_) => Ok(Response::empty()),
+            None => Err(Response::error(404, format!("No game {id}"))),
+        }
+    }
+
+    fn play_move(&self, id: u64, body: &str) -> Result<Response, Response> {
+        let m = parse_move(parse_body(body)?.get("move"))?;
+        self.with_game(id, |game| {
+            game.play(&m).map_err(|e| Response::error(422, e))?;
+            Ok(Response::json(200, game.to_json(id)))
+        })
+    }
+
+    fn engine_move(&self, id: u64, body: &str) -> Result<Response, Response> {
+        let body = parse_body(body)?;
+        let time = match body.get("time_ms") {
+            Some(t) => Duration::from_millis(t.as_u64().ok_or_else(|| Response::error(400, "Expected time_ms as a whole number"))?),
+            None => DEFAULT_ENGINE_TIME,
+        }.min(MAX_ENGINE_TIME);
+        let depth = match body.get("depth") {
+            Some(d) => Some(d.as_u64().ok_or_else(|| Response::error(400, "Expected depth as a whole number"))? as usize),
+            None => None,
+        };
+        let play = body.get("play").and_then(Value::as_bool).unwrap_or(false);
+
+        // The game is copied so that other requests aren't held up while the engine thinks
+        let (game, moves_played) = self.with_game(id, |game| match game.outcome() {
+            Some(_) => Err(Response::error(422, "The game is over")),
+            None => Ok((game.game.clone(), game.moves.len())),
+        })?;
+
+        let stop = Arc::new(AtomicBool::new(false));
+        {
+            let stop = stop.clone();
+            thread::spawn(move || {
+                thread::sleep(time);
+                stop.store(true, Ordering::Relaxed);
+            });
+        }
+        let mut best = None;
+        let mut searcher = self.searcher.lock().unwrap();
+        searcher.analyse(&game, 1, depth, &stop, |analysis| {
+            best = Some((analysis.depth, analysis.lines[0].clone()));
+        });
+        // Even the shallowest search was cut short, so settle for it regardless
+        if best.is_none() {
+            best = searcher.search(&mut game.clone(), 0, 1, &AtomicBool::new(false)).into_iter().next().map(|line| (0, line));
+        }
+        drop(searcher);
+        let Some((depth, line)) = best else {
+            return Err(Response::error(422, "No moves available"));
+        };
+
+        let mut response = json!({
+            "move": line.moves[0].to_string(),
+            "score": match score_winner(line.score) {
+                Some(team) => json!(format!("{}_wins", team_id(team))),
+                None => json!(line.score),
+            },
+            "depth": depth,
+            "line": move_list(&line.moves),
+        });
+        if play {
+            response["game"] = self.with_game(id, |game| {
+                // Another request may have moved while the engine was thinking
+                if game.moves.len() != moves_played {
+                    return Err(Response::error(422, "The game changed while the engine was thinking"));
+                }
+                game.play(&line.moves[0]).map_err(|e| Response::error(422, e))?;
+                Ok(game.to_json(id))
+            })?;
+        }
+        Ok(Response::json(200, response))
+    }
+}
+
+fn handle_connection(api: &Api, mut stream: TcpStream) {
+    if stream.set_read_timeout(Some(READ_TIMEOUT)).is_err() {
+        return;
+    }
+    let response = match Request::read(&mut BufReader::new(&stream)) {
+        Ok(request) => api.handle(&request),
+        Err(e) => Response::error(400, e),
+    };
+    let _ = response.write(&mut stream, api.allow_origin.as_deref());
+}
+
+/// Answers requests until the process ends, each connection on its own thread
+pub fn serve(listener: TcpListener, api: Arc<Api>) {
+    for stream in listener.incoming() {
+        let Ok(stream) = stream else { continue };
+        let api = api.clone();
+        thread::spawn(move || handle_connection(&api, stream));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::game_state::parse_move_list;
+    use super::*;
+
+    fn request(api: &Api, method: &str, path: &str, body: &str) -> (u16, Value) {
+        let text = format!("{method} {path} HTTP/1.1\r\nHost: localhost\r\nContent-Length: {}\r\n\r\n{body}", body.len());
+        let response = api.handle(&Request::read(&mut text.as_bytes()).unwrap());
+        let value = if response.content_type == "application/json" { serde_json::from_str(&response.body).unwrap() } else { json!(response.body) };
+        (response.status, value)
+    }
+
+    #[test]
+    fn plays_through_api() {
+        let api = Api::new(PieceBag::new(), 1, None);
+        let (status, game) = request(&api, "POST", "/games", r#"{"moves": ["Q@0,0", "Q@1,0"]}"#);
+        assert_eq!(status, 201);
+        assert_eq!(game["id"], 1);
+        assert_eq!(game["turn"], "cyan");
+        assert_eq!(game["hands"]["green"]["Queen"], 0);
+        assert_eq!(game["board"][1], json!({ "x": 1, "y": 0, "stack": [{ "team": "green", "piece": "Queen", "label": "Q" }] }));
+
+        let (status, legal) = request(&api, "GET", "/games/1/legal", "");
+        assert_eq!(status, 200);
+        assert!(legal["moves"].as_array().unwrap().contains(&json!("A@-1,0")));
+
+        let (status, game) = request(&api, "POST", "/games/1/moves", r#"{"move": "A@-1,0"}"#);
+        assert_eq!((status, game["moves"].as_array().unwrap().len()), (200, 3));
+        let (status, error) = request(&api, "POST", "/games/1/moves", r#"{"move": "A@-2,0"}"#);
+        assert_eq!(status, 422);
+        assert!(error["error"].as_str().unwrap().contains("opponent"), "{error}");
+        assert_eq!(request(&api, "POST", "/games/1/moves", "{\"move\": 5}").0, 400);
+        assert_eq!(request(&api, "POST", "/games/1/moves", "not json").0, 400);
+
+        let (status, engine) = request(&api, "POST", "/games/1/engine", r#"{"time_ms": 5000, "depth": 1, "play": true}"#);
+        assert_eq!(status, 200);
+        assert_eq!(engine["depth"], 1);
+        assert_eq!(engine["game"]["moves"][3], engine["move"]);
+        assert_eq!(engine["game"]["turn"], "cyan");
+
+        let (status, image) = request(&api, "GET", "/games/1/board.svg?coordinates=true", "");
+        assert_eq!(status, 200);
+        assert!(image.as_str().unwrap().starts_with("<svg"));
+
+        assert_eq!(request(&api, "GET", "/games", "").1, json!({ "games": [1] }));
+        assert_eq!(request(&api, "DELETE", "/games/1", "").0, 204);
+        assert_eq!(request(&api, "GET", "/games/1", "").0, 404);
+        assert_eq!(request(&api, "PUT", "/games", "").0, 405);
+        assert_eq!(request(&api, "GET", "/nothing", "").0, 404);
+        assert_eq!(request(&api, "GET", "/", "").1["endpoints"].as_array().unwrap().len(), ENDPOINTS.len());
+        assert_eq!(request(&api, "POST", "/games", r#"{"moves": ["Q@5,5"]}"#).0, 422);
+    }
+
+    #[test]
+    fn draws_when_neither_player_can_move() {
+        let api = Api::new(PieceBag::parse("Q1").unwrap(), 1, None);
+        assert_eq!(request(&api, "POST", "/games", "{}").0, 201);
+        // Skipping Cyan's first move leaves it with nothing on the board to place next to,
+        // while Green's queen can't move on its own
+        api.with_game(1, |game| {
+            game.game.pass();
+            Ok(())
+        }).unwrap();
+        let (status, game) = request(&api, "POST", "/games/1/moves", r#"{"move": "Q@1,0"}"#);
+        assert_eq!((status, game["status"].as_str()), (200, Some("draw")));
+        assert_eq!(request(&api, "GET", "/games/1/legal", "").1["moves"], json!([]));
+        assert_eq!(request(&api, "POST", "/games/1/engine", "{}").0, 422);
+    }
+
+    #[test]
+    fn rejects_oversized_requests() {
+        let long_path = format!("GET /{} HTTP/1.1\r\n\r\n", "a".repeat(MAX_LINE));
+        assert_eq!(Request::read(&mut long_path.as_bytes()), Err("Line too long in request".to_string()));
+        // Without ever ending the line
+        let mut endless = std::io::repeat(b'a');
+        assert_eq!(Request::read(&mut BufReader::new(&mut endless)), Err("Line too long in request".to_string()));
+
+        let long_header = format!("GET / HTTP/1.1\r\nX-Padding: {}\r\n\r\n", "a".repeat(MAX_LINE));
+        assert_eq!(Request::read(&mut long_header.as_bytes()), Err("Line too long in request".to_string()));
+        let many_headers = format!("GET / HTTP/1.1\r\n{}\r\n", "X-Padding: a\r\n".repeat(MAX_HEADERS + 1));
+        assert_eq!(Request::read(&mut many_headers.as_bytes()), Err("Too many headers".to_string()));
+        let enough_headers = format!("GET / HTTP/1.1\r\n{}\r\n", "X-Padding: a\r\n".repeat(MAX_HEADERS));
+        assert_eq!(Request::read(&mut enough_headers.as_bytes()).map(|r| r.path), Ok("/".to_string()));
+    }
+
+    #[test]
+    fn rejects_moves_after_win() {
+        let api = Api::new(PieceBag::new(), 1, None);
+        let moves = "Q@0,0 A@1,0 A@-1,1 A@1,1 A@-1,2 Q@1,2 A@-2,3 A@2,-1 -2,3>-1,3 1,2>0,3 A@-2,2 2,-1>1,-1 \
+            A@-2,1 A@1,-2 A@-3,2 1,-2>0,-1 -2,2>-3,3 1,1>0,1 -3,3>-4,3 A@-1,-1 -4,3>-4,2 -1,-1>-1,0";
+        let body = json!({ "moves": moves.split_whitespace().collect::<Vec<_>>() }).to_string();
+        let (status, game) = request(&api, "POST", "/games", &body);
+        assert_eq!((status, game["status"].as_str()), (201, Some("green_won")));
+
+        // The move generator would still offer this, but the game has ended
+        let mut state = GameState::new();
+        state.apply_moves(&parse_move_list(moves).unwrap()).unwrap();
+        let m = state.get_possible_moves()[0].to_string();
+        let (status, error) = request(&api, "POST", "/games/1/moves", &json!({ "move": m }).to_string());
+        assert_eq!((status, error["error"].as_str()), (422, Some("The game is over")));
+        assert_eq!(request(&api, "GET", "/games/1", "").1["moves"].as_array().unwrap().len(), 22);
+    }
+
+    #[test]
+    fn serves_over_http() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let api = Arc::new(Api::new(PieceBag::parse("Q1A2").unwrap(), 1, Some("*".to_string())));
+        thread::spawn(move || serve(listener, api));
+
+        let mut stream = TcpStream::connect(address).unwrap();
+        write!(stream, "POST /games HTTP/1.1\r\nContent-Type: application/json\r\nContent-Length: 2\r\n\r\n{{}}").unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        let (head, body) = response.split_once("\r\n\r\n").unwrap();
+        assert!(head.starts_with("HTTP/1.1 201 Created\r\n"));
+        assert!(head.contains("Access-Control-Allow-Origin: *"));
+        let game = serde_json::from_str::<Value>(body).unwrap();
+        assert_eq!(game["pieces"], "Q1A2");
+
+        // Without an allowed origin, browsers are left to refuse pages from elsewhere
+        let mut head = Vec::new();
+        Response::empty().write(&mut head, None).unwrap();
+        assert!(!String::from_utf8(head).unwrap().contains("Access-Control"));
+    }
+}
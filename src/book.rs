@@ -0,0 +1,237 @@
+use std::collections::HashMap;
+use std::fs;
+use rand::Rng;
+use crate::game_state::{GameState, Move};
+use crate::session::{GameRecord, Outcome};
+
+/// Number of moves from the start of each game added to a book by default
+pub const DEFAULT_MAX_PLIES: usize = 12;
+
+/// A move played from a book position, with results from the perspective of the side playing it
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BookMove {
+    pub m: Move,
+    pub wins: u32,
+    pub draws: u32,
+    pub losses: u32,
+}
+
+impl BookMove {
+    pub fn games(&self) -> u32 {
+        self.wins + self.draws + self.losses
+    }
+
+    /// Relative chance of the move being chosen - moves that have scored well are played more
+    /// often, but every move keeps some chance so that the book stays varied
+    pub fn weight(&self) -> u32 {
+        2 * self.wins + self.draws + 1
+    }
+}
+
+/// Moves to play in known positions, built up from the results of previous games. Positions are
+/// stored in canonical form so that symmetric positions share entries.
+#[derive(Default, Clone, Debug)]
+pub struct OpeningBook {
+    /// Moves in canonical coordinates by canonical hash
+    positions: HashMap<u64, Vec<BookMove>>,
+}
+
+impl OpeningBook {
+    pub fn new() -> OpeningBook {
+        OpeningBook::default()
+    }
+
+    /// Loads a book saved with `save`
+    pub fn load(path: &str) -> Result<OpeningBook, String> {
+        let contents = fs::read_to_string(path).map_err(|e| format!("Failed to read '{path}': {e}"))?;
+        OpeningBook::parse(&contents).map_err(|e| format!("Invalid book '{path}': {e}"))
+    }
+
+    /// Parses lines of `position move wins draws losses`, where `position` is the position's
+    /// canonical hash in hex and the move is in canonical coordinates. Anything after a `#` on a
+    /// line is ignored.
+    pub fn parse(s: &str) -> Result<OpeningBook, String> {
+        let mut book = OpeningBook::new();
+        for (i, line) in s.lines().enumerate() {
+            let fields = line.split('#').next().unwrap().split_whitespace().collect::<Vec<_>>();
+            if fields.is_empty() {
+                continue;
+            }
+            let [key, m, wins, draws, losses] = fields[..] else {
+                return Err(format!("Line {}: expected 'position move wins draws losses'", i + 1));
+            };
+            let key = u64::from_str_radix(key, 16).map_err(|_| format!("Line {}: invalid position '{key}'", i + 1))?;
+            let count = |v: &str| v.parse::<u32>().map_err(|_| format!("Line {}: invalid count '{v}'", i + 1));
+            let m = m.parse().map_err(|e| format!("Line {}: {e}", i + 1))?;
+            book.add(key, BookMove { m, wins: count(wins)?, draws: count(draws)?, losses: count(losses)? });
+        }
+        Ok(book)
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), String> {
+        fs::write(path, self.to_text()).map_err(|e| format!("Failed to write '{path}': {e}"))
+    }
+
+    /// Text form read by `parse`, sorted so that saved books are deterministic
+    pub fn to_text(&self) -> String {
+        let mut text = String::from("# position move wins draws losses\n");
+        let mut keys = self.positions.keys().collect::<Vec<_>>();
+        keys.sort();
+        for key in keys {
+            for entry in &self.positions[key] {
+                text += &format!("{key:016x} {} {} {} {}\n", entry.m, entry.wins, entry.draws, entry.losses);
+            }
+        }
+        text
+    }
+
+    /// Number of positions in the book
+    pub fn position_count(&self) -> usize {
+        self.positions.len()
+    }
+
+    /// Book moves for the position
+    pub fn moves(&self, game_state: &GameState) -> Vec<BookMove> {
+        let (hash, orientation) = game_state.canonical();
+        self.positions.get(&hash).map_or(Vec::new(), |moves| {
+            moves.iter()
+                .map(|e| BookMove { m: orientation.invert_move(&e.m), ..e.clone() })
+                .collect()
+        })
+    }
+
+    /// Merges a move into the book, adding its results to any already recorded
+    fn add(&mut self, key: u64, book_move: BookMove) {
+        let moves = self.positions.entry(key).or_default();
+        match moves.binary_search_by(|e| e.m.cmp(&book_move.m)) {
+            Ok(i) => {
+                let entry = &mut moves[i];
+                entry.wins += book_move.wins;
+                entry.draws += book_move.draws;
+                entry.losses += book_move.losses;
+            }
+            Err(i) => moves.insert(i, book_move),
+        }
+    }
+
+    /// Adds the first `max_plies` moves of a game to the book. Unfinished games count as draws.
+    pub fn add_game(&mut self, start: &GameState, record: &GameRecord, max_plies: usize) -> Result<(), String> {
+        start.clone().apply_moves(&record.moves)?;
+
+        let mut game_state = start.clone();
+        for m in record.moves.iter().take(max_plies) {
+            if game_state.get_possible_moves().is_empty() {
+                game_state.pass();
+            }
+
+            let team = game_state.turn();
+            let (wins, draws, losses) = match record.outcome {
+                Outcome::Win(winner) if winner == team => (1, 0, 0),
+                Outcome::Win(_) => (0, 0, 1),
+                Outcome::Draw | Outcome::Ended => (0, 1, 0),
+            };
+            let (hash, orientation) = game_state.canonical();
+            self.add(hash, BookMove { m: orientation.apply_move(m), wins, draws, losses });
+            game_state.apply_move(m.clone());
+        }
+        Ok(())
+    }
+
+    /// Picks a book move at random, weighted by `BookMove::weight`. Returns `None` if the
+    /// position isn't in the book.
+    pub fn choose(&self, game_state: &GameState, rng: &mut impl Rng) -> Option<Move> {
+        // Guards against hash collisions with positions where the move isn't legal
+        let legal = game_state.get_possible_moves();
+        let moves = self.moves(game_state).into_iter().filter(|e| legal.contains(&e.m)).collect::<Vec<_>>();
+
+        let total = moves.iter().map(|e| e.weight()).sum::<u32>();
+        if total == 0 {
+            return None;
+        }
+        let mut choice = rng.gen_range(0..total);
+        for entry in moves {
+            if choice < entry.weight() {
+                return Some(entry.m.clone());
+            }
+            choice -= entry.weight();
+        }
+        unreachable!()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+    use super::*;
+
+    fn record(s: &str) -> GameRecord {
+        GameRecord::parse(s).unwrap()
+    }
+
+    #[test]
+    fn add_game_merges_results() {
+        let start = GameState::new();
+        let mut book = OpeningBook::new();
+        book.add_game(&start, &record("Q@0,0 Q@1,0 A@-1,0 1-0"), DEFAULT_MAX_PLIES).unwrap();
+        book.add_game(&start, &record("Q@0,0 A@1,0 1/2-1/2"), DEFAULT_MAX_PLIES).unwrap();
+        book.add_game(&start, &record("A@0,0 Q@1,0 0-1"), DEFAULT_MAX_PLIES).unwrap();
+
+        let moves = book.moves(&start);
+        assert_eq!(moves.len(), 2);
+        let queen = moves.iter().find(|e| e.m == "Q@0,0".parse().unwrap()).unwrap();
+        assert_eq!((queen.wins, queen.draws, queen.losses), (1, 1, 0));
+        let ant = moves.iter().find(|e| e.m == "A@0,0".parse().unwrap()).unwrap();
+        assert_eq!((ant.wins, ant.draws, ant.losses), (0, 0, 1));
+
+        // Results are from Green's perspective after Cyan's first move
+        let mut after_queen = start.clone();
+        after_queen.apply_moves(&["Q@0,0".parse().unwrap()]).unwrap();
+        let replies = book.moves(&after_queen);
+        assert_eq!(replies.len(), 2);
+        assert!(replies.iter().all(|e| e.games() == 1));
+        assert_eq!(replies.iter().find(|e| e.m == "Q@1,0".parse().unwrap()).unwrap().losses, 1);
+    }
+
+    #[test]
+    fn add_game_respects_max_plies() {
+        let start = GameState::new();
+        let mut book = OpeningBook::new();
+        book.add_game(&start, &record("Q@0,0 Q@1,0 A@-1,0 A@2,0 1-0"), 2).unwrap();
+        assert_eq!(book.position_count(), 2);
+        assert!(book.add_game(&start, &record("Q@0,0 Q@5,5"), 2).is_err());
+    }
+
+    #[test]
+    fn text_round_trip() {
+        let start = GameState::new();
+        let mut book = OpeningBook::new();
+        book.add_game(&start, &record("Q@0,0 Q@1,0 A@-1,0 A@2,0 1-0"), DEFAULT_MAX_PLIES).unwrap();
+        book.add_game(&start, &record("Q@0,0 A@1,0 0-1"), DEFAULT_MAX_PLIES).unwrap();
+
+        let parsed = OpeningBook::parse(&book.to_text()).unwrap();
+        assert_eq!(parsed.positions, book.positions);
+        assert_eq!(parsed.to_text(), book.to_text());
+    }
+
+    #[test]
+    fn choose_is_weighted() {
+        let start = GameState::new();
+        let mut book = OpeningBook::new();
+        for _ in 0..8 {
+            book.add_game(&start, &record("Q@0,0 1-0"), DEFAULT_MAX_PLIES).unwrap();
+        }
+        book.add_game(&start, &record("A@0,0 0-1"), DEFAULT_MAX_PLIES).unwrap();
+
+        let mut rng = StdRng::seed_from_u64(0);
+        let queen = "Q@0,0".parse().unwrap();
+        let chosen = (0..1000).filter(|_| book.choose(&start, &mut rng).unwrap() == queen).count();
+        // Weights of 17 to 1
+        assert!(chosen > 900, "{chosen}");
+        assert!(chosen < 1000);
+
+        let mut unknown = start.clone();
+        unknown.apply_moves(&["A@0,0".parse().unwrap(), "A@1,0".parse().unwrap()]).unwrap();
+        assert_eq!(book.choose(&unknown, &mut rng), None);
+    }
+}
@@ -0,0 +1,531 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+use crate::annotate;
+use crate::api;
+use crate::book::{OpeningBook, DEFAULT_MAX_PLIES};
+use crate::game_state::{parse_move_list, Move};
+use crate::net;
+use crate::piece_bag::PieceBag;
+use crate::puzzle;
+use crate::render::BoardStyle;
+use crate::tournament::Sprt;
+
+pub const USAGE: &str = "\
+Usage: hive [GLOBAL OPTIONS] [COMMAND] [OPTIONS]
+
+Commands (default 'play'):
+  play [--white PLAYER] [--black PLAYER] [--start POSITION] [--max-turns N] [--record FILE]
+      Play a game, by default human (Cyan) against the engine (Green). Human players can
+      enter 'hint' for a suggestion, 'moves [PIECE]' to list legal moves, and 'undo' and
+      'redo' to take back moves. With --record the game is appended to FILE, including any
+      moves taken back as variations.
+  serve [--port N] [--start POSITION] [--max-turns N] [--record FILE]
+      Host a game on the local network (port 7878 by default). The first client to connect
+      plays Cyan, the second Green and any others watch. Clients can be 'hive connect' or
+      anything speaking the line protocol, e.g. 'nc HOST 7878' then 'MOVE Q@0,0'.
+  connect <HOST[:PORT]> [--player PLAYER]
+      Join a game hosted with 'serve', choosing moves with PLAYER (default human)
+  api [--port N] [--threads N] [--allow-origin ORIGIN]
+      Serve games and the engine over HTTP with JSON, on port 8080 by default. GET / lists
+      the endpoints, e.g. POST /games then POST /games/1/moves with {\"move\": \"Q@0,0\"}
+      or POST /games/1/engine with {\"time_ms\": 500}. Web pages can only call the API
+      when served from ORIGIN, e.g. http://localhost:3000, or from anywhere with '*'.
+  analyse <POSITION> [--engine PLAYER]
+      Show the engine's choice of move for a position
+  analyse <POSITION> --lines N [--depth D] [--time SECONDS] [--threads N]
+      Search with increasing depth, printing the best N moves with their scores and
+      principal variations after each depth. Runs until depth D or the time limit is
+      reached, or Enter is pressed.
+  render <POSITION> [--svg FILE] [--coordinates] [--line MOVES] [--no-highlight]
+      Draw a position, highlighting the move that led to it. With --svg the board is written
+      to FILE as an SVG image, with --line moves drawn as numbered arrows.
+  perft <DEPTH> [--position POSITION] [--divide]
+      Count the positions reachable in exactly DEPTH moves, optionally split by first move
+  selfplay [--games N] [--white PLAYER] [--black PLAYER] [--max-turns N]
+      Play games between two computer players and report the results
+  tournament --player1 PLAYER --player2 PLAYER [--games N] [--max-turns N]
+             [--openings FILE] [--sprt ELO0,ELO1] [--alpha A] [--beta B]
+      Play a match alternating colours, reporting the Elo difference of player 1 over
+      player 2. Openings are read one move list per line and each is played with both
+      colours. With --sprt the match stops once either hypothesis is accepted.
+  book import <RECORDS...> --output FILE [--max-plies N]
+  book selfplay --output FILE [--games N] [--white PLAYER] [--black PLAYER]
+                [--max-turns N] [--max-plies N]
+      Add the opening moves of games to a book, creating it if it doesn't exist. Game
+      records are read one per line as a move list followed by the result (1-0, 0-1 or
+      1/2-1/2), with any variations in brackets ignored. With 'selfplay' the games are
+      played first.
+  annotate <RECORDS> [--game N] [--depth D] [--threads N] [--output FILE]
+      Replay saved games through the engine, marking inaccuracies (?!), mistakes (?) and
+      blunders (??) by how much evaluation each move gives up, and summarising each player.
+      Annotated records are written to FILE, with the engine's line given as a variation
+      in place of each mistake. With --game only the Nth game of the file is annotated.
+  puzzle generate [RECORDS...] --output FILE [--max-moves N] [--threads N]
+                  [--games N] [--white PLAYER] [--black PLAYER] [--max-turns N]
+      Search the positions of games for ones where exactly one move forces the enemy
+      queen to be surrounded within N moves (default 3), appending them with their
+      solutions to FILE. Without record files, games are played first as with 'book
+      selfplay'.
+  puzzle solve <FILE> [--number N]
+      Present the puzzles in FILE, or only the Nth, checking each move entered. Enter
+      'hint' for the next move of the solution or 'skip' to give up on a puzzle.
+  help
+      Show this message
+
+Players are given as KIND[:KEY=VALUE,...], one of:
+  human
+  tui[:eval=N]   (full-screen board with cursor keys, evaluating to depth N)
+  random[:seed=N]
+  engine[:depth=N,threads=N,weights=FILE,ordering=false,book=FILE|false]   (also 'minimax')
+  mcts[:iterations=N,time=MS,playout=random|pressure,seed=N,book=FILE|false]
+  script:file=FILE
+
+Positions are a move list, or a file containing one, with moves separated by whitespace or ';'.
+Placements are written as Q@0,0 and movements as 0,0>1,-1. Use \"\" for the starting position.
+
+Global options:
+  --pieces SPEC       Pieces each player starts with, e.g. Q1A6 (the default)
+  --no-colour         Disable coloured output
+  --colour WHEN       'always' or 'never'
+  --seed N            Seed for players using randomness
+  --book FILE         Opening book for engines - 'book=false' disables it for a player
+  --board STYLE       How boards are drawn: 'ascii' hexagons (the default), 'unicode' or 'grid'";
+
+const DEFAULT_ENGINE: &str = "engine";
+const DEFAULT_GAMES: usize = 10;
+const DEFAULT_SELFPLAY_MAX_TURNS: usize = 300;
+const DEFAULT_TOURNAMENT_GAMES: usize = 100;
+const DEFAULT_SPRT_ERROR: f64 = 0.05;
+/// Varied enough between games to explore different openings and positions
+const DEFAULT_SOURCE_PLAYER: &str = "mcts:iterations=200";
+
+/// Options taking no value - all others take one
+const FLAGS: [&str; 5] = ["--no-colour", "--help", "--divide", "--coordinates", "--no-highlight"];
+
+pub struct GlobalOptions {
+    pub pieces: PieceBag,
+    pub colour: bool,
+    pub seed: Option<u64>,
+    pub book: Option<Arc<OpeningBook>>,
+    pub board: BoardStyle,
+}
+
+/// Where the games for building an opening book or finding puzzles come from
+pub enum GameSource {
+    /// Files of game records
+    Records(Vec<String>),
+    SelfPlay { games: usize, white: String, black: String, max_turns: Option<usize> },
+}
+
+pub enum Command {
+    Play { white: String, black: String, start: Vec<Move>, max_turns: Option<usize>, record: Option<String> },
+    Analyse { position: Vec<Move>, engine: String },
+    Analysis { position: Vec<Move>, lines: usize, depth: Option<usize>, time: Option<f64>, threads: Option<usize> },
+    Render { position: Vec<Move>, svg: Option<String>, coordinates: bool, line: Vec<Move>, highlight: bool },
+    Perft { depth: usize, position: Vec<Move>, divide: bool },
+    SelfPlay { games: usize, white: String, black: String, max_turns: Option<usize> },
+    Tournament { first: String, second: String, games: usize, max_turns: Option<usize>, openings: Vec<Vec<Move>>, sprt: Option<Sprt> },
+    Book { source: GameSource, output: String, max_plies: usize },
+    Serve { port: u16, start: Vec<Move>, max_turns: Option<usize>, record: Option<String> },
+    Connect { address: String, player: String },
+    Api { port: u16, threads: Option<usize>, allow_origin: Option<String> },
+    Annotate { records: String, game: Option<usize>, depth: usize, threads: Option<usize>, output: Option<String> },
+    PuzzleGenerate { source: GameSource, output: String, max_moves: usize, threads: Option<usize> },
+    PuzzleSolve { file: String, number: Option<usize> },
+    Help,
+}
+
+/// Arguments split into positionals and `--name [value]` options
+struct Arguments {
+    positional: Vec<String>,
+    options: HashMap<String, String>,
+}
+
+impl Arguments {
+    fn split(args: &[String]) -> Result<Arguments, String> {
+        let mut positional = Vec::new();
+        let mut options = HashMap::new();
+
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            if !arg.starts_with("--") {
+                positional.push(arg.clone());
+                continue;
+            }
+
+            let value = if FLAGS.contains(&arg.as_str()) {
+                String::new()
+            }
+            else {
+                args.next().ok_or_else(|| format!("{arg} requires a value"))?.clone()
+            };
+            if options.insert(arg.clone(), value).is_some() {
+                return Err(format!("{arg} given more than once"));
+            }
+        }
+
+        Ok(Arguments { positional, options })
+    }
+
+    fn take(&mut self, name: &str) -> Option<String> {
+        self.options.remove(name)
+    }
+
+    fn take_parsed<T: std::str::FromStr>(&mut self, name: &str) -> Result<Option<T>, String> {
+        self.take(name)
+            .map(|v| v.parse().map_err(|_| format!("Invalid value '{v}' for {name}")))
+            .transpose()
+    }
+
+    /// Errors if any options or positionals remain unused
+    fn finish(self, command: &str) -> Result<(), String> {
+        if let Some(option) = self.options.keys().next() {
+            return Err(format!("Unknown option {option} for '{command}'"));
+        }
+        if let Some(arg) = self.positional.first() {
+            return Err(format!("Unexpected argument '{arg}' for '{command}'"));
+        }
+        Ok(())
+    }
+}
+
+/// Reads a position from a file if one exists at the given path, otherwise parses it as a
+/// move list directly
+fn parse_position(position: &str) -> Result<Vec<Move>, String> {
+    if !position.trim().is_empty() && Path::new(position).is_file() {
+        let contents = fs::read_to_string(position).map_err(|e| format!("Failed to read '{position}': {e}"))?;
+        return parse_move_list(&contents);
+    }
+    parse_move_list(position)
+}
+
+/// Reads a file of openings, one move list per line
+fn parse_openings(path: &str) -> Result<Vec<Vec<Move>>, String> {
+    let contents = fs::read_to_string(path).map_err(|e| format!("Failed to read '{path}': {e}"))?;
+    let mut openings = Vec::new();
+    for (i, line) in contents.lines().enumerate() {
+        let moves = parse_move_list(line).map_err(|e| format!("Opening on line {}: {e}", i + 1))?;
+        if !moves.is_empty() {
+            openings.push(moves);
+        }
+    }
+    Ok(openings)
+}
+
+/// Parses the arguments, excluding the program name
+pub fn parse_args(args: &[String]) -> Result<(GlobalOptions, Command), String> {
+    let mut args = Arguments::split(args)?;
+
+    let pieces = match args.take("--pieces") {
+        Some(spec) => PieceBag::parse(&spec)?,
+        None => PieceBag::new(),
+    };
+    let mut colour = args.take("--no-colour").is_none();
+    if let Some(when) = args.take("--colour") {
+        colour = match when.as_str() {
+            "always" => true,
+            "never" => false,
+            _ => return Err(format!("Invalid value '{when}' for --colour")),
+        };
+    }
+    let seed = args.take_parsed("--seed")?;
+    let book = args.take("--book").map(|path| OpeningBook::load(&path)).transpose()?.map(Arc::new);
+    let board = args.take("--board").map(|s| s.parse()).transpose()?.unwrap_or(BoardStyle::Ascii);
+    let global = GlobalOptions { pieces, colour, seed, book, board };
+
+    if args.take("--help").is_some() {
+        return Ok((global, Command::Help));
+    }
+    // Default to playing a game
+    let command_name = if args.positional.is_empty() { "play".to_string() } else { args.positional.remove(0) };
+
+    let command = match command_name.as_str() {
+        "play" => Command::Play {
+            white: args.take("--white").unwrap_or("human".to_string()),
+            black: args.take("--black").unwrap_or(DEFAULT_ENGINE.to_string()),
+            start: args.take("--start").map(|p| parse_position(&p)).transpose()?.unwrap_or_default(),
+            max_turns: args.take_parsed("--max-turns")?,
+            record: args.take("--record"),
+        },
+        "serve" => Command::Serve {
+            port: args.take_parsed("--port")?.unwrap_or(net::DEFAULT_PORT),
+            start: args.take("--start").map(|p| parse_position(&p)).transpose()?.unwrap_or_default(),
+            max_turns: args.take_parsed("--max-turns")?,
+            record: args.take("--record"),
+        },
+        "connect" => {
+            if args.positional.is_empty() {
+                return Err("'connect' requires the server's address".to_string());
+            }
+            let address = args.positional.remove(0);
+            Command::Connect {
+                // Without a port the server's default is used
+                address: if address.contains(':') { address } else { format!("{address}:{}", net::DEFAULT_PORT) },
+                player: args.take("--player").unwrap_or("human".to_string()),
+            }
+        }
+        "api" => Command::Api {
+            port: args.take_parsed("--port")?.unwrap_or(api::DEFAULT_PORT),
+            threads: args.take_parsed("--threads")?,
+            allow_origin: args.take("--allow-origin"),
+        },
+        "analyse" | "analyze" => {
+            if args.positional.is_empty() {
+                return Err("'analyse' requires a position".to_string());
+            }
+            let position = parse_position(&args.positional.remove(0))?;
+            match args.take_parsed::<usize>("--lines")? {
+                Some(0) => return Err("--lines must be at least 1".to_string()),
+                Some(lines) => Command::Analysis {
+                    position,
+                    lines,
+                    depth: args.take_parsed("--depth")?,
+                    time: args.take_parsed("--time")?,
+                    threads: args.take_parsed("--threads")?,
+                },
+                None => Command::Analyse {
+                    position,
+                    engine: args.take("--engine").unwrap_or(DEFAULT_ENGINE.to_string()),
+                },
+            }
+        }
+        "render" => {
+            if args.positional.is_empty() {
+                return Err("'render' requires a position".to_string());
+            }
+            Command::Render {
+                position: parse_position(&args.positional.remove(0))?,
+                svg: args.take("--svg"),
+                coordinates: args.take("--coordinates").is_some(),
+                line: args.take("--line").map(|l| parse_move_list(&l)).transpose()?.unwrap_or_default(),
+                highlight: args.take("--no-highlight").is_none(),
+            }
+        }
+        "perft" => {
+            if args.positional.is_empty() {
+                return Err("'perft' requires a depth".to_string());
+            }
+            let depth = args.positional.remove(0);
+            Command::Perft {
+                depth: depth.parse().map_err(|_| format!("Invalid depth '{depth}'"))?,
+                position: args.take("--position").map(|p| parse_position(&p)).transpose()?.unwrap_or_default(),
+                divide: args.take("--divide").is_some(),
+            }
+        }
+        "selfplay" => Command::SelfPlay {
+            games: args.take_parsed("--games")?.unwrap_or(DEFAULT_GAMES),
+            white: args.take("--white").unwrap_or(DEFAULT_ENGINE.to_string()),
+            black: args.take("--black").unwrap_or(DEFAULT_ENGINE.to_string()),
+            max_turns: Some(args.take_parsed("--max-turns")?.unwrap_or(DEFAULT_SELFPLAY_MAX_TURNS)),
+        },
+        "tournament" => {
+            let openings = match args.take("--openings") {
+                Some(path) => parse_openings(&path)?,
+                None => Vec::new(),
+            };
+            let sprt = match args.take("--sprt") {
+                Some(bounds) => {
+                    let Some((Ok(elo0), Ok(elo1))) = bounds.split_once(',').map(|(a, b)| (a.trim().parse(), b.trim().parse())) else {
+                        return Err(format!("Invalid value '{bounds}' for --sprt - expected 'ELO0,ELO1'"));
+                    };
+                    Some(Sprt {
+                        elo0,
+                        elo1,
+                        alpha: args.take_parsed("--alpha")?.unwrap_or(DEFAULT_SPRT_ERROR),
+                        beta: args.take_parsed("--beta")?.unwrap_or(DEFAULT_SPRT_ERROR),
+                    })
+                }
+                None => None,
+            };
+            Command::Tournament {
+                first: args.take("--player1").ok_or("'tournament' requires --player1")?,
+                second: args.take("--player2").ok_or("'tournament' requires --player2")?,
+                games: args.take_parsed("--games")?.unwrap_or(DEFAULT_TOURNAMENT_GAMES),
+                max_turns: Some(args.take_parsed("--max-turns")?.unwrap_or(DEFAULT_SELFPLAY_MAX_TURNS)),
+                openings,
+                sprt,
+            }
+        }
+        "book" => {
+            if args.positional.is_empty() {
+                return Err("'book' requires 'import' or 'selfplay'".to_string());
+            }
+            let source = match args.positional.remove(0).as_str() {
+                "import" => {
+                    if args.positional.is_empty() {
+                        return Err("'book import' requires game record files".to_string());
+                    }
+                    GameSource::Records(args.positional.drain(..).collect())
+                }
+                "selfplay" => GameSource::SelfPlay {
+                    games: args.take_parsed("--games")?.unwrap_or(DEFAULT_GAMES),
+                    white: args.take("--white").unwrap_or(DEFAULT_SOURCE_PLAYER.to_string()),
+                    black: args.take("--black").unwrap_or(DEFAULT_SOURCE_PLAYER.to_string()),
+                    max_turns: Some(args.take_parsed("--max-turns")?.unwrap_or(DEFAULT_SELFPLAY_MAX_TURNS)),
+                },
+                other => return Err(format!("Unknown book command '{other}' - expected 'import' or 'selfplay'")),
+            };
+            Command::Book {
+                source,
+                output: args.take("--output").ok_or("'book' requires --output")?,
+                max_plies: args.take_parsed("--max-plies")?.unwrap_or(DEFAULT_MAX_PLIES),
+            }
+        }
+        "annotate" => {
+            if args.positional.is_empty() {
+                return Err("'annotate' requires a file of game records".to_string());
+            }
+            let game = args.take_parsed::<usize>("--game")?;
+            if game == Some(0) {
+                return Err("Games are numbered from 1".to_string());
+            }
+            Command::Annotate {
+                records: args.positional.remove(0),
+                game,
+                depth: args.take_parsed("--depth")?.unwrap_or(annotate::DEFAULT_DEPTH),
+                threads: args.take_parsed("--threads")?,
+                output: args.take("--output"),
+            }
+        }
+        "puzzle" | "puzzles" => {
+            if args.positional.is_empty() {
+                return Err("'puzzle' requires 'generate' or 'solve'".to_string());
+            }
+            match args.positional.remove(0).as_str() {
+                "generate" => {
+                    let source = if args.positional.is_empty() {
+                        GameSource::SelfPlay {
+                            games: args.take_parsed("--games")?.unwrap_or(DEFAULT_GAMES),
+                            white: args.take("--white").unwrap_or(DEFAULT_SOURCE_PLAYER.to_string()),
+                            black: args.take("--black").unwrap_or(DEFAULT_SOURCE_PLAYER.to_string()),
+                            max_turns: Some(args.take_parsed("--max-turns")?.unwrap_or(DEFAULT_SELFPLAY_MAX_TURNS)),
+                        }
+                    }
+                    else {
+                        GameSource::Records(args.positional.drain(..).collect())
+                    };
+                    let max_moves = args.take_parsed("--max-moves")?.unwrap_or(puzzle::DEFAULT_MAX_MOVES);
+                    if max_moves == 0 {
+                        return Err("--max-moves must be at least 1".to_string());
+                    }
+                    Command::PuzzleGenerate {
+                        source,
+                        output: args.take("--output").ok_or("'puzzle generate' requires --output")?,
+                        max_moves,
+                        threads: args.take_parsed("--threads")?,
+                    }
+                }
+                "solve" => {
+                    if args.positional.is_empty() {
+                        return Err("'puzzle solve' requires a file of puzzles".to_string());
+                    }
+                    let number = args.take_parsed::<usize>("--number")?;
+                    if number == Some(0) {
+                        return Err("Puzzles are numbered from 1".to_string());
+                    }
+                    Command::PuzzleSolve { file: args.positional.remove(0), number }
+                }
+                other => return Err(format!("Unknown puzzle command '{other}' - expected 'generate' or 'solve'")),
+            }
+        }
+        "help" => Command::Help,
+        _ => return Err(format!("Unknown command '{command_name}' - see 'hive help'")),
+    };
+
+    args.finish(&command_name)?;
+    Ok((global, command))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &str) -> Result<(GlobalOptions, Command), String> {
+        parse_args(&args.split_whitespace().map(String::from).collect::<Vec<_>>())
+    }
+
+    fn command(args: &str) -> Command {
+        match parse(args) {
+            Ok((_, command)) => command,
+            Err(e) => panic!("'{args}': {e}"),
+        }
+    }
+
+    fn error(args: &str) -> String {
+        match parse(args) {
+            Ok(_) => panic!("'{args}' parsed"),
+            Err(e) => e,
+        }
+    }
+
+    #[test]
+    fn defaults_to_play() {
+        let (global, play) = parse("").unwrap();
+        assert!(global.colour);
+        assert_eq!(global.board, BoardStyle::Ascii);
+        assert!(matches!(play, Command::Play { white, black, start, max_turns: None, record: None }
+            if white == "human" && black == DEFAULT_ENGINE && start.is_empty()));
+
+        let (global, play) = parse("--no-colour --seed 4 --white random --max-turns 9").unwrap();
+        assert!(!global.colour);
+        assert_eq!(global.seed, Some(4));
+        assert!(matches!(play, Command::Play { white, max_turns: Some(9), .. } if white == "random"));
+        assert!(matches!(command("--help"), Command::Help));
+        assert!(matches!(command("help"), Command::Help));
+    }
+
+    #[test]
+    fn parses_positionals() {
+        let position = parse_move_list("Q@0,0 Q@1,0").unwrap();
+        assert!(matches!(command("connect example.org"), Command::Connect { address, player }
+            if address == format!("example.org:{}", net::DEFAULT_PORT) && player == "human"));
+        assert!(matches!(command("connect example.org:99"), Command::Connect { address, .. } if address == "example.org:99"));
+        assert!(matches!(command("analyse Q@0,0;Q@1,0"), Command::Analyse { position: p, engine } if p == position && engine == DEFAULT_ENGINE));
+        assert!(matches!(command("analyze Q@0,0;Q@1,0 --lines 2 --depth 3"), Command::Analysis { position: p, lines: 2, depth: Some(3), time: None, threads: None }
+            if p == position));
+        assert!(matches!(command("render Q@0,0;Q@1,0 --coordinates --no-highlight"), Command::Render { position: p, svg: None, coordinates: true, highlight: false, .. }
+            if p == position));
+        assert!(matches!(command("perft 3 --divide"), Command::Perft { depth: 3, divide: true, .. }));
+        assert!(matches!(command("book import a.txt b.txt --output book.txt"), Command::Book { source: GameSource::Records(files), output, .. }
+            if files == ["a.txt", "b.txt"] && output == "book.txt"));
+        assert!(matches!(command("book selfplay --output book.txt --games 3"), Command::Book { source: GameSource::SelfPlay { games: 3, .. }, .. }));
+        assert!(matches!(command("annotate games.txt --game 2"), Command::Annotate { records, game: Some(2), depth: annotate::DEFAULT_DEPTH, .. }
+            if records == "games.txt"));
+        assert!(matches!(command("puzzle generate games.txt --output p.txt"), Command::PuzzleGenerate { source: GameSource::Records(files), max_moves: puzzle::DEFAULT_MAX_MOVES, .. }
+            if files == ["games.txt"]));
+        assert!(matches!(command("puzzles generate --output p.txt"), Command::PuzzleGenerate { source: GameSource::SelfPlay { .. }, .. }));
+        assert!(matches!(command("puzzle solve p.txt --number 4"), Command::PuzzleSolve { file, number: Some(4) } if file == "p.txt"));
+
+        assert_eq!(error("connect"), "'connect' requires the server's address");
+        assert_eq!(error("analyse"), "'analyse' requires a position");
+        assert_eq!(error("render"), "'render' requires a position");
+        assert_eq!(error("perft"), "'perft' requires a depth");
+        assert_eq!(error("perft deep"), "Invalid depth 'deep'");
+        assert_eq!(error("book import --output book.txt"), "'book import' requires game record files");
+        assert_eq!(error("book export"), "Unknown book command 'export' - expected 'import' or 'selfplay'");
+        assert_eq!(error("puzzle solve"), "'puzzle solve' requires a file of puzzles");
+        assert_eq!(error("perft 2 extra"), "Unexpected argument 'extra' for 'perft'");
+        assert_eq!(error("dance"), "Unknown command 'dance' - see 'hive help'");
+    }
+
+    #[test]
+    fn rejects_bad_options() {
+        assert_eq!(error("play --speed 3"), "Unknown option --speed for 'play'");
+        assert_eq!(error("play --white random --white human"), "--white given more than once");
+        assert_eq!(error("play --white"), "--white requires a value");
+        assert_eq!(error("play --max-turns many"), "Invalid value 'many' for --max-turns");
+        assert_eq!(error("--colour sometimes"), "Invalid value 'sometimes' for --colour");
+        assert_eq!(error("tournament --player1 engine"), "'tournament' requires --player2");
+        assert_eq!(error("tournament --player1 a --player2 b --sprt 5"), "Invalid value '5' for --sprt - expected 'ELO0,ELO1'");
+    }
+
+    #[test]
+    fn rejects_zero_counts() {
+        assert_eq!(error("annotate games.txt --game 0"), "Games are numbered from 1");
+        assert_eq!(error("puzzle solve p.txt --number 0"), "Puzzles are numbered from 1");
+        assert_eq!(error("analyse Q@0,0 --lines 0"), "--lines must be at least 1");
+        assert_eq!(error("puzzle generate --output p.txt --max-moves 0"), "--max-moves must be at least 1");
+    }
+}
@@ -0,0 +1,41 @@
+use std::fmt::Display;
+use std::sync::atomic::{AtomicBool, Ordering};
+use color_print::cformat;
+
+static ENABLED: AtomicBool = AtomicBool::new(true);
+
+pub fn set_enabled(enabled: bool) {
+    ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+pub fn enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+/// Formats `text` in the colour of `team` - cyan for the first player, green for the second
+pub fn team(team: bool, text: impl Display) -> String {
+    if !enabled() {
+        return text.to_string();
+    }
+    if team {
+        cformat!("<c>{}</>", text)
+    }
+    else {
+        cformat!("<g>{}</>", text)
+    }
+}
+
+pub fn dim(text: impl Display) -> String {
+    if !enabled() {
+        return text.to_string();
+    }
+    cformat!("<dim>{}</>", text)
+}
+
+/// Formats `text` to stand out, e.g. for cursors and selections
+pub fn highlight(text: impl Display) -> String {
+    if !enabled() {
+        return text.to_string();
+    }
+    cformat!("<bold><y>{}</></>", text)
+}
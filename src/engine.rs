@@ -0,0 +1,27 @@
+use crate::game_state::{GameState, Move};
+use crate::mcts::Mcts;
+use crate::search::Searcher;
+
+/// A move-choosing engine, used by the computer players
+pub enum Engine {
+    Minimax { searcher: Searcher, depth: usize },
+    Mcts(Box<Mcts>),
+}
+
+impl Engine {
+    /// Chooses a move for the side to move, along with its score (positive favours Cyan).
+    /// Returns `None` if there are no moves available.
+    pub fn best_move(&mut self, game_state: &mut GameState) -> Option<(Move, isize)> {
+        match self {
+            Engine::Minimax { searcher, depth } => searcher.best_move(game_state, *depth),
+            Engine::Mcts(mcts) => mcts.best_move(game_state),
+        }
+    }
+
+    pub fn print_stats(&self) {
+        match self {
+            Engine::Minimax { searcher, .. } => searcher.stats().print(),
+            Engine::Mcts(mcts) => mcts.stats().print(),
+        }
+    }
+}
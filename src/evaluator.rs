@@ -0,0 +1,184 @@
+use std::collections::HashSet;
+use std::fs;
+use crate::game_state::{GameState, Move};
+use crate::hex_coord::HexCoord;
+
+/// Scores a position from the perspective of the first player (Cyan) - positive is better for
+/// Cyan, negative for Green. Won or drawn positions are handled by the search and never
+/// reach the evaluator.
+pub trait Evaluator: Send + Sync {
+    fn evaluate(&self, game_state: &GameState) -> isize;
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct EvalWeights {
+    /// Per piece surrounding the enemy queen
+    pub queen_neighbours: isize,
+    /// Per available piece movement
+    pub mobility: isize,
+    /// Per own piece that can't move without splitting the hive
+    pub pinned: isize,
+    /// Per piece stacked on top of the enemy queen
+    pub queen_stacked: isize,
+    /// Per piece still available to be placed
+    pub in_hand: isize,
+    /// Per empty space the queen could step to
+    pub queen_escape: isize,
+}
+
+impl Default for EvalWeights {
+    fn default() -> Self {
+        EvalWeights {
+            queen_neighbours: 100,
+            mobility: 2,
+            pinned: -8,
+            queen_stacked: 60,
+            in_hand: 4,
+            queen_escape: 12,
+        }
+    }
+}
+
+impl EvalWeights {
+    /// Loads weights from a file of `name = value` lines. Blank lines and lines starting with
+    /// `#` are ignored, and any weight not given keeps its default value.
+    pub fn load(path: &str) -> Result<EvalWeights, String> {
+        let contents = fs::read_to_string(path).map_err(|e| format!("Failed to read '{path}': {e}"))?;
+        EvalWeights::parse(&contents)
+    }
+
+    pub fn parse(contents: &str) -> Result<EvalWeights, String> {
+        let mut weights = EvalWeights::default();
+
+        for (i, line) in contents.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let Some((name, value)) = line.split_once('=') else {
+                return Err(format!("Line {}: expected 'name = value'", i + 1));
+            };
+            let Ok(value) = value.trim().parse::<isize>() else {
+                return Err(format!("Line {}: invalid value '{}'", i + 1, value.trim()));
+            };
+
+            let weight = match name.trim() {
+                "queen_neighbours" => &mut weights.queen_neighbours,
+                "mobility" => &mut weights.mobility,
+                "pinned" => &mut weights.pinned,
+                "queen_stacked" => &mut weights.queen_stacked,
+                "in_hand" => &mut weights.in_hand,
+                "queen_escape" => &mut weights.queen_escape,
+                other => return Err(format!("Line {}: unknown weight '{other}'", i + 1)),
+            };
+            *weight = value;
+        }
+
+        Ok(weights)
+    }
+}
+
+#[derive(Default)]
+pub struct DefaultEvaluator {
+    weights: EvalWeights,
+}
+
+impl DefaultEvaluator {
+    pub fn new(weights: EvalWeights) -> DefaultEvaluator {
+        DefaultEvaluator { weights }
+    }
+
+    /// Scores everything about `team`'s own position, excluding pressure on the enemy queen
+    fn team_score(&self, game_state: &GameState, team: bool, pinned: &HashSet<HexCoord>) -> isize {
+        let w = &self.weights;
+
+        let movements = game_state.get_movements_pinned(team, pinned);
+        let mobility = movements.len() as isize;
+        let in_hand = game_state.piece_bag(team).count() as isize;
+        let pinned = pinned.iter()
+            .filter(|loc| game_state.tiles()[*loc].top().team() == team)
+            .count() as isize;
+
+        let queen_escape = game_state.queen_location(team).map_or(0, |queen| {
+            movements.iter()
+                .filter(|m| matches!(m, Move::Move(from, _) if from == queen))
+                .count() as isize
+        });
+
+        w.mobility * mobility + w.pinned * pinned + w.in_hand * in_hand + w.queen_escape * queen_escape
+    }
+
+    /// Scores the pressure `team` is putting on the enemy queen
+    fn attack_score(&self, game_state: &GameState, team: bool) -> isize {
+        let w = &self.weights;
+        let Some(queen) = game_state.queen_location(!team) else { return 0 };
+
+        let neighbours = queen.surrounding().iter()
+            .filter(|s| game_state.tiles().contains_key(s))
+            .count() as isize;
+
+        let stacked = game_state.tiles().get(queen).map_or(0, |t| t.height() - 1) as isize;
+
+        w.queen_neighbours * neighbours + w.queen_stacked * stacked
+    }
+}
+
+impl Evaluator for DefaultEvaluator {
+    fn evaluate(&self, game_state: &GameState) -> isize {
+        let pinned = game_state.pinned();
+
+        let white = self.team_score(game_state, true, &pinned) + self.attack_score(game_state, true);
+        let black = self.team_score(game_state, false, &pinned) + self.attack_score(game_state, false);
+
+        white - black
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game_state::parse_move_list;
+
+    #[test]
+    fn parses_weights() {
+        let weights = EvalWeights::parse("# Tuned\n\nmobility = 5\n  pinned=-3  \n").unwrap();
+        assert_eq!(weights, EvalWeights { mobility: 5, pinned: -3, ..EvalWeights::default() });
+        assert_eq!(EvalWeights::parse("").unwrap(), EvalWeights::default());
+
+        assert_eq!(EvalWeights::parse("mobility = 5\nspeed = 2"), Err("Line 2: unknown weight 'speed'".to_string()));
+        assert_eq!(EvalWeights::parse("mobility = fast"), Err("Line 1: invalid value 'fast'".to_string()));
+        assert_eq!(EvalWeights::parse("mobility = 2.5"), Err("Line 1: invalid value '2.5'".to_string()));
+        assert_eq!(EvalWeights::parse("mobility 5"), Err("Line 1: expected 'name = value'".to_string()));
+    }
+
+    #[test]
+    fn loads_weights() {
+        let path = std::env::temp_dir().join(format!("hive-weights-{}.txt", std::process::id()));
+        let path = path.to_str().unwrap();
+        fs::write(path, "queen_neighbours = 150\n").unwrap();
+        let loaded = EvalWeights::load(path);
+        fs::remove_file(path).unwrap();
+        assert_eq!(loaded, Ok(EvalWeights { queen_neighbours: 150, ..EvalWeights::default() }));
+        assert!(EvalWeights::load(path).unwrap_err().starts_with("Failed to read"));
+    }
+
+    #[test]
+    fn mirrored_position_negates_score() {
+        let moves = parse_move_list(
+            "Q@0,0 A@1,0 A@-1,1 A@1,1 A@-1,2 Q@1,2 A@-2,3 A@2,-1 -2,3>-1,3 1,2>0,3 A@-2,2 2,-1>1,-1 A@-2,1 A@1,-2"
+        ).unwrap();
+        let evaluator = DefaultEvaluator::default();
+        let mut game = GameState::new();
+        // Passing first gives Green every piece Cyan has in `game` and the other way round
+        let mut mirrored = GameState::new();
+        mirrored.pass();
+        for m in moves {
+            game.apply_move(m.clone());
+            mirrored.apply_move(m);
+            assert_eq!(evaluator.evaluate(&mirrored), -evaluator.evaluate(&game), "after {} moves", game.turn_count());
+        }
+        assert!(!mirrored.tiles()[&HexCoord::new(0, 0)].top().team());
+        assert_ne!(evaluator.evaluate(&game), 0);
+    }
+}
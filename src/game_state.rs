@@ -1,49 +1,203 @@
 use std::collections::{HashMap, HashSet};
-use std::hash::{DefaultHasher, Hash, Hasher};
-use color_print::cprint;
+use std::fmt::{self, Display, Formatter};
+use std::str::FromStr;
 use itertools::Itertools;
-use crate::hex_coord::HexCoord;
+use crate::colour;
+use crate::hex_coord::{HexCoord, SYMMETRIES};
 use crate::hive_tile::HiveTile;
-use crate::piece_bag::{PieceBag, STARTING_TOTAL};
+use crate::piece_bag::PieceBag;
 use crate::tile_types::TileType;
 
-#[derive(Clone)]
+#[derive(Clone, PartialEq, Eq, Hash, Debug, PartialOrd, Ord)]
 pub enum Move {
     Place(TileType, HexCoord),
     Move(HexCoord, HexCoord)
 }
 
+impl Display for Move {
+    /// Placements are written as `Q@0,0` and movements as `0,0>1,-1`
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Move::Place(tile_type, to) => write!(f, "{}@{}", tile_type.character(), to),
+            Move::Move(from, to) => write!(f, "{}>{}", from, to),
+        }
+    }
+}
+
+impl FromStr for Move {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if let Some((tile, to)) = s.split_once('@') {
+            let mut chars = tile.trim().chars();
+            let (Some(c), None) = (chars.next(), chars.next()) else {
+                return Err(format!("Invalid piece '{tile}'"));
+            };
+            let Some(tile_type) = TileType::from_character(c.to_ascii_uppercase()) else {
+                return Err(format!("Invalid piece '{tile}'"));
+            };
+            Ok(Move::Place(tile_type, to.parse()?))
+        }
+        else if let Some((from, to)) = s.split_once('>') {
+            Ok(Move::Move(from.parse()?, to.parse()?))
+        }
+        else {
+            Err(format!("Invalid move '{s}'"))
+        }
+    }
+}
+
+/// Parses a list of moves separated by whitespace or `;`. Anything after a `#` on a line is
+/// ignored.
+pub fn parse_move_list(s: &str) -> Result<Vec<Move>, String> {
+    s.lines()
+        .map(|l| l.split('#').next().unwrap())
+        .flat_map(|l| l.split(|c: char| c.is_whitespace() || c == ';'))
+        .filter(|m| !m.is_empty())
+        .map(str::parse)
+        .collect()
+}
+
+/// SplitMix64 finaliser
+fn mix(mut z: u64) -> u64 {
+    z = z.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+/// Bits identifying each level of a tile stack, to be combined with its location by `level_hash`
+fn stack_keys(tile: &HiveTile) -> impl Iterator<Item = u64> + '_ {
+    std::iter::successors(Some(tile), |t| t.above())
+        .enumerate()
+        .map(|(height, t)| ((height as u64) << 32) | ((t.tile_type().index() as u64) << 40) | ((t.team() as u64) << 48))
+}
+
+fn level_hash(loc: &HexCoord, stack_key: u64) -> u64 {
+    mix(stack_key | (loc.x as u16 as u64) | ((loc.y as u16 as u64) << 16))
+}
+
+/// Contribution of a whole stack to `GameState::get_hash`
+fn tile_hash(loc: &HexCoord, tile: &HiveTile) -> u64 {
+    stack_keys(tile).map(|k| level_hash(loc, k)).fold(0, u64::wrapping_add)
+}
+
+/// Maps the coordinates of a position onto those of its canonical form - see
+/// `GameState::canonical`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Orientation {
+    /// One of the `SYMMETRIES`, applied before translating
+    symmetry: usize,
+    /// Transformed coordinate that becomes the origin
+    origin: HexCoord,
+}
+
+impl Orientation {
+    pub fn apply(&self, c: &HexCoord) -> HexCoord {
+        c.transform(self.symmetry) - self.origin
+    }
+
+    pub fn invert(&self, c: &HexCoord) -> HexCoord {
+        (*c + self.origin).inverse_transform(self.symmetry)
+    }
+
+    pub fn apply_move(&self, m: &Move) -> Move {
+        match m {
+            Move::Place(tile_type, to) => Move::Place(*tile_type, self.apply(to)),
+            Move::Move(from, to) => Move::Move(self.apply(from), self.apply(to)),
+        }
+    }
+
+    pub fn invert_move(&self, m: &Move) -> Move {
+        match m {
+            Move::Place(tile_type, to) => Move::Place(*tile_type, self.invert(to)),
+            Move::Move(from, to) => Move::Move(self.invert(from), self.invert(to)),
+        }
+    }
+}
+
 pub type TileStore = HashMap<HexCoord, HiveTile>;
 
-#[derive(Clone)]
+#[derive(Clone, PartialEq, Eq, Debug)]
 pub struct GameState {
     turn_count: usize,
     tiles: TileStore,
     pieces: (PieceBag, PieceBag),
-    queen_location: (Option<HexCoord>, Option<HexCoord>)
+    queen_location: (Option<HexCoord>, Option<HexCoord>),
+    /// Sum of `tile_hash` over the tiles, kept up to date as moves are made
+    tiles_hash: u64,
+}
+
+impl Default for GameState {
+    fn default() -> Self {
+        GameState::new()
+    }
 }
 
 impl GameState {
     pub fn new() -> GameState {
+        GameState::with_pieces(PieceBag::new())
+    }
+
+    /// Creates a game where each player starts with the given pieces
+    pub fn with_pieces(pieces: PieceBag) -> GameState {
         GameState {
             turn_count: 0,
-            tiles: HashMap::with_capacity(STARTING_TOTAL * 2),
-            pieces: (PieceBag::new(), PieceBag::new()),
+            tiles: HashMap::with_capacity(pieces.count() * 2),
+            pieces: (pieces.clone(), pieces),
             queen_location: (None, None),
+            tiles_hash: 0,
         }
     }
 
+    /// Order-independent hash of the tiles and side to move, updated incrementally so it's cheap
+    /// enough for every search node. Unlike `canonical`, symmetric positions hash differently.
     pub fn get_hash(&self) -> u64 {
-        let mut hash = 0u64;
-        for (loc, tile) in &self.tiles {
-            let mut h = DefaultHasher::new();
-            (loc, tile).hash(&mut h);
-            hash = hash.wrapping_add(h.finish());
-        }
-        if self.turn() {
-            hash += 1;
+        self.tiles_hash.wrapping_add(self.turn() as u64)
+    }
+
+    /// Hash that is the same for every rotation, reflection and translation of the position,
+    /// along with the orientation mapping this position onto the canonical form it describes.
+    /// Of the `SYMMETRIES`, the canonical form is the one with the lowest hash once translated
+    /// so that its lowest coordinate is the origin.
+    ///
+    /// Built on a fixed mixing function rather than `DefaultHasher` so that hashes stay the same
+    /// between builds, as opening books are saved keyed by them.
+    pub fn canonical(&self) -> (u64, Orientation) {
+        let levels = self.tiles.iter()
+            .flat_map(|(loc, tile)| stack_keys(tile).map(move |k| (*loc, k)))
+            .collect::<Vec<_>>();
+        let mut transformed = levels.clone();
+
+        let mut best: Option<(u64, Orientation)> = None;
+        for symmetry in 0..SYMMETRIES {
+            // Each symmetry is one rotation on from the last, apart from the first reflection
+            if symmetry == 6 {
+                for ((c, _), (loc, _)) in transformed.iter_mut().zip(&levels) {
+                    *c = loc.reflect();
+                }
+            }
+            else if symmetry != 0 {
+                for (c, _) in transformed.iter_mut() {
+                    *c = c.rotate(1);
+                }
+            }
+
+            let origin = transformed.iter().map(|(c, _)| c).min().copied().unwrap_or_default();
+            let hash = transformed.iter()
+                .map(|(c, k)| level_hash(&(*c - origin), *k))
+                .fold(0u64, u64::wrapping_add)
+                .wrapping_add(self.turn() as u64);
+            if best.as_ref().is_none_or(|(b, _)| hash < *b) {
+                best = Some((hash, Orientation { symmetry, origin }));
+            }
         }
-        hash
+        best.unwrap()
+    }
+
+    pub fn canonical_hash(&self) -> u64 {
+        self.canonical().0
     }
 
     pub fn turn_count(&self) -> usize { self.turn_count }
@@ -72,71 +226,55 @@ impl GameState {
         (x_min - 2, y_min - 1, x_max + 2, y_max + 1)
     }
 
+    /// Prints the board on a grid where each hexagon takes up two columns. The axes are
+    /// labelled with `HexCoord::to_square` coordinates, which are anchored at the first piece
+    /// placed so don't shift as the hive grows.
     pub fn print(&self) {
         let (x_min, y_min, x_max, y_max) = self.get_square_bounds();
 
-        let mut temp_row = Vec::with_capacity((x_max - x_min) as usize + 1);
-        for _ in 0..((x_max - x_min) + 1) {
-            temp_row.push(None);
-        }
-        let mut grid = Vec::with_capacity((y_max - y_min) as usize + 1);
-        for _ in 0..((y_max - y_min) + 1) {
-            grid.push(temp_row.clone());
-        }
-
-        for (loc, tile) in &self.tiles {
-            let (x, y) = loc.to_square();
-            grid[(y - y_min) as usize][(x - x_min) as usize] = Some(tile);
-        }
-
-        for (y, row) in grid.iter().enumerate().rev() {
-            print!("{: >2}|  ", y + 1);
-            for (x, cell) in row.iter().enumerate() {
-                if let Some(tile) = cell {
-                    if tile.team() {
-                        cprint!("<c>{}</>", tile.tile_type().character())
-                    }
-                    else {
-                        cprint!("<g>{}</>", tile.tile_type().character())
-                    }
+        for y in (y_min..=y_max).rev() {
+            print!("{y: >3}|  ");
+            for x in x_min..=x_max {
+                if let Some(tile) = HexCoord::try_from_square(x, y).and_then(|c| self.tiles.get(&c)) {
+                    print!("{}", colour::team(tile.team(), tile.top().tile_type().character()));
+                }
+                else if HexCoord::try_from_square(x, y).is_some() {
+                    print!("{}", colour::dim('•'));
                 }
                 else {
-                    if (x + y + y_min.abs() as usize + x_min.abs() as usize) % 2 == 0 {
-                        cprint!("<dim>•</>");
-                    }
-                    else {
-                        print!(" ");
-                    }
+                    print!(" ");
                 }
                 print!(" ");
             }
             println!();
         }
 
-        print!("     ");
-        let len = (x_max - x_min) + 1;
-        for x in 0..len {
-            let xt = if x + 1 >= 10 { (x + 1) / 10 } else { x + 1 };
-            print!("{} ", xt);
-        }
-        println!();
-        if len >= 10 {
-            print!("     ");
-            for x in 0..len {
-                if x + 1 >= 10 {
-                    print!("{} ", (x + 1) % 10);
-                }
-                else {
-                    print!("  ");
-                }
+        // Column numbers are written downwards so that each fits in its column
+        let labels = (x_min..=x_max).map(|x| x.to_string()).collect::<Vec<_>>();
+        let height = labels.iter().map(|l| l.len()).max().unwrap_or(0);
+        for i in 0..height {
+            print!("      ");
+            for label in &labels {
+                let padding = height - label.len();
+                print!("{} ", if i < padding { ' ' } else { label.as_bytes()[i - padding] as char });
             }
-            println!()
+            println!();
         }
-
     }
 
     pub fn turn(&self) -> bool {
-        self.turn_count % 2 == 0
+        self.turn_count.is_multiple_of(2)
+    }
+
+    pub fn tiles(&self) -> &TileStore { &self.tiles }
+
+    pub fn piece_bag(&self, team: bool) -> &PieceBag {
+        if team {
+            &self.pieces.0
+        }
+        else {
+            &self.pieces.1
+        }
     }
 
     pub fn turn_piece_bag(&self) -> &PieceBag {
@@ -148,6 +286,33 @@ impl GameState {
         }
     }
 
+    pub fn queen_location(&self, team: bool) -> Option<&HexCoord> {
+        if team {
+            self.queen_location.0.as_ref()
+        }
+        else {
+            self.queen_location.1.as_ref()
+        }
+    }
+
+    /// Number of the team's pieces of a type on the board, including any covered in stacks
+    fn placed_count(&self, team: bool, tile_type: TileType) -> usize {
+        self.tiles.values()
+            .flat_map(|t| std::iter::successors(Some(t), |t| t.above()))
+            .filter(|t| t.team() == team && t.tile_type() == tile_type)
+            .count()
+    }
+
+    /// The team's pieces on top of the hive with their locations, sorted by type then ordinal
+    pub fn pieces_on_board(&self, team: bool) -> Vec<(&HiveTile, HexCoord)> {
+        let mut pieces = self.tiles.iter()
+            .map(|(loc, tile)| (tile.top(), *loc))
+            .filter(|(tile, _)| tile.team() == team)
+            .collect::<Vec<_>>();
+        pieces.sort_by_key(|(tile, _)| (tile.tile_type(), tile.ordinal()));
+        pieces
+    }
+
     fn turn_piece_bag_mut(&mut self) -> &mut PieceBag {
         if self.turn() {
             &mut self.pieces.0
@@ -157,7 +322,9 @@ impl GameState {
         }
     }
 
-    fn force_queen(&self) -> bool {
+    /// Whether the side to move has to place their queen if they haven't yet - on their
+    /// fourth turn
+    pub fn force_queen(&self) -> bool {
         self.turn_count == 6 || self.turn_count == 7
     }
 
@@ -188,7 +355,7 @@ impl GameState {
         locations
     }
 
-    pub fn get_possible_moves(&mut self) -> Vec<Move> {
+    pub fn get_possible_moves(&self) -> Vec<Move> {
         let placeable = self.turn_piece_bag().get_place_options(self.force_queen());
 
         if self.turn_count == 0 {
@@ -206,31 +373,116 @@ impl GameState {
 
         for loc in placeable_locations {
             for tile in &placeable {
-                moves.push(Move::Place(*tile, loc.clone()));
+                moves.push(Move::Place(*tile, loc));
             }
         }
 
-        if (self.turn() && self.queen_location.0.is_none()) || (!self.turn() && self.queen_location.1.is_none()) {
+        if self.queen_location(self.turn()).is_none() {
+            return moves;
+        }
+
+        moves.extend(self.get_movements(self.turn()));
+
+        moves
+    }
+
+    /// Gets all piece movements available to `team`, ignoring whose turn it is
+    pub fn get_movements(&self, team: bool) -> Vec<Move> {
+        self.get_movements_pinned(team, &self.pinned())
+    }
+
+    /// Same as `get_movements` but using already computed pinned pieces
+    pub fn get_movements_pinned(&self, team: bool, pinned: &HashSet<HexCoord>) -> Vec<Move> {
+        let mut moves = Vec::new();
+        if self.queen_location(team).is_none() {
             return moves;
         }
 
         for (loc, tile) in &self.tiles {
-            if tile.team() != self.turn() {
+            if tile.team() != team || pinned.contains(loc) {
                 continue;
             }
 
             let possible_moves = tile.tile_type().get_moves(loc, &self.tiles);
 
             for m in possible_moves {
-                let allowed = !self.is_broken(loc, &m);
+                // The rest of the hive is connected as the piece isn't pinned, so the move is
+                // only allowed if the piece rejoins it
+                let allowed = m.surrounding().iter().any(|s| s != loc && self.tiles.contains_key(s));
                 if !allowed { continue; }
-                moves.push(Move::Move(loc.clone(), m));
+                moves.push(Move::Move(*loc, m));
             }
         }
 
         moves
     }
 
+    /// Gets the pieces that cannot be moved without splitting the hive i.e. the articulation
+    /// points of the hive, found with an iterative Tarjan's algorithm
+    pub fn pinned(&self) -> HashSet<HexCoord> {
+        let mut pinned = HashSet::new();
+        if self.tiles.len() < 3 {
+            return pinned;
+        }
+
+        let locations = self.tiles.keys().collect_vec();
+        let indices: HashMap<&HexCoord, usize> = locations.iter().enumerate().map(|(i, l)| (*l, i)).collect();
+        let adjacent = locations.iter()
+            .map(|l| l.surrounding().iter().filter_map(|s| indices.get(s).copied()).collect_vec())
+            .collect_vec();
+
+        const UNVISITED: usize = usize::MAX;
+        let mut discovered = vec![UNVISITED; locations.len()];
+        let mut low = vec![0; locations.len()];
+        let mut parent = vec![UNVISITED; locations.len()];
+        let mut is_pinned = vec![false; locations.len()];
+
+        let root = 0;
+        let mut root_children = 0;
+        let mut timer = 1;
+        discovered[root] = 0;
+        // (vertex, index of next neighbour to visit)
+        let mut stack = vec![(root, 0)];
+
+        while let Some((v, next)) = stack.last_mut() {
+            let v = *v;
+            if let Some(&u) = adjacent[v].get(*next) {
+                *next += 1;
+                if discovered[u] == UNVISITED {
+                    parent[u] = v;
+                    discovered[u] = timer;
+                    low[u] = timer;
+                    timer += 1;
+                    if v == root {
+                        root_children += 1;
+                    }
+                    stack.push((u, 0));
+                }
+                else if u != parent[v] {
+                    low[v] = low[v].min(discovered[u]);
+                }
+            }
+            else {
+                stack.pop();
+                if let Some(&(p, _)) = stack.last() {
+                    low[p] = low[p].min(low[v]);
+                    if p != root && low[v] >= discovered[p] {
+                        is_pinned[p] = true;
+                    }
+                }
+            }
+        }
+        is_pinned[root] = root_children > 1;
+
+        for (i, location) in locations.into_iter().enumerate() {
+            if is_pinned[i] {
+                pinned.insert(*location);
+            }
+        }
+
+        pinned
+    }
+
     fn set_queen_location(&mut self, location: HexCoord) {
         if self.turn() {
             self.queen_location.0 = Some(location);
@@ -253,16 +505,20 @@ impl GameState {
         match to_move {
             Move::Place(tile_type, location) => {
                 if matches!(tile_type, TileType::Queen) {
-                    self.set_queen_location(location.clone());
+                    self.set_queen_location(location);
                 }
                 self.turn_piece_bag_mut().use_piece(tile_type);
-                self.tiles.insert(location, HiveTile::new(self.turn(), tile_type));
+                let ordinal = self.placed_count(self.turn(), tile_type) + 1;
+                let tile = HiveTile::new(self.turn(), tile_type, ordinal);
+                self.tiles_hash = self.tiles_hash.wrapping_add(tile_hash(&location, &tile));
+                self.tiles.insert(location, tile);
             }
             Move::Move(from, to) => {
                 let removed = self.tiles.remove(&from).unwrap();
                 if matches!(&removed.tile_type(), TileType::Queen) {
-                    self.set_queen_location(to.clone());
+                    self.set_queen_location(to);
                 }
+                self.tiles_hash = self.tiles_hash.wrapping_sub(tile_hash(&from, &removed)).wrapping_add(tile_hash(&to, &removed));
                 self.tiles.insert(to, removed);
             }
         };
@@ -276,49 +532,91 @@ impl GameState {
                 if matches!(tile_type, TileType::Queen) {
                     self.unset_queen_location();
                 }
-                self.tiles.remove(&location);
+                let removed = self.tiles.remove(&location).unwrap();
+                self.tiles_hash = self.tiles_hash.wrapping_sub(tile_hash(&location, &removed));
                 self.turn_piece_bag_mut().unuse_piece(tile_type);
             }
             Move::Move(from, to) => {
                 let removed = self.tiles.remove(&to).unwrap();
                 if matches!(&removed.tile_type(), TileType::Queen) {
-                    self.set_queen_location(from.clone());
+                    self.set_queen_location(from);
                 }
+                self.tiles_hash = self.tiles_hash.wrapping_sub(tile_hash(&to, &removed)).wrapping_add(tile_hash(&from, &removed));
                 self.tiles.insert(from, removed);
             }
         };
     }
 
-    fn is_broken(&self, moved_from: &HexCoord, moved_to: &HexCoord) -> bool {
+    /// Applies each move in turn, checking that it is legal and passing for any player left
+    /// without moves
+    pub fn apply_moves(&mut self, moves: &[Move]) -> Result<(), String> {
+        for (i, m) in moves.iter().enumerate() {
+            let (_, white_win, black_win) = self.score();
+            if white_win || black_win {
+                return Err(format!("Move {} ({m}) is after the end of the game", i + 1));
+            }
 
-        let mut visited = HashSet::with_capacity(self.tiles.len());
-        let mut locs = self.tiles.keys();
-        let mut start = locs.next().unwrap();
-        if start == moved_from {
-            if let Some(s) = locs.next() {
-                start = s;
+            let mut possible = self.get_possible_moves();
+            if possible.is_empty() {
+                self.pass();
+                possible = self.get_possible_moves();
             }
-            else {
-                return false;
+            if !possible.contains(m) {
+                return Err(format!("Move {} ({m}) is illegal", i + 1));
             }
+            self.apply_move(m.clone());
         }
-
-        visited.insert(start.clone());
-        self.ant(start.clone(), &mut visited, moved_from, moved_to);
-        debug_assert!(visited.len() <= self.tiles.len());
-        visited.len() < self.tiles.len()
+        Ok(())
     }
 
-    fn ant(&self, location: HexCoord, visited: &mut HashSet<HexCoord>, moved_from: &HexCoord, moved_to: &HexCoord) {
-        for surround in location.surrounding() {
-            if &surround == moved_from {
-                continue;
-            }
+    /// Counts the leaf nodes of the move tree `depth` moves deep. Passing counts as a move when
+    /// no others are available, and finished games have no moves.
+    pub fn perft(&mut self, depth: usize) -> u64 {
+        if depth == 0 {
+            return 1;
+        }
+        let (_, white_win, black_win) = self.score();
+        if white_win || black_win {
+            return 0;
+        }
 
-            if (self.tiles.contains_key(&surround) || &surround == moved_to) && visited.insert(surround.clone()) {
-                self.ant(surround, visited, moved_from, moved_to);
-            }
+        let moves = self.get_possible_moves();
+        if moves.is_empty() {
+            self.pass();
+            let count = self.perft(depth - 1);
+            self.unpass();
+            return count;
+        }
+        if depth == 1 {
+            return moves.len() as u64;
+        }
+
+        let mut count = 0;
+        for m in moves {
+            self.apply_move(m.clone());
+            count += self.perft(depth - 1);
+            self.undo_move(m);
+        }
+        count
+    }
+
+    /// Runs perft for each move available, giving the count below each
+    pub fn perft_divide(&mut self, depth: usize) -> Vec<(Move, u64)> {
+        let (_, white_win, black_win) = self.score();
+        if depth == 0 || white_win || black_win {
+            return Vec::new();
         }
+
+        let mut moves = self.get_possible_moves();
+        moves.sort();
+        moves.into_iter()
+            .map(|m| {
+                self.apply_move(m.clone());
+                let count = self.perft(depth - 1);
+                self.undo_move(m.clone());
+                (m, count)
+            })
+            .collect()
     }
 
     pub fn pass(&mut self) {
@@ -334,7 +632,7 @@ impl GameState {
         let mut black_win = false;
         if let Some(queen_location) = &self.queen_location.0 {
             for surround in queen_location.surrounding() {
-                if self.tiles.get(&surround).is_some() {
+                if self.tiles.contains_key(&surround) {
                     white_surroundings += 1;
                 }
             }
@@ -350,7 +648,7 @@ impl GameState {
         let mut white_win = false;
         if let Some(queen_location) = &self.queen_location.1 {
             for surround in queen_location.surrounding() {
-                if self.tiles.get(&surround).is_some() {
+                if self.tiles.contains_key(&surround) {
                     black_surroundings += 1;
                 }
             }
@@ -364,4 +662,364 @@ impl GameState {
 
         (black_surroundings - white_surroundings, white_win, black_win)
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MIDGAME: &str = "A@0,0 A@1,0 Q@-1,0 Q@2,0 A@-1,1 A@3,-1";
+
+    /// Counts recorded from this move generator rather than an independent reference, to catch
+    /// unintended changes. Ants currently move one space like queens, so these need updating
+    /// along with any rules change.
+    const REGRESSION_START: [u64; 7] = [1, 2, 4, 22, 121, 910, 6656];
+    const REGRESSION_MIDGAME: [u64; 5] = [1, 10, 89, 1015, 11344];
+
+    fn from_moves(moves: &str) -> GameState {
+        let mut game = GameState::new();
+        game.apply_moves(&parse_move_list(moves).unwrap()).unwrap();
+        game
+    }
+
+    /// Builds a position directly from `(x, y, team, tile)`, with Cyan to move
+    fn from_tiles(tiles: &[(isize, isize, bool, TileType)]) -> GameState {
+        let mut game = GameState::new();
+        for (x, y, team, tile_type) in tiles {
+            let location = HexCoord::new(*x, *y);
+            if matches!(tile_type, TileType::Queen) {
+                if *team { game.queen_location.0 = Some(location) } else { game.queen_location.1 = Some(location) }
+            }
+            if *team { game.pieces.0.use_piece(*tile_type) } else { game.pieces.1.use_piece(*tile_type) }
+            let ordinal = game.placed_count(*team, *tile_type) + 1;
+            let tile = HiveTile::new(*team, *tile_type, ordinal);
+            game.tiles_hash = game.tiles_hash.wrapping_add(tile_hash(&location, &tile));
+            game.tiles.insert(location, tile);
+        }
+        // Past the opening so placement and queen rules apply normally
+        game.turn_count = 8;
+        game
+    }
+
+    fn movements_from(game: &GameState, x: isize, y: isize) -> Vec<HexCoord> {
+        let from = HexCoord::new(x, y);
+        let mut destinations = game.get_possible_moves().into_iter()
+            .filter_map(|m| match m {
+                Move::Move(f, t) if f == from => Some(t),
+                _ => None,
+            })
+            .collect_vec();
+        destinations.sort();
+        destinations
+    }
+
+    #[test]
+    fn perft_start_regression() {
+        let mut game = GameState::new();
+        for (depth, count) in REGRESSION_START.into_iter().enumerate() {
+            assert_eq!(game.perft(depth), count, "depth {depth}");
+        }
+    }
+
+    #[test]
+    fn perft_midgame_regression() {
+        let mut game = from_moves(MIDGAME);
+        for (depth, count) in REGRESSION_MIDGAME.into_iter().enumerate() {
+            assert_eq!(game.perft(depth), count, "depth {depth}");
+        }
+    }
+
+    #[test]
+    fn perft_divide_sums_to_perft() {
+        let mut game = from_moves(MIDGAME);
+        for depth in 1..4 {
+            let divided: u64 = game.perft_divide(depth).iter().map(|(_, c)| c).sum();
+            assert_eq!(divided, game.perft(depth), "depth {depth}");
+        }
+    }
+
+    #[test]
+    fn perft_restores_position() {
+        let mut game = from_moves(MIDGAME);
+        let before = game.clone();
+        game.perft(3);
+        assert_eq!(game, before);
+        assert_eq!(game.turn_count(), 6);
+    }
+
+    #[test]
+    fn opening_placements_fixed() {
+        let game = GameState::new();
+        let mut moves = game.get_possible_moves();
+        moves.sort();
+        assert_eq!(moves, vec![
+            Move::Place(TileType::Queen, HexCoord::new(0, 0)),
+            Move::Place(TileType::Ant, HexCoord::new(0, 0)),
+        ]);
+
+        let game = from_moves("A@0,0");
+        assert!(game.get_possible_moves().iter().all(|m| matches!(m, Move::Place(_, l) if l == &HexCoord::new(1, 0))));
+    }
+
+    #[test]
+    fn placements_not_adjacent_to_enemy() {
+        let game = from_moves("A@0,0 A@1,0");
+        let mut locations = game.get_possible_moves().into_iter()
+            .map(|m| match m {
+                Move::Place(_, l) => l,
+                Move::Move(_, _) => panic!("no movement before the queen is placed"),
+            })
+            .unique()
+            .collect_vec();
+        locations.sort();
+        assert_eq!(locations, vec![HexCoord::new(-1, 0), HexCoord::new(-1, 1), HexCoord::new(0, -1)]);
+    }
+
+    #[test]
+    fn queen_forced_by_fourth_placement() {
+        let game = from_moves("A@0,0 A@1,0 A@-1,0 A@2,0 A@-2,0 A@3,0");
+        assert_eq!(game.turn_count(), 6);
+        assert!(game.get_possible_moves().iter().all(|m| matches!(m, Move::Place(TileType::Queen, _))));
+    }
+
+    #[test]
+    fn no_movement_before_queen() {
+        let game = from_moves("Q@0,0 A@1,0 A@-1,0 A@2,0");
+        // Cyan's queen is placed, Green's isn't
+        assert!(game.get_possible_moves().iter().any(|m| matches!(m, Move::Move(_, _))));
+        let game = from_moves("Q@0,0 A@1,0 A@-1,0");
+        assert!(game.get_possible_moves().iter().all(|m| matches!(m, Move::Place(_, _))));
+    }
+
+    #[test]
+    fn pinned_piece_cannot_move() {
+        // Line of three with the Cyan queen in the middle
+        let game = from_tiles(&[
+            (-1, 0, true, TileType::Ant),
+            (0, 0, true, TileType::Queen),
+            (1, 0, false, TileType::Queen),
+        ]);
+        assert_eq!(game.pinned(), HashSet::from([HexCoord::new(0, 0)]));
+        assert!(movements_from(&game, 0, 0).is_empty());
+        assert!(!movements_from(&game, -1, 0).is_empty());
+    }
+
+    #[test]
+    fn gate_blocks_sliding() {
+        // Ring of pieces around (0,1), which the Cyan queen at the origin borders
+        let game = from_tiles(&[
+            (0, 0, true, TileType::Queen),
+            (1, 0, true, TileType::Ant),
+            (1, 1, true, TileType::Ant),
+            (0, 2, false, TileType::Queen),
+            (-1, 2, false, TileType::Ant),
+            (-1, 1, false, TileType::Ant),
+        ]);
+        // (0,1) is between (1,0) and (-1,1) so the queen can't slide into it
+        assert_eq!(movements_from(&game, 0, 0), vec![HexCoord::new(-1, 0), HexCoord::new(1, -1)]);
+    }
+
+    #[test]
+    fn movement_keeps_hive_connected() {
+        let game = from_tiles(&[
+            (0, 0, true, TileType::Queen),
+            (1, 0, false, TileType::Queen),
+        ]);
+        // Only the two spaces touching the Green queen keep the hive together
+        assert_eq!(movements_from(&game, 0, 0), vec![HexCoord::new(0, 1), HexCoord::new(1, -1)]);
+    }
+
+    #[test]
+    fn move_notation_round_trips() {
+        for m in from_moves(MIDGAME).get_possible_moves() {
+            assert_eq!(m.to_string().parse::<Move>(), Ok(m));
+        }
+        assert!("X@0,0".parse::<Move>().is_err());
+        assert!("0,0>1".parse::<Move>().is_err());
+    }
+
+    #[test]
+    fn canonical_ignores_symmetry_and_translation() {
+        let moves = parse_move_list(MIDGAME).unwrap();
+        let game = from_moves(MIDGAME);
+        let (hash, orientation) = game.canonical();
+        let canonical_moves = game.get_possible_moves().iter().map(|m| orientation.apply_move(m)).collect::<HashSet<_>>();
+
+        for symmetry in 0..SYMMETRIES {
+            for (dx, dy) in [(0, 0), (3, -7), (-20, 11)] {
+                let map = |c: &HexCoord| {
+                    let t = c.transform(symmetry);
+                    HexCoord::new(t.x + dx, t.y + dy)
+                };
+                // Opening placements are fixed so the moves can't be validated
+                let mut moved = GameState::new();
+                for m in &moves {
+                    match m {
+                        Move::Place(tile_type, to) => moved.apply_move(Move::Place(*tile_type, map(to))),
+                        Move::Move(from, to) => moved.apply_move(Move::Move(map(from), map(to))),
+                    }
+                }
+
+                let (moved_hash, moved_orientation) = moved.canonical();
+                assert_eq!(moved_hash, hash, "symmetry {symmetry} offset {dx},{dy}");
+                let moved_moves = moved.get_possible_moves().iter().map(|m| moved_orientation.apply_move(m)).collect::<HashSet<_>>();
+                assert_eq!(moved_moves, canonical_moves, "symmetry {symmetry} offset {dx},{dy}");
+            }
+        }
+    }
+
+    #[test]
+    fn canonical_distinguishes_positions() {
+        let game = from_moves(MIDGAME);
+        let (hash, orientation) = game.canonical();
+        for m in game.get_possible_moves() {
+            assert_eq!(orientation.invert_move(&orientation.apply_move(&m)), m);
+        }
+
+        let mut passed = game.clone();
+        passed.pass();
+        assert_ne!(passed.canonical_hash(), hash);
+        assert_ne!(from_moves("A@0,0 A@1,0 Q@-1,0 Q@2,0 A@-1,1 A@2,-1").canonical_hash(), hash);
+        let swapped = from_tiles(&[
+            (0, 0, false, TileType::Ant),
+            (1, 0, true, TileType::Ant),
+            (-1, 0, false, TileType::Queen),
+            (2, 0, true, TileType::Queen),
+            (-1, 1, false, TileType::Ant),
+            (3, -1, true, TileType::Ant),
+        ]);
+        assert_ne!(swapped.canonical_hash(), hash);
+    }
+
+    #[test]
+    fn hash_follows_moves() {
+        let mut game = from_moves(MIDGAME);
+        let before = game.get_hash();
+        for m in game.get_possible_moves() {
+            game.apply_move(m.clone());
+            let from_scratch = game.tiles.iter().map(|(l, t)| tile_hash(l, t)).fold(0, u64::wrapping_add);
+            assert_eq!(game.get_hash(), from_scratch.wrapping_add(game.turn() as u64), "{m}");
+            game.undo_move(m);
+            assert_eq!(game.get_hash(), before);
+        }
+
+        // Reached by either move order
+        let transposed = from_moves("A@0,0 A@1,0 Q@-1,0 Q@2,0 A@-1,1 A@3,-1");
+        assert_eq!(from_moves("A@0,0 A@1,0 A@-1,1 Q@2,0 Q@-1,0 A@3,-1").get_hash(), transposed.get_hash());
+        let mut passed = transposed.clone();
+        passed.pass();
+        assert_ne!(passed.get_hash(), transposed.get_hash());
+    }
+
+    /// Flood fills from any tile, returning whether every tile was reached
+    fn is_connected(game: &GameState) -> bool {
+        let Some(start) = game.tiles.keys().next() else { return true };
+        let mut visited = HashSet::from([*start]);
+        let mut stack = vec![*start];
+        while let Some(location) = stack.pop() {
+            for surround in location.surrounding() {
+                if game.tiles.contains_key(&surround) && visited.insert(surround) {
+                    stack.push(surround);
+                }
+            }
+        }
+        visited.len() == game.tiles.len()
+    }
+
+    fn check_invariants(game: &GameState, start: &PieceBag) {
+        assert!(is_connected(game), "hive split: {game:?}");
+
+        for team in [true, false] {
+            for i in 0..TileType::VARIANT_COUNT {
+                let tile_type = TileType::from_index(i).unwrap();
+                let on_board: usize = game.tiles.values()
+                    .map(|t| {
+                        assert!(t.height() >= 1);
+                        let mut count = 0;
+                        let mut tile = Some(t);
+                        while let Some(current) = tile {
+                            if current.team() == team && current.tile_type() == tile_type {
+                                count += 1;
+                            }
+                            tile = current.above();
+                        }
+                        count
+                    })
+                    .sum();
+                assert_eq!(
+                    on_board + game.piece_bag(team).count_of(tile_type),
+                    start.count_of(tile_type),
+                    "{tile_type:?} count wrong for team {team}: {game:?}"
+                );
+            }
+
+            let queen = game.tiles.iter()
+                .find(|(_, t)| t.team() == team && matches!(t.tile_type(), TileType::Queen))
+                .map(|(l, _)| l);
+            assert_eq!(game.queen_location(team), queen);
+        }
+    }
+
+    /// Plays random games, checking that every move can be undone exactly and that the
+    /// position stays valid throughout
+    fn random_games(pieces: &str, games: u64, max_turns: usize) {
+        use rand::rngs::StdRng;
+        use rand::{Rng, SeedableRng};
+
+        let start = PieceBag::parse(pieces).unwrap();
+        for seed in 0..games {
+            let mut rng = StdRng::seed_from_u64(seed);
+            let mut game = GameState::with_pieces(start.clone());
+            // `None` for passes
+            let mut history = Vec::new();
+
+            while game.turn_count() < max_turns {
+                let (_, white_win, black_win) = game.score();
+                if white_win || black_win {
+                    break;
+                }
+
+                let mut moves = game.get_possible_moves();
+                moves.sort();
+                let before = game.clone();
+                let key = game.canonical();
+
+                let Some(m) = (!moves.is_empty()).then(|| moves.swap_remove(rng.gen_range(0..moves.len()))) else {
+                    game.pass();
+                    game.unpass();
+                    assert_eq!(game, before);
+                    game.pass();
+                    history.push(None);
+                    continue;
+                };
+
+                game.apply_move(m.clone());
+                check_invariants(&game, &start);
+                game.undo_move(m.clone());
+                assert_eq!(game, before, "undoing {m} (seed {seed})");
+                assert_eq!(game.canonical(), key, "undoing {m} (seed {seed})");
+
+                game.apply_move(m.clone());
+                history.push(Some(m));
+            }
+
+            for m in history.into_iter().rev() {
+                match m {
+                    Some(m) => game.undo_move(m),
+                    None => game.unpass(),
+                }
+            }
+            assert_eq!(game, GameState::with_pieces(start.clone()), "unwinding game (seed {seed})");
+        }
+    }
+
+    #[test]
+    fn random_games_reversible() {
+        random_games("Q1A6", 40, 150);
+    }
+
+    #[test]
+    fn random_games_reversible_small_set() {
+        random_games("Q1A2", 40, 100);
+    }
+}
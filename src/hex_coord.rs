@@ -1,11 +1,105 @@
-use derive_getters::Getters;
+use std::fmt::{self, Display, Formatter};
+use std::ops::{Add, AddAssign, Mul, Neg, Sub, SubAssign};
+use std::str::FromStr;
 
-#[derive(Hash, Eq, PartialEq, Clone, Debug)]
+/// Number of symmetries of the hex grid about a point - 6 rotations, each optionally reflected
+pub const SYMMETRIES: usize = 12;
+
+/// Axial coordinates of a hexagon. Neighbours differ by one of the `Direction` offsets, and on
+/// the board `y` increases upwards with each row shifted half a hexagon right of the one below.
+#[derive(Hash, Eq, PartialEq, Clone, Copy, Debug, PartialOrd, Ord, Default)]
 pub struct HexCoord {
     pub x: isize,
     pub y: isize
 }
 
+/// Cube coordinates of a hexagon, where `q + r + s == 0`. `q` and `r` match the axial `x` and `y`.
+#[derive(Hash, Eq, PartialEq, Clone, Copy, Debug)]
+pub struct CubeCoord {
+    pub q: isize,
+    pub r: isize,
+    pub s: isize,
+}
+
+impl CubeCoord {
+    /// Rounds fractional cube coordinates to the hexagon containing them
+    pub fn round(q: f64, r: f64, s: f64) -> CubeCoord {
+        let (mut rq, mut rr, mut rs) = (q.round(), r.round(), s.round());
+        let (dq, dr, ds) = ((rq - q).abs(), (rr - r).abs(), (rs - s).abs());
+        // Recalculate the coordinate with the largest rounding error from the other two
+        if dq > dr && dq > ds {
+            rq = -rr - rs;
+        }
+        else if dr > ds {
+            rr = -rq - rs;
+        }
+        else {
+            rs = -rq - rr;
+        }
+        CubeCoord { q: rq as isize, r: rr as isize, s: rs as isize }
+    }
+}
+
+impl From<HexCoord> for CubeCoord {
+    fn from(c: HexCoord) -> CubeCoord {
+        CubeCoord { q: c.x, r: c.y, s: -c.x - c.y }
+    }
+}
+
+impl From<CubeCoord> for HexCoord {
+    fn from(c: CubeCoord) -> HexCoord {
+        debug_assert_eq!(c.q + c.r + c.s, 0);
+        HexCoord::new(c.q, c.r)
+    }
+}
+
+/// Directions to each neighbour of a hexagon, in order around it
+#[derive(Hash, Eq, PartialEq, Clone, Copy, Debug, PartialOrd, Ord)]
+pub enum Direction {
+    East,
+    SouthEast,
+    SouthWest,
+    West,
+    NorthWest,
+    NorthEast,
+}
+
+impl Direction {
+    pub const ALL: [Direction; 6] = [
+        Direction::East,
+        Direction::SouthEast,
+        Direction::SouthWest,
+        Direction::West,
+        Direction::NorthWest,
+        Direction::NorthEast,
+    ];
+
+    pub fn index(self) -> usize {
+        self as usize
+    }
+
+    pub fn offset(self) -> HexCoord {
+        match self {
+            Direction::East => HexCoord::new(1, 0),
+            Direction::SouthEast => HexCoord::new(1, -1),
+            Direction::SouthWest => HexCoord::new(0, -1),
+            Direction::West => HexCoord::new(-1, 0),
+            Direction::NorthWest => HexCoord::new(-1, 1),
+            Direction::NorthEast => HexCoord::new(0, 1),
+        }
+    }
+
+    /// Rotates 60 degrees `turns` times, in the same direction as `HexCoord::rotate`
+    pub fn rotate(self, turns: usize) -> Direction {
+        Direction::ALL[(self.index() + turns) % 6]
+    }
+
+    #[allow(dead_code)]
+    pub fn opposite(self) -> Direction {
+        self.rotate(3)
+    }
+}
+
 impl HexCoord {
     pub fn new(x: isize, y: isize) -> HexCoord {
         HexCoord {
@@ -14,38 +108,410 @@ impl HexCoord {
         }
     }
 
-    pub fn to_square(&self) -> (isize, isize) {
+    /// Position on the printed grid, where each hexagon takes up two columns
+    pub fn to_square(self) -> (isize, isize) {
         ((self.x * 2) + self.y, self.y)
     }
 
+    /// Hexagon covering a grid square - the square from `to_square` or the one to its right.
+    /// Uses Euclidean division so that negative columns round the same way as positive ones.
     pub fn from_square(x: isize, y: isize) -> HexCoord {
         HexCoord {
-            x: (x - y) / 2,
+            x: (x - y).div_euclid(2),
             y
         }
     }
 
+    /// Inverse of `to_square`, `None` for squares that aren't the first column of a hexagon
     pub fn try_from_square(x: isize, y: isize) -> Option<HexCoord> {
-        if (x - y) % 2 != 0 {
+        if (x - y).rem_euclid(2) != 0 {
             return None
         }
 
-        Some(HexCoord {
-            x: (x - y) / 2,
-            y
-        })
-    }
-
-    pub fn surrounding(&self) -> [HexCoord; 6] {
-        let x = self.x;
-        let y = self.y;
-        [
-            HexCoord::new(x, y + 1),
-            HexCoord::new(x - 1, y + 1),
-            HexCoord::new(x + 1, y),
-            HexCoord::new(x + 1, y - 1),
-            HexCoord::new(x, y - 1),
-            HexCoord::new(x - 1, y),
-        ]
-    }
-}
\ No newline at end of file
+        Some(HexCoord::from_square(x, y))
+    }
+
+    pub fn neighbor(self, direction: Direction) -> HexCoord {
+        self + direction.offset()
+    }
+
+    /// Neighbours in order around the hexagon, so that consecutive coordinates are adjacent
+    pub fn surrounding(self) -> [HexCoord; 6] {
+        Direction::ALL.map(|d| self.neighbor(d))
+    }
+
+    /// Rotates 60 degrees about the origin `turns` times, in the same direction as `surrounding`
+    pub fn rotate(self, turns: usize) -> HexCoord {
+        let (x, y) = (self.x, self.y);
+        match turns % 6 {
+            0 => HexCoord::new(x, y),
+            1 => HexCoord::new(x + y, -x),
+            2 => HexCoord::new(y, -x - y),
+            3 => HexCoord::new(-x, -y),
+            4 => HexCoord::new(-x - y, x),
+            _ => HexCoord::new(-y, x + y),
+        }
+    }
+
+    /// Reflects in the line through the origin along the x axis
+    pub fn reflect(self) -> HexCoord {
+        HexCoord::new(self.x + self.y, -self.y)
+    }
+
+    /// Applies one of the `SYMMETRIES` - `symmetry % 6` rotations, preceded by a reflection for
+    /// symmetries 6 and above
+    pub fn transform(self, symmetry: usize) -> HexCoord {
+        if symmetry >= 6 {
+            self.reflect().rotate(symmetry - 6)
+        }
+        else {
+            self.rotate(symmetry)
+        }
+    }
+
+    /// Undoes `transform(symmetry)`
+    pub fn inverse_transform(self, symmetry: usize) -> HexCoord {
+        if symmetry >= 6 {
+            // Reflections are their own inverse
+            self.transform(symmetry)
+        }
+        else {
+            self.rotate(6 - symmetry)
+        }
+    }
+}
+
+/// Grid geometry for piece movement and rendering
+impl HexCoord {
+    pub fn to_cube(self) -> CubeCoord {
+        self.into()
+    }
+
+    /// Direction of an adjacent coordinate, `None` if not adjacent
+    pub fn direction_to(self, other: HexCoord) -> Option<Direction> {
+        Direction::ALL.into_iter().find(|d| self.neighbor(*d) == other)
+    }
+
+    /// Number of steps from the origin
+    pub fn length(self) -> usize {
+        let c = self.to_cube();
+        (c.q.unsigned_abs() + c.r.unsigned_abs() + c.s.unsigned_abs()) / 2
+    }
+
+    /// Number of steps between two coordinates
+    pub fn distance(self, other: HexCoord) -> usize {
+        (self - other).length()
+    }
+}
+
+/// Lines and areas of the grid, for pieces that move further than one space, such as a
+/// Grasshopper jumping along a line or a Spider walking around a ring. Queens and Ants only step.
+#[allow(dead_code)]
+impl HexCoord {
+    /// Coordinates in a straight line in the given direction, not including this one
+    pub fn line(self, direction: Direction) -> impl Iterator<Item = HexCoord> {
+        std::iter::successors(Some(self.neighbor(direction)), move |c| Some(c.neighbor(direction)))
+    }
+
+    /// Coordinates on the shortest straight path to `other`, including both ends
+    pub fn line_to(self, other: HexCoord) -> Vec<HexCoord> {
+        let distance = self.distance(other);
+        let (a, b) = (self.to_cube(), other.to_cube());
+        // Nudged so that points exactly between two hexagons always round the same way
+        let lerp = |a: isize, b: isize, t: f64, nudge: f64| a as f64 + (b - a) as f64 * t + nudge;
+        (0..=distance)
+            .map(|i| {
+                let t = if distance == 0 { 0.0 } else { i as f64 / distance as f64 };
+                CubeCoord::round(lerp(a.q, b.q, t, 1e-6), lerp(a.r, b.r, t, 2e-6), lerp(a.s, b.s, t, -3e-6)).into()
+            })
+            .collect()
+    }
+
+    /// Coordinates exactly `radius` steps away, in order around the ring starting from the
+    /// north-west corner. A radius of 0 gives this coordinate alone.
+    pub fn ring(self, radius: usize) -> Vec<HexCoord> {
+        if radius == 0 {
+            return vec![self];
+        }
+        let mut ring = Vec::with_capacity(6 * radius);
+        let mut current = self + Direction::NorthWest.offset() * radius as isize;
+        for direction in Direction::ALL {
+            for _ in 0..radius {
+                ring.push(current);
+                current = current.neighbor(direction);
+            }
+        }
+        ring
+    }
+
+    /// Coordinates at most `radius` steps away, nearest first
+    pub fn range(self, radius: usize) -> Vec<HexCoord> {
+        (0..=radius).flat_map(|r| self.ring(r)).collect()
+    }
+}
+
+impl Add for HexCoord {
+    type Output = HexCoord;
+
+    fn add(self, other: HexCoord) -> HexCoord {
+        HexCoord::new(self.x + other.x, self.y + other.y)
+    }
+}
+
+impl Sub for HexCoord {
+    type Output = HexCoord;
+
+    fn sub(self, other: HexCoord) -> HexCoord {
+        HexCoord::new(self.x - other.x, self.y - other.y)
+    }
+}
+
+impl Neg for HexCoord {
+    type Output = HexCoord;
+
+    fn neg(self) -> HexCoord {
+        HexCoord::new(-self.x, -self.y)
+    }
+}
+
+impl Mul<isize> for HexCoord {
+    type Output = HexCoord;
+
+    fn mul(self, scale: isize) -> HexCoord {
+        HexCoord::new(self.x * scale, self.y * scale)
+    }
+}
+
+impl AddAssign for HexCoord {
+    fn add_assign(&mut self, other: HexCoord) {
+        *self = *self + other;
+    }
+}
+
+impl SubAssign for HexCoord {
+    fn sub_assign(&mut self, other: HexCoord) {
+        *self = *self - other;
+    }
+}
+
+impl Display for HexCoord {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{},{}", self.x, self.y)
+    }
+}
+
+impl FromStr for HexCoord {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let Some((x, y)) = s.split_once(',') else {
+            return Err(format!("Invalid coordinate '{s}'"));
+        };
+        let (Ok(x), Ok(y)) = (x.trim().parse(), y.trim().parse()) else {
+            return Err(format!("Invalid coordinate '{s}'"));
+        };
+        Ok(HexCoord::new(x, y))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::hash_map::Entry;
+    use std::collections::{HashMap, HashSet};
+    use super::*;
+
+    /// Every coordinate within `size` on both axes
+    fn grid(size: isize) -> impl Iterator<Item = HexCoord> {
+        (-size..=size).flat_map(move |x| (-size..=size).map(move |y| HexCoord::new(x, y)))
+    }
+
+    #[test]
+    fn transforms_are_symmetries() {
+        for c in grid(5) {
+            assert_eq!(c.rotate(6), c);
+            assert_eq!(c.reflect().reflect(), c);
+            for symmetry in 0..SYMMETRIES {
+                let t = c.transform(symmetry);
+                assert_eq!(t.inverse_transform(symmetry), c, "{c} symmetry {symmetry}");
+                assert_eq!(t.length(), c.length());
+                // Neighbours stay neighbours
+                for s in c.surrounding() {
+                    assert!(t.surrounding().contains(&s.transform(symmetry)));
+                }
+            }
+        }
+        // Every symmetry is distinct
+        let images = (0..SYMMETRIES).map(|s| (HexCoord::new(2, 1).transform(s), HexCoord::new(1, 0).transform(s))).collect::<HashSet<_>>();
+        assert_eq!(images.len(), SYMMETRIES);
+        assert_eq!(HexCoord::default().transform(7), HexCoord::default());
+    }
+
+    #[test]
+    fn square_round_trip() {
+        for c in grid(200) {
+            let (x, y) = c.to_square();
+            assert_eq!(HexCoord::from_square(x, y), c);
+            assert_eq!(HexCoord::try_from_square(x, y), Some(c));
+            // Second column of the hexagon
+            assert_eq!(HexCoord::from_square(x + 1, y), c);
+            assert_eq!(HexCoord::try_from_square(x + 1, y), None);
+            assert_eq!(HexCoord::try_from_square(x - 1, y), None);
+        }
+        for y in -50..=50 {
+            // Squares to the right never map to hexagons further left
+            let mut last = HexCoord::from_square(-101, y);
+            for x in -100..=100 {
+                let c = HexCoord::from_square(x, y);
+                assert!(c.x == last.x || c.x == last.x + 1, "{x},{y}");
+                assert!(c.to_square().0 == x || c.to_square().0 == x - 1, "{x},{y}");
+                last = c;
+            }
+        }
+    }
+
+    #[test]
+    fn cube_round_trip() {
+        for c in grid(10) {
+            let cube = c.to_cube();
+            assert_eq!(cube.q + cube.r + cube.s, 0);
+            assert_eq!(HexCoord::from(cube), c);
+            assert_eq!(CubeCoord::round(cube.q as f64 + 0.2, cube.r as f64 - 0.3, cube.s as f64 + 0.1), cube);
+        }
+    }
+
+    #[test]
+    fn operators() {
+        for a in grid(4) {
+            assert_eq!(-(-a), a);
+            assert_eq!(a * 3, a + a + a);
+            assert_eq!(a * -1, -a);
+            for b in grid(3) {
+                assert_eq!(a + b, b + a);
+                assert_eq!(a + b - b, a);
+                assert_eq!(a - b, -(b - a));
+                let mut c = a;
+                c += b;
+                assert_eq!(c, a + b);
+                c -= b;
+                assert_eq!(c, a);
+            }
+        }
+    }
+
+    #[test]
+    fn directions() {
+        let origin = HexCoord::default();
+        assert_eq!(origin.surrounding().to_vec(), Direction::ALL.map(Direction::offset).to_vec());
+        for d in Direction::ALL {
+            assert_eq!(Direction::ALL[d.index()], d);
+            assert_eq!(d.opposite().offset(), -d.offset());
+            assert_eq!(d.opposite().opposite(), d);
+            assert_eq!(d.offset().length(), 1);
+            for turns in 0..12 {
+                assert_eq!(d.rotate(turns).offset(), d.offset().rotate(turns));
+            }
+            // Consecutive directions are adjacent
+            assert_eq!(d.offset().distance(d.rotate(1).offset()), 1);
+        }
+        for c in grid(3) {
+            for d in Direction::ALL {
+                assert_eq!(c.neighbor(d) - c, d.offset());
+                assert_eq!(c.direction_to(c.neighbor(d)), Some(d));
+                assert_eq!(c.neighbor(d).neighbor(d.opposite()), c);
+            }
+            assert_eq!(c.direction_to(c), None);
+            assert_eq!(c.direction_to(c + HexCoord::new(2, -1)), None);
+        }
+    }
+
+    #[test]
+    fn distance_is_a_metric() {
+        for a in grid(4) {
+            assert_eq!(a.distance(a), 0);
+            assert_eq!(a.distance(HexCoord::default()), a.length());
+            for b in grid(4) {
+                let d = a.distance(b);
+                assert_eq!(d, b.distance(a));
+                assert_eq!((a + HexCoord::new(3, -5)).distance(b + HexCoord::new(3, -5)), d);
+                assert_eq!(d == 1, a.surrounding().contains(&b));
+                for c in grid(2) {
+                    assert!(a.distance(c) <= d + b.distance(c));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn distance_matches_steps() {
+        // Breadth first search from the origin
+        let mut steps = HashMap::from([(HexCoord::default(), 0)]);
+        let mut frontier = vec![HexCoord::default()];
+        for step in 1..=8 {
+            let mut next = Vec::new();
+            for c in frontier {
+                for n in c.surrounding() {
+                    if let Entry::Vacant(e) = steps.entry(n) {
+                        e.insert(step);
+                        next.push(n);
+                    }
+                }
+            }
+            frontier = next;
+        }
+        for (c, step) in steps {
+            assert_eq!(c.length(), step, "{c}");
+        }
+    }
+
+    #[test]
+    fn lines() {
+        for c in grid(3) {
+            for d in Direction::ALL {
+                for (i, l) in c.line(d).take(10).enumerate() {
+                    assert_eq!(l, c + d.offset() * (i as isize + 1));
+                    assert_eq!(l.distance(c), i + 1);
+                }
+            }
+            for other in grid(4) {
+                let line = c.line_to(other);
+                assert_eq!(line.len(), c.distance(other) + 1);
+                assert_eq!(line.first(), Some(&c));
+                assert_eq!(line.last(), Some(&other));
+                for (i, l) in line.iter().enumerate() {
+                    assert_eq!(l.distance(c), i);
+                    assert_eq!(l.distance(other), line.len() - 1 - i);
+                }
+            }
+            // Straight lines match the rays
+            for d in Direction::ALL {
+                assert_eq!(c.line_to(c + d.offset() * 5)[1..], c.line(d).take(5).collect::<Vec<_>>());
+            }
+        }
+    }
+
+    #[test]
+    fn rings_and_ranges() {
+        for c in [HexCoord::default(), HexCoord::new(3, -2), HexCoord::new(-7, 4)] {
+            assert_eq!(c.ring(0), vec![c]);
+            for radius in 1..=6 {
+                let ring = c.ring(radius);
+                assert_eq!(ring.len(), 6 * radius);
+                assert_eq!(ring.iter().collect::<HashSet<_>>().len(), ring.len());
+                assert!(ring.iter().all(|r| r.distance(c) == radius));
+                // In order around the ring
+                for (i, r) in ring.iter().enumerate() {
+                    assert_eq!(r.distance(ring[(i + 1) % ring.len()]), 1);
+                }
+            }
+            for radius in 0..=6 {
+                let range = c.range(radius);
+                assert_eq!(range.len(), 3 * radius * (radius + 1) + 1);
+                let expected = grid(20)
+                    .map(|g| g + c)
+                    .filter(|g| g.distance(c) <= radius)
+                    .collect::<HashSet<_>>();
+                assert_eq!(range.into_iter().collect::<HashSet<_>>(), expected);
+            }
+        }
+    }
+}
@@ -1,31 +1,58 @@
 use crate::tile_types::TileType;
 
-#[derive(Clone, Hash)]
+#[derive(Clone, Hash, PartialEq, Eq, Debug)]
 pub struct HiveTile {
     team: bool,
     tile_type: TileType,
+    /// Order the piece was placed in among its team's pieces of the same type, starting at 1
+    ordinal: usize,
     above: Option<Box<HiveTile>>
 }
 
 impl HiveTile {
-    pub fn new(team: bool, tile_type: TileType) -> HiveTile {
+    pub fn new(team: bool, tile_type: TileType, ordinal: usize) -> HiveTile {
         HiveTile {
             team,
             tile_type,
+            ordinal,
             above: None,
         }
     }
 
     pub fn top(&self) -> &HiveTile {
-        if let Some(above) = &self.above {
+        if let Some(above) = self.above() {
             above.top()
         }
         else {
-            &self
+            self
         }
     }
 
+    pub fn height(&self) -> usize {
+        if let Some(above) = self.above() {
+            above.height() + 1
+        }
+        else {
+            1
+        }
+    }
+
+    pub fn above(&self) -> Option<&HiveTile> {
+        self.above.as_deref()
+    }
+
     pub fn team(&self) -> bool { self.team }
 
     pub fn tile_type(&self) -> TileType { self.tile_type }
-}
\ No newline at end of file
+
+    pub fn ordinal(&self) -> usize { self.ordinal }
+
+    /// Identifies the piece among its team's pieces, e.g. `A2` for the second ant placed. The
+    /// queen is unique so is just `Q`.
+    pub fn label(&self) -> String {
+        match self.tile_type {
+            TileType::Queen => self.tile_type.character().to_string(),
+            _ => format!("{}{}", self.tile_type.character(), self.ordinal),
+        }
+    }
+}
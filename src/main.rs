@@ -1,382 +1,520 @@
-use std::collections::HashMap;
-use std::io::{stdin, stdout, Write};
-use itertools::Itertools;
-use rand::{random, Rng, thread_rng};
-use game_state::GameState;
-use crate::game_state::Move;
-use crate::hex_coord::HexCoord;
-use crate::tile_types::TileType;
+use std::collections::HashSet;
+use std::fs;
+use std::io::{IsTerminal, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+use game_state::{GameState, Move};
+use crate::annotate::PlayerSummary;
+use crate::api::Api;
+use crate::book::OpeningBook;
+use crate::cli::{Command, GameSource, GlobalOptions};
+use crate::net::ServerOptions;
+use crate::player::{create_player, PlayerOptions};
+use crate::puzzle::{Answer, Attempt, Puzzle};
+use crate::search::{format_score, Searcher};
+use crate::session::{team_name, GameRecord, Outcome, Session};
+use crate::svg::SvgOptions;
+use crate::tournament::TournamentConfig;
 
 mod hex_coord;
 mod hive_tile;
 mod piece_bag;
 mod game_state;
 mod tile_types;
-
-fn player_select(game_state: &mut GameState) {
-    let moves = game_state.get_possible_moves();
-    if moves.len() == 0 {
-        println!("Passing turn - no moves available");
-        game_state.pass();
-        return;
-    }
-
-    let mut movements = Vec::new();
-    let mut placements = Vec::new();
-    for m in moves {
-        match m {
-            Move::Place(tile, to) => {
-                placements.push((tile, to));
-            }
-            Move::Move(from, to) => {
-                movements.push((from, to));
-            }
+mod search;
+mod evaluator;
+mod transposition;
+mod mcts;
+mod engine;
+mod player;
+mod colour;
+mod session;
+mod cli;
+mod tournament;
+mod book;
+mod render;
+mod svg;
+mod tui;
+mod rules;
+mod annotate;
+mod puzzle;
+mod net;
+mod api;
+
+fn play(global: &GlobalOptions, white: &str, black: &str, start: &[Move], max_turns: Option<usize>, record: Option<&str>) -> Result<(), String> {
+    let mut game = GameState::with_pieces(global.pieces.clone());
+    game.apply_moves(start)?;
+
+    let options = PlayerOptions { seed: global.seed, verbose: true, book: global.book.clone() };
+    let mut white = create_player(white, &options)?;
+    // Offset so that two random players with the same seed don't mirror each other
+    let mut black = create_player(black, &PlayerOptions { seed: global.seed.map(|s| s.wrapping_add(1)), ..options })?;
+
+    let mut session = Session::new(game);
+    session.set_max_turns(max_turns);
+    let outcome = session.play(white.as_mut(), black.as_mut());
+
+    if let Some(path) = record {
+        // Records start from the initial position rather than the session's
+        let mut game_record = session.record(outcome);
+        for variation in &mut game_record.variations {
+            variation.ply += start.len();
         }
+        game_record.moves.splice(0..0, start.iter().cloned());
+        save_record(path, &game_record)?;
     }
+    Ok(())
+}
 
-    let mut first_loop = true;
-    let mut error = None;
+/// Appends the record to the file at `path`
+fn save_record(path: &str, record: &GameRecord) -> Result<(), String> {
+    let mut file = fs::OpenOptions::new().create(true).append(true).open(path)
+        .map_err(|e| format!("Failed to open '{path}': {e}"))?;
+    writeln!(file, "{record}").map_err(|e| format!("Failed to write '{path}': {e}"))?;
+    println!("Saved game to {path}");
+    Ok(())
+}
 
-    loop {
-        if !first_loop {
-            game_state.print();
-            println!();
-            if let Some(e) = error {
-                println!("Error: {e}");
-                error = None;
-            }
-        }
-        first_loop = false;
+fn serve(global: &GlobalOptions, port: u16, start: &[Move], max_turns: Option<usize>, record: Option<&str>) -> Result<(), String> {
+    let listener = TcpListener::bind(("0.0.0.0", port)).map_err(|e| format!("Failed to listen on port {port}: {e}"))?;
+    println!("Waiting for players on port {port}");
 
-        print!("To Place: ");
-        game_state.turn_piece_bag().print(game_state.turn());
+    let options = ServerOptions { start: start.to_vec(), max_turns, verbose: true };
+    let game_record = net::serve(listener, global.pieces.clone(), &options)?;
+    if let Some(path) = record {
+        save_record(path, &game_record)?;
+    }
+    Ok(())
+}
 
-        if placements.is_empty() {
-            println!("X| No placements available");
-        }
-        else {
-            println!("1| Place piece");
-        }
+fn serve_api(global: &GlobalOptions, port: u16, threads: Option<usize>, allow_origin: Option<String>) -> Result<(), String> {
+    let listener = TcpListener::bind(("127.0.0.1", port)).map_err(|e| format!("Failed to listen on port {port}: {e}"))?;
+    let threads = threads.unwrap_or_else(search::default_threads);
+    println!("Serving the API at http://localhost:{port}/");
+    api::serve(listener, Arc::new(Api::new(global.pieces.clone(), threads, allow_origin)));
+    Ok(())
+}
 
-        if movements.is_empty() {
-            println!("X| No movements available");
-        }
-        else {
-            println!("2| Move piece");
-        }
+fn connect(global: &GlobalOptions, address: &str, player: &str) -> Result<(), String> {
+    let mut player = create_player(player, &PlayerOptions { seed: global.seed, verbose: true, book: global.book.clone() })?;
+    let stream = TcpStream::connect(address).map_err(|e| format!("Failed to connect to {address}: {e}"))?;
+    println!("Connected to {address}");
+    let outcome = net::connect(stream, player.as_mut(), true)?;
+    println!("{}", outcome.describe());
+    Ok(())
+}
 
-        print!("> ");
-        stdout().flush().unwrap();
-        let mut input = String::new();
-        stdin().read_line(&mut input).unwrap();
-
-        let Ok(c) = input.trim().parse::<usize>() else {
-            error = Some("Invalid selection");
-            continue;
-        };
-
-        fn coordinate_parser(input: &str) -> Option<(usize, usize)> {
-            let input = input.trim();
-            let mut split = input.split(' ');
-            let Some(x) = split.next().and_then(|s| s.parse().ok()) else { return None };
-            let Some(y) = split.next().and_then(|s| s.parse().ok()) else { return None };
-            if split.next().is_some() { return None; }
-            Some((x, y))
-        }
+fn analyse(global: &GlobalOptions, position: &[Move], engine: &str) -> Result<(), String> {
+    let mut game = GameState::with_pieces(global.pieces.clone());
+    game.apply_moves(position)?;
+    render::print_board(&game);
 
-        if c == 1 && !placements.is_empty() {
-            println!("Select piece to place");
-            print!("> ");
-            stdout().flush().unwrap();
-
-            let mut input = String::new();
-            stdin().read_line(&mut input).unwrap();
-            let input = input.trim();
-            if input.chars().count() != 1 {
-                error = Some("Invalid piece name");
-                continue;
-            }
-            let c = input.chars().next().unwrap().to_ascii_uppercase();
-            let Some(piece) = TileType::from_character(c) else {
-                error = Some("Invalid piece name");
-                continue;
-            };
-            if !placements.iter().any(|(t, _)| t == &piece) {
-                error = Some("No available placements for given piece");
-                continue;
-            }
+    if let Some(outcome) = Session::new(game.clone()).outcome() {
+        println!("{}", outcome.describe());
+        return Ok(());
+    }
+    println!("Turn: {} [{}]\n", team_name(game.turn()), game.turn_count());
 
-            if game_state.turn_count() == 0 {
-                game_state.apply_move(Move::Place(piece, HexCoord::new(0, 0)));
-                return;
-            }
-            else if game_state.turn_count() == 1 {
-                game_state.apply_move(Move::Place(piece, HexCoord::new(1, 0)));
-                return;
-            }
+    let mut engine = create_player(engine, &PlayerOptions { seed: global.seed, verbose: true, book: global.book.clone() })?;
+    if game.get_possible_moves().is_empty() {
+        println!("No moves available - must pass");
+        return Ok(());
+    }
+    if let Some(m) = engine.choose_move(&game) {
+        println!("Best move: {m}");
+    }
+    Ok(())
+}
 
-            println!("Enter coordinates to place at - 'x y' e.g. '3 1'");
-            print!("> ");
-            stdout().flush().unwrap();
-            let mut input = String::new();
-            stdin().read_line(&mut input).unwrap();
-
-            let Some((x, y)) = coordinate_parser(&input) else {
-                error = Some("Invalid coordinates");
-                continue;
-            };
-
-            let bounds = game_state.get_square_bounds();
-            let x = x as isize + bounds.0 - 1;
-            let y = y as isize + bounds.1 - 1;
-            let Some(hex_coordinates) = HexCoord::try_from_square(x, y) else {
-                error = Some("Invalid coordinates");
-                continue;
-            };
-
-            if !placements.iter().any(|(t, p)| t == &piece && p == &hex_coordinates) {
-                error = Some("Piece cannot be placed at specified location");
-                continue;
+fn render_position(global: &GlobalOptions, position: &[Move], svg: Option<&str>, coordinates: bool, line: &[Move], highlight: bool) -> Result<(), String> {
+    let mut game = GameState::with_pieces(global.pieces.clone());
+    game.apply_moves(position)?;
+
+    let Some(path) = svg else {
+        render::print_board(&game);
+        return Ok(());
+    };
+    let options = SvgOptions {
+        coordinates,
+        highlight: if highlight { position.last().cloned() } else { None },
+        line: line.to_vec(),
+    };
+    let image = svg::render_svg(&game, &options)?;
+    fs::write(path, image).map_err(|e| format!("Failed to write '{path}': {e}"))?;
+    println!("Wrote {path}");
+    Ok(())
+}
+
+fn analysis(global: &GlobalOptions, position: &[Move], lines: usize, depth: Option<usize>, time: Option<f64>, threads: Option<usize>) -> Result<(), String> {
+    let mut game = GameState::with_pieces(global.pieces.clone());
+    game.apply_moves(position)?;
+    render::print_board(&game);
+
+    if let Some(outcome) = Session::new(game.clone()).outcome() {
+        println!("{}", outcome.describe());
+        return Ok(());
+    }
+    println!("Turn: {} [{}]\n", team_name(game.turn()), game.turn_count());
+
+    let stop = Arc::new(AtomicBool::new(false));
+    if let Some(seconds) = time {
+        let stop = stop.clone();
+        thread::spawn(move || {
+            thread::sleep(Duration::from_secs_f64(seconds));
+            stop.store(true, Ordering::Relaxed);
+        });
+    }
+    {
+        let stop = stop.clone();
+        thread::spawn(move || {
+            // Ignores input ending, e.g. when not run from a terminal
+            if std::io::stdin().read_line(&mut String::new()).is_ok_and(|n| n > 0) {
+                stop.store(true, Ordering::Relaxed);
             }
-            game_state.apply_move(Move::Place(piece, hex_coordinates));
-            return;
+        });
+    }
+    println!("Press Enter to stop\n");
+
+    let mut searcher = Searcher::new();
+    searcher.set_threads(threads.unwrap_or_else(search::default_threads));
+    searcher.analyse(&game, lines, depth, &stop, |analysis| {
+        println!(
+            "Depth {} - {} nodes, {} nodes/s, {:.2}s",
+            analysis.depth, analysis.nodes, analysis.nodes_per_second(), analysis.elapsed.as_secs_f64()
+        );
+        for (i, line) in analysis.lines.iter().enumerate() {
+            let moves = line.moves.iter().map(|m| m.to_string()).collect::<Vec<_>>();
+            println!("{:>4}. {:>10}  {}", i + 1, format_score(line.score), moves.join(" "));
         }
-        else if c == 2 && !movements.is_empty() {
-            println!("Enter coordinates to move from - 'x y' e.g. '3 1'");
-            print!("> ");
-            stdout().flush().unwrap();
-            let mut input = String::new();
-            stdin().read_line(&mut input).unwrap();
-
-            let Some((x, y)) = coordinate_parser(&input) else {
-                error = Some("Invalid coordinates");
-                continue;
-            };
-            let bounds = game_state.get_square_bounds();
-            let x = x as isize + bounds.0 - 1;
-            let y = y as isize + bounds.1 - 1;
-            let Some(from_coords) = HexCoord::try_from_square(x, y) else {
-                error = Some("Invalid coordinates");
-                continue;
-            };
-            if !movements.iter().any(|(f, _)| f == &from_coords) {
-                error = Some("No valid moves originating from the given position");
-                continue;
-            }
+        println!();
+    });
+    Ok(())
+}
 
-            println!("Enter coordinates to move to - 'x y' e.g. '3 1'");
-            print!("> ");
-            stdout().flush().unwrap();
-            let mut input = String::new();
-            stdin().read_line(&mut input).unwrap();
-
-            let Some((x, y)) = coordinate_parser(&input) else {
-                error = Some("Invalid coordinates");
-                continue;
-            };
-            let bounds = game_state.get_square_bounds();
-            let x = x as isize + bounds.0 - 1;
-            let y = y as isize + bounds.1 - 1;
-            let Some(to_coords) = HexCoord::try_from_square(x, y) else {
-                error = Some("Invalid coordinates");
-                continue;
-            };
-            if !movements.iter().any(|(_, t)| t == &to_coords) {
-                error = Some("Invalid move");
-                continue;
-            }
+fn perft(global: &GlobalOptions, depth: usize, position: &[Move], divide: bool) -> Result<(), String> {
+    let mut game = GameState::with_pieces(global.pieces.clone());
+    game.apply_moves(position)?;
 
-            game_state.apply_move(Move::Move(from_coords, to_coords));
-            return;
+    let start = Instant::now();
+    let count = if divide && depth > 0 {
+        let counts = game.perft_divide(depth);
+        for (m, count) in &counts {
+            println!("{m}: {count}");
         }
+        println!();
+        // Passing is the only move when none are available
+        if counts.is_empty() { game.perft(depth) } else { counts.iter().map(|(_, c)| c).sum() }
     }
+    else {
+        game.perft(depth)
+    };
+    let elapsed = start.elapsed();
+    println!("Nodes: {count}");
+    println!("Time: {:.3}s", elapsed.as_secs_f64());
+    Ok(())
 }
 
-fn computer_select(game_state: &mut GameState, global_hash: &mut HashMap<u64, (isize, usize)>) {
-    let moves = game_state.get_possible_moves();
-    if moves.len() == 0 {
-        println!("Passing turn - no moves available");
-        game_state.pass();
-        return;
-    }
-
-    if game_state.turn() {
-        let len = moves.len();
-        game_state.apply_move(moves.into_iter().nth(thread_rng().gen_range(0..len)).unwrap());
-        return;
+fn selfplay(global: &GlobalOptions, games: usize, white: &str, black: &str, max_turns: Option<usize>) -> Result<(), String> {
+    let options = PlayerOptions { seed: global.seed, verbose: false, book: global.book.clone() };
+    let mut white_player = create_player(white, &options)?;
+    let mut black_player = create_player(black, &PlayerOptions { seed: global.seed.map(|s| s.wrapping_add(1)), ..options })?;
+
+    let (mut white_wins, mut black_wins, mut draws) = (0, 0, 0);
+    for i in 0..games {
+        let mut session = Session::new(GameState::with_pieces(global.pieces.clone()));
+        session.set_verbose(false);
+        session.set_max_turns(max_turns);
+
+        let outcome = session.play(white_player.as_mut(), black_player.as_mut());
+        match outcome {
+            Outcome::Win(true) => white_wins += 1,
+            Outcome::Win(false) => black_wins += 1,
+            Outcome::Draw => draws += 1,
+            Outcome::Ended => return Err(format!("Game {} ended early", i + 1)),
+        }
+        println!("Game {}: {} ({} turns)", i + 1, outcome.describe(), session.game().turn_count());
     }
 
-    let mut counter: u64 = 0;
-
-    const MAX_DEPTH: usize = 6;
-
-    let best_move = moves.into_iter()
-        .fold(
-            (if game_state.turn() { isize::MIN } else { isize::MAX }, Move::Move(HexCoord::new(0, 0), HexCoord::new(0, 0))), // Move never used
-            |(best_score, current_m), m| {
-                let turn = game_state.turn();
-                game_state.apply_move(m.clone());
-
-                let score = if turn {
-                    minimax(game_state, 0, MAX_DEPTH, best_score, isize::MAX, global_hash, &mut counter)
-                }
-                else {
-                    minimax(game_state, 0, MAX_DEPTH, isize::MIN, best_score, global_hash, &mut counter)
-                };
-
-                if game_state.turn_count() > 7 {
-                    global_hash.insert(game_state.get_hash(), (score, MAX_DEPTH + 1));
-                }
-
-                game_state.undo_move(m.clone());
+    println!();
+    println!("{} ({white}) wins: {white_wins}", team_name(true));
+    println!("{} ({black}) wins: {black_wins}", team_name(false));
+    println!("Draws: {draws}");
+    Ok(())
+}
 
-                // LE / GE necessary to prevent default move from being used
-                if (game_state.turn() && score >= best_score) || (!game_state.turn() && score <= best_score) {
-                    (score, m)
-                }
-                else {
-                    (best_score, current_m)
+/// Calls `each` with every game from the source, playing the games first for self-play
+fn for_each_game(global: &GlobalOptions, source: &GameSource, mut each: impl FnMut(&GameRecord) -> Result<(), String>) -> Result<(), String> {
+    match source {
+        GameSource::Records(files) => {
+            for file in files {
+                let contents = fs::read_to_string(file).map_err(|e| format!("Failed to read '{file}': {e}"))?;
+                let mut games = 0;
+                for (i, line) in contents.lines().enumerate() {
+                    let record = GameRecord::parse(line).map_err(|e| format!("{file} line {}: {e}", i + 1))?;
+                    if record.moves.is_empty() {
+                        continue;
+                    }
+                    each(&record).map_err(|e| format!("{file} line {}: {e}", i + 1))?;
+                    games += 1;
                 }
+                println!("Imported {games} games from {file}");
             }
-        ).1;
+        }
+        GameSource::SelfPlay { games, white, black, max_turns } => {
+            for i in 0..*games {
+                // Fresh players with varied seeds so that games differ
+                let options = PlayerOptions {
+                    seed: global.seed.map(|s| s.wrapping_add(i as u64 * 2)),
+                    verbose: false,
+                    book: global.book.clone(),
+                };
+                let mut white_player = create_player(white, &options)?;
+                let mut black_player = create_player(black, &PlayerOptions { seed: options.seed.map(|s| s.wrapping_add(1)), ..options.clone() })?;
 
-    println!("Searched: {counter}");
+                let mut session = Session::new(GameState::with_pieces(global.pieces.clone()));
+                session.set_verbose(false);
+                session.set_max_turns(*max_turns);
+                let outcome = session.play(white_player.as_mut(), black_player.as_mut());
+                println!("Game {}: {} ({} turns)", i + 1, outcome.describe(), session.game().turn_count());
 
-    debug_assert!(
-        match &best_move {
-            Move::Place(_, _) => { true }
-            Move::Move(a, b) => {
-                a != b
+                each(&session.record(outcome)).map_err(|e| format!("Game {}: {e}", i + 1))?;
             }
         }
-    );
+    }
+    Ok(())
+}
 
-    game_state.apply_move(best_move);
+/// Adds games to the book at `output`, creating it if needed
+fn build_book(global: &GlobalOptions, source: &GameSource, output: &str, max_plies: usize) -> Result<(), String> {
+    let mut book = if Path::new(output).is_file() { OpeningBook::load(output)? } else { OpeningBook::new() };
+    let start = GameState::with_pieces(global.pieces.clone());
 
-    println!("Score: {}", game_state.score().0);
-}
+    for_each_game(global, source, |record| book.add_game(&start, record, max_plies))?;
 
-fn get_score(score: (isize, bool, bool)) -> isize {
-    if score.1 && score.2 {
-        return 0;
-    }
-    if score.1 {
-        return isize::MAX;
-    }
-    else if score.2 {
-        return isize::MIN;
-    }
-    score.0
+    book.save(output)?;
+    println!("Saved {output} ({} positions)", book.position_count());
+    Ok(())
 }
 
-fn minimax(current_state: &mut GameState, depth: usize, max_depth: usize, mut alpha: isize, mut beta: isize, global_hash: &mut HashMap<u64, (isize, usize)>, counter: &mut u64) -> isize {
-    *counter += 1;
-    let turn = current_state.turn();
-    let moves = current_state.get_possible_moves();
-
-    if moves.len() == 0 {
-        current_state.pass();
-        let hash = current_state.get_hash();
-        let found = global_hash.get(&hash);
-        let score = if found.is_some() && found.unwrap().1 >= max_depth - depth {
-            found.unwrap().0
-        }
-        else {
-            if depth == max_depth {
-                // Evaluate board at final depth
-                get_score(current_state.score())
-            }
-            else {
-                let score = minimax(current_state, depth + 1, max_depth, alpha, beta, global_hash, counter);
-                if current_state.turn_count() > 7 {
-                    global_hash.insert(hash, (score, max_depth - depth));
-                }
-                score
+/// Appends the puzzles found in games to `output`
+fn generate_puzzles(global: &GlobalOptions, source: &GameSource, output: &str, max_moves: usize, threads: Option<usize>) -> Result<(), String> {
+    let start = GameState::with_pieces(global.pieces.clone());
+    let mut searcher = Searcher::new();
+    searcher.set_threads(threads.unwrap_or_else(search::default_threads));
+    let mut seen = HashSet::new();
+    let mut found = Vec::new();
+
+    for_each_game(global, source, |record| {
+        let puzzles = puzzle::find_puzzles(&mut searcher, &start, &record.moves, max_moves, &mut seen)?;
+        for puzzle in puzzles {
+            let mut game = start.clone();
+            game.apply_moves(&puzzle.position)?;
+            if game.get_possible_moves().is_empty() {
+                game.pass();
             }
-        };
-        current_state.unpass();
+            let description = format!("{} wins in {}", team_name(game.turn()), puzzle.moves(&start)?);
+            println!("  {description}: {}", puzzle.solution[0]);
+            found.push(format!("{puzzle} # {description}"));
+        }
+        Ok(())
+    })?;
 
-        return score;
+    let mut file = fs::OpenOptions::new().create(true).append(true).open(output)
+        .map_err(|e| format!("Failed to open '{output}': {e}"))?;
+    for line in &found {
+        writeln!(file, "{line}").map_err(|e| format!("Failed to write '{output}': {e}"))?;
     }
+    println!("Saved {} puzzles to {output}", found.len());
+    Ok(())
+}
 
-    let mut best = if current_state.turn() { isize::MIN } else { isize::MAX };
-    for m in moves.into_iter() {
-        current_state.apply_move(m.clone());
-
-        let hash = current_state.get_hash();
-        let found = global_hash.get(&hash);
-        let score = if found.is_some() && found.unwrap().1 >= max_depth - depth {
-            found.unwrap().0
-        }
-        else {
-            if depth == max_depth {
-                // Evaluate board at final depth
-                get_score(current_state.score())
-            }
-            else {
-                let score = minimax(current_state, depth + 1, max_depth, alpha, beta, global_hash, counter);
-                if current_state.turn_count() > 7 {
-                    global_hash.insert(hash, (score, max_depth - depth));
+/// Presents puzzles one at a time, reading moves from standard input
+fn solve_puzzles(global: &GlobalOptions, file: &str, number: Option<usize>) -> Result<(), String> {
+    let puzzles = Puzzle::load(file)?;
+    let selected = match number {
+        Some(n) if n > puzzles.len() => return Err(format!("{file} only has {} puzzles", puzzles.len())),
+        Some(n) => n - 1..n,
+        None => 0..puzzles.len(),
+    };
+    let start = GameState::with_pieces(global.pieces.clone());
+    let mut searcher = Searcher::new();
+    let stdin = std::io::stdin();
+    let (mut solved, mut attempted) = (0, 0);
+
+    'puzzles: for i in selected {
+        let mut attempt = Attempt::new(&start, &puzzles[i]).map_err(|e| format!("Puzzle {}: {e}", i + 1))?;
+        let team = team_name(attempt.team());
+        attempted += 1;
+        println!("Puzzle {} of {}", i + 1, puzzles.len());
+
+        loop {
+            render::print_board(attempt.game());
+            let moves_left = attempt.moves_left();
+            println!("{team} to play and win in {moves_left} move{}", if moves_left == 1 { "" } else { "s" });
+
+            loop {
+                print!("Enter move: ");
+                std::io::stdout().flush().ok();
+                let mut line = String::new();
+                if stdin.read_line(&mut line).map_err(|e| e.to_string())? == 0 {
+                    break 'puzzles;
+                }
+                let m = match line.trim() {
+                    "" => continue,
+                    "skip" => {
+                        let solution = puzzles[i].solution.iter().map(|m| m.to_string()).collect::<Vec<_>>();
+                        println!("Solution: {}\n", solution.join(" "));
+                        continue 'puzzles;
+                    }
+                    "hint" => {
+                        match attempt.hint() {
+                            Some(m) => println!("Hint: {m}"),
+                            None => println!("No hint available"),
+                        }
+                        continue;
+                    }
+                    text => match text.parse::<Move>() {
+                        Ok(m) => m,
+                        Err(e) => {
+                            println!("{e}");
+                            continue;
+                        }
+                    },
+                };
+                match attempt.answer(&mut searcher, &m) {
+                    Answer::Correct(reply) => {
+                        println!("Correct! {}", reply.map_or("Opponent passes".to_string(), |r| format!("Opponent plays {r}")));
+                        break;
+                    }
+                    Answer::Solved => {
+                        render::print_board(attempt.game());
+                        println!("Solved!\n");
+                        solved += 1;
+                        continue 'puzzles;
+                    }
+                    Answer::Wrong(reason) => println!("Not quite - {reason}"),
                 }
-                score
             }
-        };
+        }
+    }
 
-        current_state.undo_move(m.clone());
+    println!("Solved {solved} of {attempted} puzzles");
+    Ok(())
+}
 
-        if turn {
-            if score == isize::MAX { return score; }
-            best = best.max(score);
-            alpha = best.max(score);
+fn annotate_games(global: &GlobalOptions, records: &str, game: Option<usize>, depth: usize, threads: Option<usize>, output: Option<&str>) -> Result<(), String> {
+    let contents = fs::read_to_string(records).map_err(|e| format!("Failed to read '{records}': {e}"))?;
+    let mut games = Vec::new();
+    for (i, line) in contents.lines().enumerate() {
+        let record = GameRecord::parse(line).map_err(|e| format!("{records} line {}: {e}", i + 1))?;
+        if !record.moves.is_empty() {
+            games.push(record);
         }
-        else {
-            if score == isize::MIN { return score; }
-            best = best.min(score);
-            beta = beta.min(score);
+    }
+    let selected = match game {
+        Some(n) if n > games.len() => return Err(format!("{records} only has {} games", games.len())),
+        Some(n) => n - 1..n,
+        None => 0..games.len(),
+    };
+
+    let mut searcher = Searcher::new();
+    searcher.set_threads(threads.unwrap_or_else(search::default_threads));
+    let start = GameState::with_pieces(global.pieces.clone());
+    let mut annotated = String::new();
+
+    for i in selected {
+        let record = &games[i];
+        println!("Game {} ({} moves, {})", i + 1, record.moves.len(), record.outcome.describe());
+        let interactive = std::io::stdout().is_terminal();
+        let annotations = annotate::annotate_game(&mut searcher, &start, record, depth, |ply| {
+            if interactive {
+                print!("\r  Analysing move {}/{}", ply + 1, record.moves.len());
+                std::io::stdout().flush().ok();
+            }
+        }).map_err(|e| format!("Game {}: {e}", i + 1))?;
+        if interactive {
+            print!("\r{}\r", " ".repeat(40));
         }
 
-        if beta <= alpha {
-            break;
+        for annotation in &annotations {
+            let Some(judgement) = annotation.judgement() else { continue };
+            println!(
+                "  {:>3}. {}{} {} by {} - loses {} ({} to {}), best was {}",
+                annotation.ply + 1,
+                annotation.played,
+                judgement.symbol(),
+                judgement.name(),
+                team_name(annotation.team),
+                annotation.loss(),
+                format_score(annotation.before),
+                format_score(annotation.after),
+                annotation.best_line.first().map_or("-".to_string(), |m| m.to_string()),
+            );
         }
+        let cyan = PlayerSummary::new(&annotations, true);
+        let green = PlayerSummary::new(&annotations, false);
+        println!("  Cyan: {cyan}");
+        println!("  Green: {green}");
+        println!();
+
+        let text = annotate::annotated_record(record, &annotations);
+        if output.is_none() {
+            println!("{text}\n");
+        }
+        annotated += &format!("# Game {}\n# Cyan: {cyan}\n# Green: {green}\n{text}\n", i + 1);
     }
 
-    best
+    if let Some(path) = output {
+        fs::write(path, annotated).map_err(|e| format!("Failed to write '{path}': {e}"))?;
+        println!("Wrote {path}");
+    }
+    Ok(())
 }
 
 fn main() {
-    let mut global_hash_scores = HashMap::new();
-
-    let mut game = GameState::new();
-    loop {
-        game.print();
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
 
-        let (score, w, b) = game.score();
-
-        if w && b {
-            println!("Draw!");
-            break;
+    let (global, command) = match cli::parse_args(&args) {
+        Ok(parsed) => parsed,
+        Err(e) => {
+            eprintln!("Error: {e}");
+            std::process::exit(2);
         }
-        if w {
-            println!("Cyan wins!");
-            break;
+    };
+    colour::set_enabled(global.colour);
+    render::set_style(global.board);
+
+    let result = match command {
+        Command::Play { white, black, start, max_turns, record } => play(&global, &white, &black, &start, max_turns, record.as_deref()),
+        Command::Analyse { position, engine } => analyse(&global, &position, &engine),
+        Command::Analysis { position, lines, depth, time, threads } => analysis(&global, &position, lines, depth, time, threads),
+        Command::Render { position, svg, coordinates, line, highlight } => render_position(&global, &position, svg.as_deref(), coordinates, &line, highlight),
+        Command::Perft { depth, position, divide } => perft(&global, depth, &position, divide),
+        Command::SelfPlay { games, white, black, max_turns } => selfplay(&global, games, &white, &black, max_turns),
+        Command::Tournament { first, second, games, max_turns, openings, sprt } => {
+            let config = TournamentConfig { games, max_turns, pieces: global.pieces.clone(), openings, sprt, seed: global.seed, book: global.book.clone() };
+            tournament::run(&first, &second, &config)
+                .map(|results| tournament::print_summary(&first, &second, &results, sprt.as_ref()))
         }
-        if b {
-            println!("Green wins!");
-            break;
+        Command::Book { source, output, max_plies } => build_book(&global, &source, &output, max_plies),
+        Command::PuzzleGenerate { source, output, max_moves, threads } => generate_puzzles(&global, &source, &output, max_moves, threads),
+        Command::PuzzleSolve { file, number } => solve_puzzles(&global, &file, number),
+        Command::Serve { port, start, max_turns, record } => serve(&global, port, &start, max_turns, record.as_deref()),
+        Command::Connect { address, player } => connect(&global, &address, &player),
+        Command::Api { port, threads, allow_origin } => serve_api(&global, port, threads, allow_origin),
+        Command::Annotate { records, game, depth, threads, output } => annotate_games(&global, &records, game, depth, threads, output.as_deref()),
+        Command::Help => {
+            println!("{}", cli::USAGE);
+            Ok(())
         }
+    };
 
-        println!("Turn: {} [{}]\n", if game.turn() { "Cyan" } else { "Green" }, game.turn_count());
-
-        // stdout().flush().unwrap();
-        // stdin().read_line(&mut String::new()).unwrap();
-
-        println!("Working...");
-
-        if game.turn() {
-            computer_select(&mut game, &mut global_hash_scores);
-        }
-        else {
-            computer_select(&mut game, &mut global_hash_scores);
-        }
+    if let Err(e) = result {
+        eprintln!("Error: {e}");
+        std::process::exit(1);
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,391 @@
+use std::time::{Duration, Instant};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use crate::game_state::{GameState, Move};
+
+/// Score given to the side to move for a won playout - lost playouts score 0 and draws or
+/// playouts cut off before finishing score half
+const WIN: f64 = 1.0;
+const DRAW: f64 = 0.5;
+
+/// `None` represents passing when no moves are available
+type Action = Option<Move>;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PlayoutPolicy {
+    /// Uniformly random legal moves
+    Random,
+    /// Moves that add pressure around the enemy queen are chosen when available, otherwise random
+    QueenPressure,
+}
+
+#[derive(Clone, Debug)]
+pub struct MctsConfig {
+    /// Maximum number of iterations per move, `None` for no limit
+    pub iterations: Option<usize>,
+    /// Maximum time per move, `None` for no limit. If neither limit is set a default
+    /// iteration count is used.
+    pub time_limit: Option<Duration>,
+    /// UCT exploration constant
+    pub exploration: f64,
+    pub playout: PlayoutPolicy,
+    /// Playouts longer than this are scored as a draw
+    pub max_playout_moves: usize,
+    /// Seed for the playout RNG, `None` for a random seed
+    pub seed: Option<u64>,
+}
+
+impl Default for MctsConfig {
+    fn default() -> Self {
+        MctsConfig {
+            iterations: Some(20_000),
+            time_limit: None,
+            exploration: std::f64::consts::SQRT_2,
+            playout: PlayoutPolicy::QueenPressure,
+            max_playout_moves: 200,
+            seed: None,
+        }
+    }
+}
+
+#[derive(Default, Clone)]
+pub struct MctsStats {
+    pub iterations: usize,
+    pub nodes: usize,
+    /// Win rate of the chosen move for the side that played it
+    pub win_rate: f64,
+}
+
+impl MctsStats {
+    pub fn print(&self) {
+        println!("Iterations: {} ({} nodes)", self.iterations, self.nodes);
+        println!("Win rate: {:.1}%", self.win_rate * 100.0);
+    }
+}
+
+struct Node {
+    action: Action,
+    parent: Option<usize>,
+    children: Vec<usize>,
+    untried: Vec<Action>,
+    /// Team that played `action` - totals are from its perspective
+    team: bool,
+    visits: u32,
+    total: f64,
+}
+
+pub struct Mcts {
+    config: MctsConfig,
+    rng: StdRng,
+    stats: MctsStats,
+}
+
+impl Mcts {
+    pub fn new(config: MctsConfig) -> Mcts {
+        let rng = match config.seed {
+            Some(seed) => StdRng::seed_from_u64(seed),
+            None => StdRng::from_entropy(),
+        };
+        Mcts {
+            config,
+            rng,
+            stats: MctsStats::default(),
+        }
+    }
+
+    pub fn stats(&self) -> &MctsStats { &self.stats }
+
+    /// Searches the current position, returning the most visited move along with a score in the
+    /// same orientation as the minimax search (positive favours Cyan). Returns `None` if there
+    /// are no moves available.
+    pub fn best_move(&mut self, game_state: &mut GameState) -> Option<(Move, isize)> {
+        let root_actions = actions(game_state);
+        if root_actions.iter().all(|a| a.is_none()) {
+            return None;
+        }
+
+        let mut tree = vec![Node {
+            action: None,
+            parent: None,
+            children: Vec::new(),
+            untried: root_actions,
+            team: !game_state.turn(),
+            visits: 0,
+            total: 0.0,
+        }];
+
+        let start = Instant::now();
+        let iteration_limit = match (self.config.iterations, self.config.time_limit) {
+            (None, None) => MctsConfig::default().iterations,
+            (iterations, _) => iterations,
+        };
+
+        let mut iterations = 0;
+        loop {
+            if iteration_limit.is_some_and(|limit| iterations >= limit) {
+                break;
+            }
+            // Checking the time is comparatively slow
+            if iterations % 64 == 0 && self.config.time_limit.is_some_and(|limit| start.elapsed() >= limit) {
+                break;
+            }
+
+            self.iterate(&mut tree, game_state);
+            iterations += 1;
+        }
+
+        let best = tree[0].children.iter()
+            .copied()
+            .max_by_key(|c| tree[*c].visits)?;
+        let node = &tree[best];
+        let win_rate = if node.visits == 0 { DRAW } else { node.total / node.visits as f64 };
+
+        self.stats = MctsStats {
+            iterations,
+            nodes: tree.len(),
+            win_rate,
+        };
+
+        let white_rate = if node.team { win_rate } else { 1.0 - win_rate };
+        let score = ((white_rate - DRAW) * 2000.0) as isize;
+
+        node.action.clone().map(|m| (m, score))
+    }
+
+    fn iterate(&mut self, tree: &mut Vec<Node>, game_state: &mut GameState) {
+        let mut path = Vec::new();
+        let mut current = 0;
+
+        // Selection
+        while tree[current].untried.is_empty() && !tree[current].children.is_empty() {
+            current = self.select_child(tree, current);
+            apply(game_state, &tree[current].action);
+            path.push(tree[current].action.clone());
+        }
+
+        // Expansion
+        if !tree[current].untried.is_empty() && !is_finished(game_state) {
+            let i = self.rng.gen_range(0..tree[current].untried.len());
+            let action = tree[current].untried.swap_remove(i);
+            let team = game_state.turn();
+            apply(game_state, &action);
+            path.push(action.clone());
+
+            let child = tree.len();
+            tree.push(Node {
+                action,
+                parent: Some(current),
+                children: Vec::new(),
+                untried: if is_finished(game_state) { Vec::new() } else { actions(game_state) },
+                team,
+                visits: 0,
+                total: 0.0,
+            });
+            tree[current].children.push(child);
+            current = child;
+        }
+
+        // Simulation
+        let winner = self.playout(game_state);
+
+        // Backpropagation
+        let mut node = Some(current);
+        while let Some(i) = node {
+            let n = &mut tree[i];
+            n.visits += 1;
+            n.total += match winner {
+                Some(team) if team == n.team => WIN,
+                Some(_) => 0.0,
+                None => DRAW,
+            };
+            node = n.parent;
+        }
+
+        for action in path.into_iter().rev() {
+            undo(game_state, action);
+        }
+    }
+
+    fn select_child(&self, tree: &[Node], parent: usize) -> usize {
+        let log_visits = (tree[parent].visits.max(1) as f64).ln();
+        let uct = |c: usize| {
+            let n = &tree[c];
+            if n.visits == 0 {
+                return f64::INFINITY;
+            }
+            let visits = n.visits as f64;
+            n.total / visits + self.config.exploration * (log_visits / visits).sqrt()
+        };
+
+        tree[parent].children.iter()
+            .copied()
+            .max_by(|a, b| uct(*a).total_cmp(&uct(*b)))
+            .unwrap()
+    }
+
+    /// Plays the game out from the current position, returning the winning team or `None` for
+    /// a draw. The position is restored afterwards.
+    fn playout(&mut self, game_state: &mut GameState) -> Option<bool> {
+        let mut played = Vec::new();
+
+        let winner = loop {
+            let (_, white_win, black_win) = game_state.score();
+            if white_win || black_win {
+                break if white_win && black_win { None } else { Some(white_win) };
+            }
+            if played.len() >= self.config.max_playout_moves {
+                break None;
+            }
+
+            let action = self.playout_action(game_state);
+            apply(game_state, &action);
+            played.push(action);
+        };
+
+        for action in played.into_iter().rev() {
+            undo(game_state, action);
+        }
+
+        winner
+    }
+
+    fn playout_action(&mut self, game_state: &mut GameState) -> Action {
+        let mut moves = game_state.get_possible_moves();
+        if moves.is_empty() {
+            return None;
+        }
+        moves.sort();
+
+        if self.config.playout == PlayoutPolicy::QueenPressure {
+            if let Some(queen) = game_state.queen_location(!game_state.turn()) {
+                let surrounding = queen.surrounding();
+                let pressure = moves.iter()
+                    .filter(|m| match m {
+                        Move::Place(_, to) => surrounding.contains(to),
+                        Move::Move(from, to) => surrounding.contains(to) && !surrounding.contains(from),
+                    })
+                    .collect::<Vec<_>>();
+                if !pressure.is_empty() {
+                    return Some(pressure[self.rng.gen_range(0..pressure.len())].clone());
+                }
+            }
+        }
+
+        let i = self.rng.gen_range(0..moves.len());
+        moves.into_iter().nth(i)
+    }
+}
+
+fn is_finished(game_state: &GameState) -> bool {
+    let (_, white_win, black_win) = game_state.score();
+    white_win || black_win
+}
+
+fn actions(game_state: &mut GameState) -> Vec<Action> {
+    let mut moves = game_state.get_possible_moves();
+    if moves.is_empty() {
+        return vec![None];
+    }
+    // Move generation order depends on hash map iteration so sort to stay deterministic
+    moves.sort();
+    moves.into_iter().map(Some).collect()
+}
+
+fn apply(game_state: &mut GameState, action: &Action) {
+    match action {
+        Some(m) => game_state.apply_move(m.clone()),
+        None => game_state.pass(),
+    }
+}
+
+fn undo(game_state: &mut GameState, action: Action) {
+    match action {
+        Some(m) => game_state.undo_move(m),
+        None => game_state.unpass(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game_state::parse_move_list;
+
+    /// Green to move, and `-1,-1>-1,0` surrounds Cyan's queen
+    fn winning_position() -> GameState {
+        let mut game = GameState::new();
+        game.apply_moves(&parse_move_list(
+            "Q@0,0 A@1,0 A@-1,1 A@1,1 A@-1,2 Q@1,2 A@-2,3 A@2,-1 -2,3>-1,3 1,2>0,3 A@-2,2 2,-1>1,-1 \
+            A@-2,1 A@1,-2 A@-3,2 1,-2>0,-1 -2,2>-3,3 1,1>0,1 -3,3>-4,3 A@-1,-1 -4,3>-4,2"
+        ).unwrap()).unwrap();
+        game
+    }
+
+    /// Short playouts keep the tests quick
+    fn config(iterations: Option<usize>, time_limit: Option<Duration>) -> MctsConfig {
+        MctsConfig { iterations, time_limit, max_playout_moves: 30, seed: Some(7), ..MctsConfig::default() }
+    }
+
+    #[test]
+    fn finds_immediate_win() {
+        let mut game = winning_position();
+        let before = game.clone();
+        let mut mcts = Mcts::new(config(Some(500), None));
+        let (m, score) = mcts.best_move(&mut game).unwrap();
+        assert_eq!(m, parse_move_list("-1,-1>-1,0").unwrap()[0]);
+        assert!(score < 0, "{score}");
+        assert_eq!(game, before);
+    }
+
+    #[test]
+    fn seeded_search_is_deterministic() {
+        let mut game = GameState::new();
+        game.apply_moves(&parse_move_list("Q@0,0 Q@1,0 A@-1,0 A@2,0").unwrap()).unwrap();
+        let run = || {
+            let mut mcts = Mcts::new(config(Some(200), None));
+            let result = mcts.best_move(&mut game.clone());
+            (result, mcts.stats().nodes, mcts.stats().win_rate)
+        };
+        assert_eq!(run(), run());
+    }
+
+    #[test]
+    fn respects_limits() {
+        let mut game = GameState::new();
+        game.apply_moves(&parse_move_list("Q@0,0 Q@1,0").unwrap()).unwrap();
+
+        let mut mcts = Mcts::new(config(Some(123), None));
+        mcts.best_move(&mut game).unwrap();
+        assert_eq!(mcts.stats().iterations, 123);
+
+        let limit = Duration::from_millis(100);
+        let mut mcts = Mcts::new(config(None, Some(limit)));
+        let start = Instant::now();
+        mcts.best_move(&mut game).unwrap();
+        let elapsed = start.elapsed();
+        // The time is only checked every so many iterations
+        assert!(elapsed >= limit && elapsed < limit + Duration::from_secs(2), "{elapsed:?}");
+        assert!(mcts.stats().iterations > 0);
+
+        // Whichever limit is reached first ends the search
+        let mut mcts = Mcts::new(config(Some(10), Some(Duration::from_secs(60))));
+        mcts.best_move(&mut game).unwrap();
+        assert_eq!(mcts.stats().iterations, 10);
+    }
+
+    #[test]
+    fn queen_pressure_playouts_are_legal() {
+        for seed in 0..10 {
+            let mut mcts = Mcts::new(MctsConfig { seed: Some(seed), playout: PlayoutPolicy::QueenPressure, ..MctsConfig::default() });
+            let mut game = GameState::new();
+            while game.turn_count() < 100 && !is_finished(&game) {
+                let legal = game.get_possible_moves();
+                let action = mcts.playout_action(&mut game);
+                match &action {
+                    Some(m) => assert!(legal.contains(m), "{m} after {} turns", game.turn_count()),
+                    None => assert!(legal.is_empty()),
+                }
+                apply(&mut game, &action);
+            }
+        }
+    }
+}
@@ -0,0 +1,549 @@
+//! Play over the network, with a server holding the game and relaying moves between two clients.
+//!
+//! The protocol is line based, with a command word followed by arguments separated by spaces.
+//! The first two clients to connect play Cyan and Green in that order, and any others spectate.
+//!
+//! Server to client:
+//! - `WELCOME cyan|green|spectator` - the client's role, sent on connecting
+//! - `POSITION PIECES MOVES...` - the piece set each player starts with (e.g. `Q1A6`) and the
+//!   moves played so far, sent on connecting and when asked for
+//! - `START` - both players have connected and the game has begun
+//! - `TURN cyan|green` - the team to move next
+//! - `MOVED cyan|green MOVE` - a move was played
+//! - `PASS cyan|green` - the team had no moves and passed
+//! - `LEGAL MOVES...` - the legal moves of the team to move, when asked for
+//! - `INFO TEXT` - a message to show, e.g. a player connecting
+//! - `ERROR TEXT` - the last command was rejected, e.g. an illegal move
+//! - `END RESULT TEXT` - the game is over, with the result as in game records (`1-0`, `0-1`,
+//!   `1/2-1/2` or `*`) and its reason. The server closes the connection afterwards.
+//!
+//! Client to server:
+//! - `MOVE MOVE` - play a move, e.g. `MOVE A@-1,0` or `MOVE 0,0>1,-1`
+//! - `MOVES` - ask for the legal moves
+//! - `POSITION` - ask for the position
+//! - `RESIGN` - give up the game
+//! - `QUIT` - disconnect, which ends the game if sent by a player
+
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread;
+use crate::game_state::{GameState, Move};
+use crate::piece_bag::PieceBag;
+use crate::player::{Choice, Player};
+use crate::render;
+use crate::rules;
+use crate::session::{team_id, team_name, GameRecord, Outcome};
+
+pub const DEFAULT_PORT: u16 = 7878;
+/// Moves the server can reject in a turn before the client resigns, so that a player repeating an
+/// illegal move doesn't leave both sides waiting
+const MAX_REJECTED_MOVES: usize = 3;
+
+fn parse_team(s: &str) -> Result<bool, String> {
+    match s {
+        "cyan" => Ok(true),
+        "green" => Ok(false),
+        _ => Err(format!("Unknown team '{s}'")),
+    }
+}
+
+fn join_moves(moves: &[Move]) -> String {
+    moves.iter().map(|m| m.to_string()).collect::<Vec<_>>().join(" ")
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Role {
+    Player(bool),
+    Spectator,
+}
+
+impl Role {
+    fn name(&self) -> &'static str {
+        match self {
+            Role::Player(team) => team_id(*team),
+            Role::Spectator => "spectator",
+        }
+    }
+}
+
+/// Sent to the server's game loop by the threads handling connections
+enum Event {
+    Joined(usize, TcpStream),
+    Line(usize, String),
+    Left(usize),
+}
+
+struct Client {
+    id: usize,
+    role: Role,
+    stream: TcpStream,
+}
+
+pub struct ServerOptions {
+    /// Moves played before the clients take over
+    pub start: Vec<Move>,
+    /// Declares the game a draw once this many turns have been played
+    pub max_turns: Option<usize>,
+    /// Whether to print the board and connections as the game is played
+    pub verbose: bool,
+}
+
+struct Server {
+    events: Receiver<Event>,
+    clients: Vec<Client>,
+    pieces: PieceBag,
+    game: GameState,
+    moves: Vec<Move>,
+    started: bool,
+    verbose: bool,
+}
+
+/// What a client's command means for the game
+enum Request {
+    Move(Move),
+    Resign(bool),
+    None,
+}
+
+impl Server {
+    fn log(&self, message: &str) {
+        if self.verbose {
+            println!("{message}");
+        }
+    }
+
+    /// Write failures are ignored, as the client's reader will report it leaving
+    fn send(&mut self, id: usize, line: &str) {
+        if let Some(client) = self.clients.iter_mut().find(|c| c.id == id) {
+            let _ = writeln!(client.stream, "{line}");
+        }
+    }
+
+    fn broadcast(&mut self, line: &str) {
+        for client in &mut self.clients {
+            let _ = writeln!(client.stream, "{line}");
+        }
+    }
+
+    fn role(&self, id: usize) -> Option<Role> {
+        self.clients.iter().find(|c| c.id == id).map(|c| c.role)
+    }
+
+    fn position(&self) -> String {
+        format!("POSITION {} {}", self.pieces, join_moves(&self.moves)).trim_end().to_string()
+    }
+
+    fn join(&mut self, id: usize, stream: TcpStream) {
+        let taken = |team| self.clients.iter().any(|c| c.role == Role::Player(team));
+        let role = if !taken(true) {
+            Role::Player(true)
+        }
+        else if !taken(false) {
+            Role::Player(false)
+        }
+        else {
+            Role::Spectator
+        };
+        let address = stream.peer_addr().map_or("Unknown client".to_string(), |a| a.to_string());
+        self.broadcast(&format!("INFO A {} joined", role.name()));
+        self.clients.push(Client { id, role, stream });
+        self.log(&format!("{address} joined as {}", role.name()));
+
+        self.send(id, &format!("WELCOME {}", role.name()));
+        let position = self.position();
+        self.send(id, &position);
+        if self.started {
+            self.send(id, "START");
+            self.send(id, &format!("TURN {}", team_id(self.game.turn())));
+        }
+    }
+
+    /// Removes the client, returning their role
+    fn leave(&mut self, id: usize) -> Option<Role> {
+        let index = self.clients.iter().position(|c| c.id == id)?;
+        let client = self.clients.remove(index);
+        self.log(&format!("{} left", client.role.name()));
+        if client.role == Role::Spectator {
+            self.broadcast("INFO A spectator left");
+        }
+        Some(client.role)
+    }
+
+    fn handle(&mut self, id: usize, line: &str) -> Request {
+        let Some(role) = self.role(id) else { return Request::None };
+        let (command, argument) = line.trim().split_once(' ').unwrap_or((line.trim(), ""));
+
+        match command {
+            "MOVE" => {
+                let Role::Player(team) = role else {
+                    self.send(id, "ERROR Spectators can't move");
+                    return Request::None;
+                };
+                if !self.started {
+                    self.send(id, "ERROR The game hasn't started");
+                    return Request::None;
+                }
+                if team != self.game.turn() {
+                    self.send(id, "ERROR It isn't your turn");
+                    return Request::None;
+                }
+                match argument.trim().parse::<Move>() {
+                    Ok(m) => match rules::explain_illegal(&self.game, &m) {
+                        Some(reason) => self.send(id, &format!("ERROR {reason}")),
+                        None => return Request::Move(m),
+                    },
+                    Err(e) => self.send(id, &format!("ERROR {e}")),
+                }
+            }
+            "MOVES" => {
+                let mut moves = self.game.get_possible_moves();
+                moves.sort();
+                self.send(id, format!("LEGAL {}", join_moves(&moves)).trim_end());
+            }
+            "POSITION" => {
+                let position = self.position();
+                self.send(id, &position);
+            }
+            "RESIGN" => match role {
+                Role::Player(team) if self.started => return Request::Resign(team),
+                Role::Player(_) => self.send(id, "ERROR The game hasn't started"),
+                Role::Spectator => self.send(id, "ERROR Spectators can't resign"),
+            },
+            "QUIT" => {
+                if let Some(client) = self.clients.iter().find(|c| c.id == id) {
+                    let _ = client.stream.shutdown(std::net::Shutdown::Both);
+                }
+            }
+            "" => {}
+            _ => self.send(id, &format!("ERROR Unknown command '{command}'")),
+        }
+        Request::None
+    }
+
+    /// Plays the game once both players have joined, returning the result and its reason
+    fn run(&mut self, max_turns: Option<usize>) -> (Outcome, String) {
+        while !self.started {
+            let Ok(event) = self.events.recv() else { return (Outcome::Ended, "Server stopped".to_string()) };
+            match event {
+                Event::Joined(id, stream) => self.join(id, stream),
+                Event::Line(id, line) => {
+                    self.handle(id, &line);
+                }
+                Event::Left(id) => {
+                    self.leave(id);
+                }
+            }
+            self.started = [true, false].iter().all(|team| self.clients.iter().any(|c| c.role == Role::Player(*team)));
+        }
+        self.broadcast("START");
+        self.log("Game started");
+
+        loop {
+            if self.verbose {
+                render::print_board(&self.game);
+            }
+            let (_, w, b) = self.game.score();
+            match (w, b) {
+                (true, true) => return (Outcome::Draw, "Both queens are surrounded".to_string()),
+                (true, false) => return (Outcome::Win(true), format!("{}'s queen is surrounded", team_name(false))),
+                (false, true) => return (Outcome::Win(false), format!("{}'s queen is surrounded", team_name(true))),
+                (false, false) => {}
+            }
+            if max_turns.is_some_and(|max| self.game.turn_count() >= max) {
+                return (Outcome::Draw, "Turn limit reached".to_string());
+            }
+
+            let team = self.game.turn();
+            if self.game.get_possible_moves().is_empty() {
+                self.game.pass();
+                self.broadcast(&format!("PASS {}", team_id(team)));
+                continue;
+            }
+            self.broadcast(&format!("TURN {}", team_id(team)));
+
+            let m = loop {
+                let Ok(event) = self.events.recv() else { return (Outcome::Ended, "Server stopped".to_string()) };
+                match event {
+                    Event::Joined(id, stream) => self.join(id, stream),
+                    Event::Line(id, line) => match self.handle(id, &line) {
+                        Request::Move(m) => break m,
+                        Request::Resign(team) => {
+                            let name = team_name(team);
+                            return (Outcome::Win(!team), format!("{name} resigned"));
+                        }
+                        Request::None => {}
+                    },
+                    Event::Left(id) => {
+                        if let Some(Role::Player(team)) = self.leave(id) {
+                            let name = team_name(team);
+                            return (Outcome::Ended, format!("{name} disconnected"));
+                        }
+                    }
+                }
+            };
+            self.log(&format!("{} played {m}", team_name(team)));
+            self.game.apply_move(m.clone());
+            self.broadcast(&format!("MOVED {} {m}", team_id(team)));
+            self.moves.push(m);
+        }
+    }
+}
+
+/// Passes lines read from a connection to the game loop until it closes
+fn read_lines(id: usize, stream: TcpStream, events: Sender<Event>) {
+    for line in BufReader::new(stream).lines() {
+        let Ok(line) = line else { break };
+        if events.send(Event::Line(id, line)).is_err() {
+            return;
+        }
+    }
+    let _ = events.send(Event::Left(id));
+}
+
+/// Hosts a single game for clients connecting to `listener`, returning its record once it's over
+pub fn serve(listener: TcpListener, pieces: PieceBag, options: &ServerOptions) -> Result<GameRecord, String> {
+    let mut game = GameState::with_pieces(pieces.clone());
+    game.apply_moves(&options.start)?;
+
+    let (sender, events) = mpsc::channel();
+    thread::spawn(move || {
+        for (id, stream) in listener.incoming().enumerate() {
+            let Ok(stream) = stream else { continue };
+            let Ok(reader) = stream.try_clone() else { continue };
+            if sender.send(Event::Joined(id, stream)).is_err() {
+                return;
+            }
+            let sender = sender.clone();
+            thread::spawn(move || read_lines(id, reader, sender));
+        }
+    });
+
+    let mut server = Server {
+        events,
+        clients: Vec::new(),
+        pieces,
+        game,
+        moves: options.start.clone(),
+        started: false,
+        verbose: options.verbose,
+    };
+    let (outcome, reason) = server.run(options.max_turns);
+    server.log(&reason);
+    server.broadcast(&format!("END {} {reason}", outcome.notation()));
+    for client in &server.clients {
+        let _ = client.stream.shutdown(std::net::Shutdown::Both);
+    }
+
+    Ok(GameRecord { moves: server.moves, outcome, variations: Vec::new() })
+}
+
+/// Asks the player for their move, giving the command to send
+fn choose_turn(player: &mut dyn Player, game: &GameState) -> String {
+    loop {
+        match player.choose(game) {
+            Some(Choice::Play(m)) => return format!("MOVE {m}"),
+            Some(Choice::Undo | Choice::Redo) => println!("Moves can't be taken back in network games"),
+            None => return "RESIGN".to_string(),
+        }
+    }
+}
+
+/// Takes part in a game hosted by a server, choosing moves with `player` when playing and
+/// otherwise watching. Returns the result once the game is over.
+pub fn connect(stream: TcpStream, player: &mut dyn Player, verbose: bool) -> Result<Outcome, String> {
+    let mut writer = stream.try_clone().map_err(|e| e.to_string())?;
+    let mut send = |line: String| writeln!(writer, "{line}").map_err(|e| format!("Failed to send to the server: {e}"));
+    let log = |message: &str| if verbose { println!("{message}") };
+
+    let mut role = Role::Spectator;
+    let mut game = GameState::new();
+    // Moves rejected so far in the turn being played, `None` when not choosing a move
+    let mut rejected = None;
+    for line in BufReader::new(stream).lines() {
+        let line = line.map_err(|e| format!("Failed to read from the server: {e}"))?;
+        let (command, argument) = line.split_once(' ').unwrap_or((&line, ""));
+        match command {
+            "WELCOME" => {
+                role = match argument {
+                    "spectator" => Role::Spectator,
+                    team => Role::Player(parse_team(team)?),
+                };
+                match role {
+                    Role::Player(team) => log(&format!("Playing as {}", team_name(team))),
+                    Role::Spectator => log("Both players have joined - spectating"),
+                }
+            }
+            "POSITION" => {
+                let (pieces, moves) = argument.split_once(' ').unwrap_or((argument, ""));
+                game = GameState::with_pieces(PieceBag::parse(pieces)?);
+                game.apply_moves(&crate::game_state::parse_move_list(moves)?)?;
+                if verbose {
+                    render::print_board(&game);
+                }
+            }
+            "START" => log("Game started"),
+            "TURN" => {
+                let team = parse_team(argument)?;
+                if role != Role::Player(team) {
+                    log(&format!("Waiting for {}", team_name(team)));
+                    continue;
+                }
+                rejected = Some(0);
+                send(choose_turn(player, &game))?;
+            }
+            "MOVED" => {
+                let Some((_, m)) = argument.split_once(' ') else {
+                    return Err(format!("Invalid message from the server: {line}"));
+                };
+                let m = m.parse::<Move>()?;
+                rejected = None;
+                game.apply_move(m.clone());
+                player.observe_move(&game, &m);
+                if verbose {
+                    render::print_board(&game);
+                }
+            }
+            "PASS" => {
+                game.pass();
+                log(&format!("{} has no moves and passes", team_name(parse_team(argument)?)));
+            }
+            "LEGAL" => log(&format!("Legal moves: {argument}")),
+            "INFO" => log(argument),
+            "ERROR" => {
+                println!("Server: {argument}");
+                // The server is still waiting for a move
+                if let Some(count) = rejected.as_mut() {
+                    *count += 1;
+                    if *count < MAX_REJECTED_MOVES {
+                        send(choose_turn(player, &game))?;
+                    }
+                    else {
+                        println!("Resigning after {count} rejected moves");
+                        rejected = None;
+                        send("RESIGN".to_string())?;
+                    }
+                }
+            }
+            "END" => {
+                let (result, reason) = argument.split_once(' ').unwrap_or((argument, ""));
+                log(reason);
+                return Outcome::from_notation(result).ok_or_else(|| format!("Unknown result '{result}'"));
+            }
+            _ => log(&format!("Unknown message from the server: {line}")),
+        }
+    }
+    Err("The server closed the connection".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game_state::parse_move_list;
+    use crate::player::ScriptedPlayer;
+
+    /// Ends with Green surrounding Cyan's queen
+    const GAME: &str = "Q@0,0 A@1,0 A@-1,1 A@1,1 A@-1,2 Q@1,2 A@-2,3 A@2,-1 -2,3>-1,3 1,2>0,3 A@-2,2 2,-1>1,-1 \
+        A@-2,1 A@1,-2 A@-3,2 1,-2>0,-1 -2,2>-3,3 1,1>0,1 -3,3>-4,3 A@-1,-1 -4,3>-4,2 -1,-1>-1,0";
+
+    fn start_server(start: &str) -> (String, thread::JoinHandle<Result<GameRecord, String>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        let options = ServerOptions { start: parse_move_list(start).unwrap(), max_turns: None, verbose: false };
+        (address, thread::spawn(move || serve(listener, PieceBag::new(), &options)))
+    }
+
+    fn read_line(reader: &mut BufReader<TcpStream>) -> String {
+        let mut line = String::new();
+        reader.read_line(&mut line).unwrap();
+        line.trim_end().to_string()
+    }
+
+    #[test]
+    fn plays_game_between_clients() {
+        let (address, server) = start_server("Q@0,0 A@1,0");
+        let moves = parse_move_list(GAME).unwrap();
+        let scripts = [true, false].map(|team| {
+            let mut script = moves[2..].iter().skip(if team { 0 } else { 1 }).step_by(2).cloned().collect::<Vec<_>>();
+            // Rejected by the server, so the client chooses again
+            if team {
+                script.insert(0, "A@5,5".parse().unwrap());
+            }
+            ScriptedPlayer::new(script)
+        });
+
+        // Connected in order so that the first script plays Cyan
+        let streams = [(); 2].map(|_| TcpStream::connect(&address).unwrap());
+        let clients = streams.into_iter().zip(scripts).map(|(stream, mut script)| {
+            thread::spawn(move || connect(stream, &mut script, false))
+        }).collect::<Vec<_>>();
+
+        for client in clients {
+            assert_eq!(client.join().unwrap(), Ok(Outcome::Win(false)));
+        }
+        let record = server.join().unwrap().unwrap();
+        assert_eq!(record.moves, moves);
+        assert_eq!(record.outcome, Outcome::Win(false));
+
+    }
+
+    #[test]
+    fn rejects_invalid_commands() {
+        let (address, server) = start_server("");
+        let [mut cyan, mut green] = [(); 2].map(|_| {
+            let stream = TcpStream::connect(&address).unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let welcome = read_line(&mut reader);
+            assert_eq!(read_line(&mut reader), "POSITION Q1A6");
+            (stream, reader, welcome)
+        });
+        assert_eq!((cyan.2.as_str(), green.2.as_str()), ("WELCOME cyan", "WELCOME green"));
+        assert_eq!(read_line(&mut cyan.1), "INFO A green joined");
+        for reader in [&mut cyan.1, &mut green.1] {
+            assert_eq!(read_line(reader), "START");
+            assert_eq!(read_line(reader), "TURN cyan");
+        }
+
+        writeln!(green.0, "MOVE A@0,0").unwrap();
+        assert_eq!(read_line(&mut green.1), "ERROR It isn't your turn");
+        writeln!(cyan.0, "MOVE Q@1,1").unwrap();
+        assert!(read_line(&mut cyan.1).starts_with("ERROR The first piece"));
+        writeln!(cyan.0, "JUMP").unwrap();
+        assert_eq!(read_line(&mut cyan.1), "ERROR Unknown command 'JUMP'");
+        writeln!(cyan.0, "MOVES").unwrap();
+        assert_eq!(read_line(&mut cyan.1), "LEGAL Q@0,0 A@0,0");
+
+        writeln!(cyan.0, "MOVE A@0,0").unwrap();
+        assert_eq!(read_line(&mut green.1), "MOVED cyan A@0,0");
+        assert_eq!(read_line(&mut green.1), "TURN green");
+
+        let mut spectator = BufReader::new(TcpStream::connect(&address).unwrap());
+        for expected in ["WELCOME spectator", "POSITION Q1A6 A@0,0", "START", "TURN green"] {
+            assert_eq!(read_line(&mut spectator), expected);
+        }
+        assert_eq!(read_line(&mut green.1), "INFO A spectator joined");
+
+        writeln!(green.0, "RESIGN").unwrap();
+        assert_eq!(read_line(&mut green.1), "END 1-0 Green resigned");
+        assert_eq!(read_line(&mut spectator), "END 1-0 Green resigned");
+
+        let record = server.join().unwrap().unwrap();
+        assert_eq!(record.to_string(), "A@0,0 1-0");
+    }
+
+    #[test]
+    fn resigns_after_rejected_moves() {
+        let (address, server) = start_server("");
+        // Cyan's legal move comes too late to be played
+        let scripts = [parse_move_list("Q@1,1 A@2,2 Q@1,1 Q@0,0").unwrap(), Vec::new()].map(ScriptedPlayer::new);
+        let streams = [(); 2].map(|_| TcpStream::connect(&address).unwrap());
+        let clients = streams.into_iter().zip(scripts).map(|(stream, mut script)| {
+            thread::spawn(move || connect(stream, &mut script, false))
+        }).collect::<Vec<_>>();
+
+        for client in clients {
+            assert_eq!(client.join().unwrap(), Ok(Outcome::Win(false)));
+        }
+        assert_eq!(server.join().unwrap().unwrap().to_string(), "0-1");
+    }
+}
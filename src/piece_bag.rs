@@ -1,81 +1,105 @@
-use color_print::cprint;
+use std::fmt::{self, Display, Formatter};
+use crate::colour;
 use crate::tile_types::TileType;
 
 const STARTING_ANTS: usize = 6;
-pub const STARTING_TOTAL: usize = 1 + STARTING_ANTS;
 
-#[derive(Clone)]
+#[derive(Clone, PartialEq, Eq, Debug)]
 pub struct PieceBag {
-    queen: bool,
-    ants: usize,
+    counts: [usize; TileType::VARIANT_COUNT],
+}
+
+impl Default for PieceBag {
+    fn default() -> Self {
+        PieceBag::new()
+    }
+}
+
+/// Written in the form read by `PieceBag::parse`, e.g. `Q1A6`
+impl Display for PieceBag {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        for (i, count) in self.counts.iter().enumerate() {
+            write!(f, "{}{count}", TileType::from_index(i).unwrap().character())?;
+        }
+        Ok(())
+    }
 }
 
 impl PieceBag {
     pub fn new() -> PieceBag {
-        PieceBag {
-            queen: true,
-            ants: STARTING_ANTS,
-        }
+        let mut counts = [0; TileType::VARIANT_COUNT];
+        counts[TileType::Queen.index()] = 1;
+        counts[TileType::Ant.index()] = STARTING_ANTS;
+        PieceBag { counts }
     }
 
-    pub fn print(&self, turn: bool) {
-        let q = if self.queen { 1 } else { 0 };
-        if turn {
-            cprint!("<c>Q</>:{} ", q);
-        }
-        else {
-            cprint!("<g>Q</>:{} ", q);
+    /// Parses a piece set such as `Q1A6` - a piece letter followed by its count, for each piece.
+    /// Pieces not given are left out, except the queen which there must always be exactly one of.
+    pub fn parse(spec: &str) -> Result<PieceBag, String> {
+        let mut counts = [0; TileType::VARIANT_COUNT];
+        counts[TileType::Queen.index()] = 1;
+
+        let mut chars = spec.trim().chars().peekable();
+        while let Some(c) = chars.next() {
+            let Some(tile_type) = TileType::from_character(c.to_ascii_uppercase()) else {
+                return Err(format!("Invalid piece '{c}' in piece set '{spec}'"));
+            };
+            let mut count = String::new();
+            while let Some(d) = chars.next_if(|d| d.is_ascii_digit()) {
+                count.push(d);
+            }
+            let Ok(count) = count.parse() else {
+                return Err(format!("Missing count for '{c}' in piece set '{spec}'"));
+            };
+            counts[tile_type.index()] = count;
         }
 
-        if turn {
-            cprint!("<c>A</>:{} ", self.ants);
+        if counts[TileType::Queen.index()] != 1 {
+            return Err("There must be exactly one queen".to_string());
         }
-        else {
-            cprint!("<g>A</>:{} ", self.ants);
+
+        Ok(PieceBag { counts })
+    }
+
+    pub fn print(&self, turn: bool) {
+        for i in 0..TileType::VARIANT_COUNT {
+            let tile_type = TileType::from_index(i).unwrap();
+            print!("{}:{} ", colour::team(turn, tile_type.character()), self.counts[i]);
         }
 
         println!();
     }
 
+    pub fn count(&self) -> usize {
+        self.counts.iter().sum()
+    }
+
+    pub fn count_of(&self, tile_type: TileType) -> usize {
+        self.counts[tile_type.index()]
+    }
+
     pub fn get_place_options(&self, is_queen_forced: bool) -> Vec<TileType> {
-        if is_queen_forced && self.queen {
+        if is_queen_forced && self.count_of(TileType::Queen) > 0 {
             return vec![TileType::Queen];
         }
 
         let mut options = Vec::with_capacity(TileType::VARIANT_COUNT);
 
-        if self.queen {
-            options.push(TileType::Queen);
-        }
-        if self.ants > 0 {
-            options.push(TileType::Ant);
+        for i in 0..TileType::VARIANT_COUNT {
+            if self.counts[i] > 0 {
+                options.push(TileType::from_index(i).unwrap());
+            }
         }
 
         options
     }
 
     pub fn use_piece(&mut self, tile_type: TileType) {
-        match tile_type {
-            TileType::Queen => {
-                debug_assert!(self.queen);
-                self.queen = false;
-            }
-            TileType::Ant => {
-                debug_assert!(self.ants > 0);
-                self.ants -= 1;
-            }
-        }
+        debug_assert!(self.counts[tile_type.index()] > 0);
+        self.counts[tile_type.index()] -= 1;
     }
 
     pub fn unuse_piece(&mut self, tile_type: TileType) {
-        match tile_type {
-            TileType::Queen => {
-                debug_assert!(!self.queen);
-                self.queen = true;
-            }
-            TileType::Ant => {
-                self.ants += 1;
-            }
-        }
+        self.counts[tile_type.index()] += 1;
     }
-}
\ No newline at end of file
+}
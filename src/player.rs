@@ -0,0 +1,686 @@
+use std::collections::VecDeque;
+use std::fs;
+use std::io::{stdin, stdout, Write};
+use std::sync::Arc;
+use std::time::Duration;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use crate::book::OpeningBook;
+use crate::colour;
+use crate::engine::Engine;
+use crate::evaluator::{DefaultEvaluator, EvalWeights};
+use crate::game_state::{parse_move_list, GameState, Move};
+use crate::hex_coord::HexCoord;
+use crate::mcts::{Mcts, MctsConfig, PlayoutPolicy};
+use crate::render;
+use crate::rules::{explain_illegal, explain_immobile};
+use crate::search::{self, describe_score, Searcher};
+use crate::tile_types::TileType;
+use crate::tui::{TuiPlayer, DEFAULT_EVAL_DEPTH};
+
+const DEFAULT_DEPTH: usize = 6;
+/// Depth of the search suggesting moves to human players - quick enough to feel instant
+pub const HINT_DEPTH: usize = 4;
+
+/// What a player does on their turn
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Choice {
+    Play(Move),
+    /// Take back the player's last move along with any reply to it, or just the last move when
+    /// both players are interactive
+    Undo,
+    /// Replay moves that were taken back
+    Redo,
+}
+
+pub trait Player {
+    /// Chooses a move for the side to move. Only called when at least one move is available -
+    /// passing is handled by the caller. Returning `None` ends the game early, e.g. when a
+    /// script runs out of moves.
+    fn choose_move(&mut self, game_state: &GameState) -> Option<Move>;
+
+    /// Chooses what to do on the player's turn, which for interactive players can be taking
+    /// moves back. Used by `Session` in place of `choose_move`.
+    fn choose(&mut self, game_state: &GameState) -> Option<Choice> {
+        self.choose_move(game_state).map(Choice::Play)
+    }
+
+    /// Called after each move of the game is played, by either side, with the position after
+    /// the move
+    fn observe_move(&mut self, _game_state: &GameState, _m: &Move) {}
+
+    /// Called after a move is taken back, with the position before the move
+    fn observe_undo(&mut self, _game_state: &GameState, _m: &Move) {}
+
+    /// Whether moves are chosen by a person, who may want to take back their own moves rather
+    /// than have the opponent's reply taken back with them
+    fn is_interactive(&self) -> bool { false }
+
+    fn name(&self) -> &str;
+}
+
+/// Settings shared by all players, which individual player specifications can override
+#[derive(Clone, Default)]
+pub struct PlayerOptions {
+    /// Seed for any randomness, `None` for a random seed
+    pub seed: Option<u64>,
+    /// Whether engines print their search statistics
+    pub verbose: bool,
+    /// Opening book used by engines
+    pub book: Option<Arc<OpeningBook>>,
+}
+
+/// Creates a player from a specification of the form `kind[:key=value,...]`:
+/// - `human`
+/// - `tui[:eval=N]` - full-screen board, showing the engine's evaluation at depth N (0 to disable)
+/// - `random[:seed=N]`
+/// - `minimax[:depth=N,threads=N,weights=FILE,ordering=false,book=FILE|false]` - also
+///   available as `engine`
+/// - `mcts[:iterations=N,time=MS,playout=random|pressure,seed=N,book=FILE|false]`
+/// - `script:file=FILE` - replays moves from a file
+pub fn create_player(spec: &str, player_options: &PlayerOptions) -> Result<Box<dyn Player>, String> {
+    let (kind, options) = spec.split_once(':').unwrap_or((spec, ""));
+    let options = options.split(',')
+        .filter(|o| !o.is_empty())
+        .map(|o| o.split_once('=').ok_or_else(|| format!("Invalid option '{o}' - expected 'key=value'")))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let parse = |key: &str, value: &str| -> Result<u64, String> {
+        value.parse().map_err(|_| format!("Invalid value '{value}' for '{key}'"))
+    };
+    let unknown = |key: &str| Err(format!("Unknown option '{key}' for player '{kind}'"));
+    // Engines use the shared book unless given their own or told not to use one
+    let book = |value: &str| -> Result<Option<Arc<OpeningBook>>, String> {
+        match value {
+            "false" => Ok(None),
+            path => Ok(Some(Arc::new(OpeningBook::load(path)?))),
+        }
+    };
+
+    Ok(match kind {
+        "human" => {
+            if let Some((key, _)) = options.first() {
+                return unknown(key);
+            }
+            Box::new(HumanPlayer::new())
+        }
+        "tui" => {
+            let mut eval_depth = DEFAULT_EVAL_DEPTH;
+            for (key, value) in options {
+                match key {
+                    "eval" => eval_depth = parse(key, value)? as usize,
+                    _ => return unknown(key),
+                }
+            }
+            Box::new(TuiPlayer::new(eval_depth))
+        }
+        "random" => {
+            let mut seed = player_options.seed;
+            for (key, value) in options {
+                match key {
+                    "seed" => seed = Some(parse(key, value)?),
+                    _ => return unknown(key),
+                }
+            }
+            Box::new(RandomPlayer::new(seed))
+        }
+        "minimax" | "engine" => {
+            let mut depth = DEFAULT_DEPTH;
+            let mut threads = search::default_threads();
+            let mut weights = EvalWeights::default();
+            let mut ordering = true;
+            let mut engine_book = player_options.book.clone();
+            for (key, value) in options {
+                match key {
+                    "depth" => depth = parse(key, value)? as usize,
+                    "threads" => threads = parse(key, value)? as usize,
+                    "weights" => weights = EvalWeights::load(value)?,
+                    // Allows the cut-off rate to be compared with the unordered search
+                    "ordering" => ordering = value != "false",
+                    "book" => engine_book = book(value)?,
+                    _ => return unknown(key),
+                }
+            }
+
+            let mut searcher = Searcher::with_evaluator(Box::new(DefaultEvaluator::new(weights)));
+            searcher.set_threads(threads);
+            searcher.set_ordering(ordering);
+            Box::new(EnginePlayer::new(Engine::Minimax { searcher, depth }, engine_book, player_options))
+        }
+        "mcts" => {
+            let mut config = MctsConfig {
+                seed: player_options.seed,
+                ..MctsConfig::default()
+            };
+            let mut engine_book = player_options.book.clone();
+            for (key, value) in options {
+                match key {
+                    "iterations" => config.iterations = Some(parse(key, value)? as usize),
+                    "time" => {
+                        config.time_limit = Some(Duration::from_millis(parse(key, value)?));
+                        config.iterations = None;
+                    }
+                    "playout" => config.playout = match value {
+                        "random" => PlayoutPolicy::Random,
+                        "pressure" => PlayoutPolicy::QueenPressure,
+                        _ => return Err(format!("Unknown playout policy '{value}'")),
+                    },
+                    "seed" => config.seed = Some(parse(key, value)?),
+                    "book" => engine_book = book(value)?,
+                    _ => return unknown(key),
+                }
+            }
+            Box::new(EnginePlayer::new(Engine::Mcts(Box::new(Mcts::new(config))), engine_book, player_options))
+        }
+        "script" => {
+            let mut file = None;
+            for (key, value) in options {
+                match key {
+                    "file" => file = Some(value),
+                    _ => return unknown(key),
+                }
+            }
+            let Some(file) = file else {
+                return Err("Scripted player requires 'file=FILE'".to_string());
+            };
+            Box::new(ScriptedPlayer::load(file)?)
+        }
+        _ => return Err(format!("Unknown player '{kind}'")),
+    })
+}
+
+/// Flushes any prompt and reads a line, returning `None` once input has ended
+fn read_input() -> Option<String> {
+    stdout().flush().unwrap();
+    let mut input = String::new();
+    match stdin().read_line(&mut input) {
+        Ok(0) | Err(_) => None,
+        Ok(_) => Some(input),
+    }
+}
+
+/// Parses a grid square as `x y`, as labelled on boards drawn as a grid, or an axial coordinate as
+/// `x,y`
+fn parse_location(input: &str) -> Option<HexCoord> {
+    let input = input.trim();
+    if input.contains(',') {
+        return input.parse().ok();
+    }
+    let mut split = input.split_whitespace();
+    let x = split.next()?.parse().ok()?;
+    let y = split.next()?.parse().ok()?;
+    if split.next().is_some() { return None; }
+    HexCoord::try_from_square(x, y)
+}
+
+/// Asks for a coordinate in the form shown on the board - grid squares are only labelled when
+/// the board is drawn as a grid, otherwise empty cells show their axial coordinate
+fn prompt_location(action: &str, piece: bool) -> String {
+    let forms = match render::style() {
+        render::BoardStyle::Grid => "grid 'x y' e.g. '3 1' or axial 'x,y'",
+        _ => "'x,y' as shown in the empty cells",
+    };
+    format!("Enter where to {action} - {forms}{}", if piece { ", or a piece e.g. 'A2'" } else { "" })
+}
+
+/// Finds one of the team's pieces by label, e.g. `A2`. The number can be left out when the team
+/// only has one piece of that type on the board.
+fn find_piece(game_state: &GameState, team: bool, label: &str) -> Result<HexCoord, String> {
+    let mut chars = label.chars();
+    let piece = chars.next()
+        .and_then(|c| TileType::from_character(c.to_ascii_uppercase()))
+        .ok_or_else(|| format!("Invalid piece '{label}'"))?;
+    let ordinal = match chars.as_str() {
+        "" => None,
+        n => Some(n.parse::<usize>().map_err(|_| format!("Invalid piece '{label}'"))?),
+    };
+
+    let matching = game_state.pieces_on_board(team).into_iter()
+        .filter(|(tile, _)| tile.tile_type() == piece && ordinal.is_none_or(|o| tile.ordinal() == o))
+        .collect::<Vec<_>>();
+    match matching[..] {
+        [(_, location)] => Ok(location),
+        [] => Err(format!("No piece {label} on the board")),
+        _ => Err(format!("More than one {label} on the board - include its number, e.g. {}", matching[0].0.label())),
+    }
+}
+
+/// Parses a move in notation, e.g. `A@-1,0` or `0,0>1,-1`, or a piece label in place of the
+/// starting coordinate, e.g. `A2>1,-1`
+fn parse_move_input(game_state: &GameState, input: &str) -> Result<Move, String> {
+    if let Some((from, to)) = input.split_once('>') {
+        if from.trim().starts_with(|c: char| c.is_ascii_alphabetic()) {
+            let from = find_piece(game_state, game_state.turn(), from.trim())?;
+            return Ok(Move::Move(from, to.parse()?));
+        }
+    }
+    input.parse()
+}
+
+/// Suggests a move for the side to move, along with a description including the engine's
+/// evaluation
+pub fn hint(searcher: &mut Searcher, game_state: &GameState) -> Option<(Move, String)> {
+    let (m, score) = searcher.best_move(&mut game_state.clone(), HINT_DEPTH)?;
+    let description = format!("Hint: {m} - evaluation {}", describe_score(score));
+    Some((m, description))
+}
+
+/// Lists the legal moves for players, either all of them or those of the piece at `from`
+pub fn describe_moves(game_state: &GameState, from: Option<HexCoord>) -> Vec<String> {
+    let moves = game_state.get_possible_moves();
+    let join = |mut locations: Vec<HexCoord>| {
+        locations.sort();
+        locations.iter().map(|l| l.to_string()).collect::<Vec<_>>().join("  ")
+    };
+
+    let mut lines = Vec::new();
+    if from.is_none() {
+        for tile_type in game_state.turn_piece_bag().get_place_options(game_state.force_queen()) {
+            let locations = moves.iter()
+                .filter_map(|m| match m {
+                    Move::Place(t, to) if *t == tile_type => Some(*to),
+                    _ => None,
+                })
+                .collect::<Vec<_>>();
+            if !locations.is_empty() {
+                lines.push(format!("Place {} at: {}", tile_type.character(), join(locations)));
+            }
+        }
+    }
+
+    let pieces = match from {
+        Some(from) => {
+            if let Some(reason) = explain_immobile(game_state, from) {
+                return vec![reason];
+            }
+            vec![from]
+        }
+        None => game_state.pieces_on_board(game_state.turn()).into_iter().map(|(_, loc)| loc).collect(),
+    };
+    for from in pieces {
+        let destinations = moves.iter()
+            .filter_map(|m| match m {
+                Move::Move(f, to) if *f == from => Some(*to),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        if !destinations.is_empty() {
+            lines.push(format!("Move {} ({from}) to: {}", game_state.tiles()[&from].top().label(), join(destinations)));
+        }
+    }
+    lines
+}
+
+/// Interactive player choosing moves through the terminal
+#[derive(Default)]
+pub struct HumanPlayer {
+    /// Created the first time a hint is asked for
+    hints: Option<Searcher>,
+}
+
+impl HumanPlayer {
+    pub fn new() -> HumanPlayer {
+        HumanPlayer::default()
+    }
+}
+
+impl Player for HumanPlayer {
+    fn choose_move(&mut self, game_state: &GameState) -> Option<Move> {
+        // Taking moves back needs the history kept by a session
+        loop {
+            match self.choose(game_state)? {
+                Choice::Play(m) => return Some(m),
+                _ => println!("Moves can't be taken back here"),
+            }
+        }
+    }
+
+    fn choose(&mut self, game_state: &GameState) -> Option<Choice> {
+        let moves = game_state.get_possible_moves();
+
+        let mut movements = Vec::new();
+        let mut placements = Vec::new();
+        for m in &moves {
+            match m {
+                Move::Place(tile, to) => {
+                    placements.push((*tile, *to));
+                }
+                Move::Move(from, to) => {
+                    movements.push((*from, *to));
+                }
+            }
+        }
+
+        let mut first_loop = true;
+        let mut error = None;
+        // Output of 'hint' and 'moves'
+        let mut notes = Vec::new();
+
+        loop {
+            if !first_loop {
+                render::print_board(game_state);
+                println!();
+                for note in notes.drain(..) {
+                    println!("{note}");
+                }
+                if let Some(e) = error.take() {
+                    println!("Error: {e}");
+                }
+            }
+            first_loop = false;
+
+            let team = game_state.turn();
+            let on_board = game_state.pieces_on_board(team);
+            if !on_board.is_empty() {
+                let pieces = on_board.iter().map(|(tile, loc)| format!("{} {}", tile.label(), loc)).collect::<Vec<_>>();
+                println!("On board: {}", colour::team(team, pieces.join("  ")));
+            }
+            print!("To Place: ");
+            game_state.turn_piece_bag().print(team);
+
+            if placements.is_empty() {
+                println!("X| No placements available");
+            }
+            else {
+                println!("1| Place piece");
+            }
+
+            if movements.is_empty() {
+                println!("X| No movements available");
+            }
+            else {
+                println!("2| Move piece");
+            }
+            println!("Or enter a move, e.g. 'A@-1,0', '0,0>1,-1' or 'A2>1,-1', 'hint', 'moves [PIECE]', 'undo' or 'redo'");
+
+            print!("> ");
+            let input = read_input()?;
+
+            match input.trim() {
+                "undo" => return Some(Choice::Undo),
+                "redo" => return Some(Choice::Redo),
+                "hint" => {
+                    let searcher = self.hints.get_or_insert_with(Searcher::new);
+                    if let Some((_, description)) = hint(searcher, game_state) {
+                        notes.push(description);
+                    }
+                    continue;
+                }
+                "moves" => {
+                    notes = describe_moves(game_state, None);
+                    continue;
+                }
+                _ => {}
+            }
+            if let Some(piece) = input.trim().strip_prefix("moves ") {
+                match parse_location(piece).map_or_else(|| find_piece(game_state, team, piece.trim()), Ok) {
+                    Ok(from) => notes = describe_moves(game_state, Some(from)),
+                    Err(e) => error = Some(e),
+                }
+                continue;
+            }
+            let Ok(c) = input.trim().parse::<usize>() else {
+                match parse_move_input(game_state, input.trim()) {
+                    Ok(m) if moves.contains(&m) => return Some(Choice::Play(m)),
+                    Ok(m) => error = explain_illegal(game_state, &m),
+                    Err(e) => error = Some(e),
+                }
+                continue;
+            };
+
+            if c == 1 && !placements.is_empty() {
+                println!("Select piece to place");
+                print!("> ");
+                let input = read_input()?;
+                let input = input.trim();
+                if input.chars().count() != 1 {
+                    error = Some("Invalid piece name".to_string());
+                    continue;
+                }
+                let c = input.chars().next().unwrap().to_ascii_uppercase();
+                let Some(piece) = TileType::from_character(c) else {
+                    error = Some("Invalid piece name".to_string());
+                    continue;
+                };
+                if !placements.iter().any(|(t, _)| t == &piece) {
+                    // Any location a piece can be placed at, so that the reason is the piece
+                    error = explain_illegal(game_state, &Move::Place(piece, placements[0].1));
+                    continue;
+                }
+
+                if game_state.turn_count() == 0 {
+                    return Some(Choice::Play(Move::Place(piece, HexCoord::new(0, 0))));
+                }
+                else if game_state.turn_count() == 1 {
+                    return Some(Choice::Play(Move::Place(piece, HexCoord::new(1, 0))));
+                }
+
+                println!("{}", prompt_location("place at", false));
+                print!("> ");
+                let input = read_input()?;
+
+                let Some(hex_coordinates) = parse_location(&input) else {
+                    error = Some("Invalid coordinates".to_string());
+                    continue;
+                };
+
+                if !placements.iter().any(|(t, p)| t == &piece && p == &hex_coordinates) {
+                    error = explain_illegal(game_state, &Move::Place(piece, hex_coordinates));
+                    continue;
+                }
+                return Some(Choice::Play(Move::Place(piece, hex_coordinates)));
+            }
+            else if c == 2 && !movements.is_empty() {
+                println!("{}", prompt_location("move from", true));
+                print!("> ");
+                let input = read_input()?;
+
+                let from_coords = match parse_location(&input) {
+                    Some(from) => from,
+                    None => match find_piece(game_state, team, input.trim()) {
+                        Ok(from) => from,
+                        Err(e) => {
+                            error = Some(e);
+                            continue;
+                        }
+                    },
+                };
+                if !movements.iter().any(|(f, _)| f == &from_coords) {
+                    error = explain_immobile(game_state, from_coords);
+                    continue;
+                }
+
+                println!("{}", prompt_location("move to", false));
+                print!("> ");
+                let input = read_input()?;
+
+                let Some(to_coords) = parse_location(&input) else {
+                    error = Some("Invalid coordinates".to_string());
+                    continue;
+                };
+                if !movements.iter().any(|(f, t)| f == &from_coords && t == &to_coords) {
+                    error = explain_illegal(game_state, &Move::Move(from_coords, to_coords));
+                    continue;
+                }
+
+                return Some(Choice::Play(Move::Move(from_coords, to_coords)));
+            }
+            else {
+                error = Some("Invalid selection".to_string());
+            }
+        }
+    }
+
+    fn is_interactive(&self) -> bool { true }
+
+    fn name(&self) -> &str { "Human" }
+}
+
+pub struct RandomPlayer {
+    rng: StdRng,
+}
+
+impl RandomPlayer {
+    pub fn new(seed: Option<u64>) -> RandomPlayer {
+        RandomPlayer {
+            rng: match seed {
+                Some(seed) => StdRng::seed_from_u64(seed),
+                None => StdRng::from_entropy(),
+            },
+        }
+    }
+}
+
+impl Player for RandomPlayer {
+    fn choose_move(&mut self, game_state: &GameState) -> Option<Move> {
+        let mut moves = game_state.get_possible_moves();
+        // Move generation order depends on hash map iteration so sort to stay deterministic
+        moves.sort();
+        let i = self.rng.gen_range(0..moves.len());
+        moves.into_iter().nth(i)
+    }
+
+    fn name(&self) -> &str { "Random" }
+}
+
+pub struct EnginePlayer {
+    engine: Engine,
+    book: Option<Arc<OpeningBook>>,
+    /// Used to pick between book moves
+    rng: StdRng,
+    verbose: bool,
+}
+
+impl EnginePlayer {
+    pub fn new(engine: Engine, book: Option<Arc<OpeningBook>>, options: &PlayerOptions) -> EnginePlayer {
+        EnginePlayer {
+            engine,
+            book,
+            rng: match options.seed {
+                Some(seed) => StdRng::seed_from_u64(seed),
+                None => StdRng::from_entropy(),
+            },
+            verbose: options.verbose,
+        }
+    }
+}
+
+impl Player for EnginePlayer {
+    fn choose_move(&mut self, game_state: &GameState) -> Option<Move> {
+        if let Some(m) = self.book.as_ref().and_then(|b| b.choose(game_state, &mut self.rng)) {
+            if self.verbose {
+                let games = self.book.as_ref().unwrap().moves(game_state).iter().map(|e| e.games()).sum::<u32>();
+                println!("Book move ({games} games)");
+            }
+            return Some(m);
+        }
+
+        if self.verbose {
+            println!("Working...");
+        }
+
+        let (best_move, score) = self.engine.best_move(&mut game_state.clone())?;
+
+        if self.verbose {
+            self.engine.print_stats();
+            println!("Score: {score}");
+        }
+
+        debug_assert!(
+            match &best_move {
+                Move::Place(_, _) => { true }
+                Move::Move(a, b) => {
+                    a != b
+                }
+            }
+        );
+
+        Some(best_move)
+    }
+
+    fn name(&self) -> &str {
+        match self.engine {
+            Engine::Minimax { .. } => "Minimax",
+            Engine::Mcts(_) => "MCTS",
+        }
+    }
+}
+
+/// Replays a fixed list of moves, ending the game once they run out
+pub struct ScriptedPlayer {
+    moves: VecDeque<Move>,
+}
+
+impl ScriptedPlayer {
+    pub fn new(moves: Vec<Move>) -> ScriptedPlayer {
+        ScriptedPlayer { moves: moves.into() }
+    }
+
+    /// Loads a move list from a file - see `parse_move_list`
+    pub fn load(path: &str) -> Result<ScriptedPlayer, String> {
+        let contents = fs::read_to_string(path).map_err(|e| format!("Failed to read '{path}': {e}"))?;
+        Ok(ScriptedPlayer::new(parse_move_list(&contents)?))
+    }
+}
+
+impl Player for ScriptedPlayer {
+    fn choose_move(&mut self, _game_state: &GameState) -> Option<Move> {
+        self.moves.pop_front()
+    }
+
+    fn name(&self) -> &str { "Script" }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn midgame() -> GameState {
+        let mut game = GameState::new();
+        game.apply_moves(&parse_move_list("A@0,0 A@1,0 Q@-1,0 Q@2,0 A@-1,1 A@3,-1").unwrap()).unwrap();
+        game
+    }
+
+    #[test]
+    fn locations_are_absolute() {
+        assert_eq!(parse_location("-2 0"), Some(HexCoord::new(-1, 0)));
+        assert_eq!(parse_location(" -1 1 "), Some(HexCoord::new(-1, 1)));
+        assert_eq!(parse_location("-1,1"), Some(HexCoord::new(-1, 1)));
+        // Second column of a hexagon
+        assert_eq!(parse_location("-1 0"), None);
+        assert_eq!(parse_location("1 2 3"), None);
+    }
+
+    #[test]
+    fn pieces_found_by_label() {
+        let game = midgame();
+        assert_eq!(find_piece(&game, true, "Q"), Ok(HexCoord::new(-1, 0)));
+        assert_eq!(find_piece(&game, true, "a1"), Ok(HexCoord::new(0, 0)));
+        assert_eq!(find_piece(&game, true, "A2"), Ok(HexCoord::new(-1, 1)));
+        assert_eq!(find_piece(&game, false, "A2"), Ok(HexCoord::new(3, -1)));
+        assert!(find_piece(&game, true, "A").is_err());
+        assert!(find_piece(&game, true, "A3").is_err());
+        assert!(find_piece(&game, true, "X1").is_err());
+    }
+
+    #[test]
+    fn moves_by_label_or_notation() {
+        let game = midgame();
+        assert_eq!(parse_move_input(&game, "A2>0,1"), Ok(Move::Move(HexCoord::new(-1, 1), HexCoord::new(0, 1))));
+        assert_eq!(parse_move_input(&game, "-1,1>0,1"), Ok(Move::Move(HexCoord::new(-1, 1), HexCoord::new(0, 1))));
+        assert_eq!(parse_move_input(&game, "A@-2,1"), Ok(Move::Place(TileType::Ant, HexCoord::new(-2, 1))));
+        assert!(parse_move_input(&game, "A>0,1").is_err());
+    }
+
+    #[test]
+    fn moves_described() {
+        let game = midgame();
+        let all = describe_moves(&game, None);
+        assert_eq!(all[0], "Place A at: -2,0  -2,1  -2,2  -1,-1  -1,2  0,-1");
+        // The first ant holds the hive together
+        assert_eq!(&all[1..], ["Move Q (-1,0) to: -2,1  0,-1", "Move A2 (-1,1) to: -2,1  0,1"]);
+
+        assert_eq!(describe_moves(&game, Some(HexCoord::new(-1, 1))), ["Move A2 (-1,1) to: -2,1  0,1"]);
+        assert!(describe_moves(&game, Some(HexCoord::new(0, 0)))[0].contains("split the hive"));
+    }
+}
@@ -0,0 +1,338 @@
+use std::collections::HashSet;
+use std::fmt::{self, Display, Formatter};
+use std::fs;
+use std::sync::atomic::AtomicBool;
+use crate::game_state::{parse_move_list, GameState, Move};
+use crate::rules;
+use crate::search::Searcher;
+
+pub const DEFAULT_MAX_MOVES: usize = 3;
+
+/// A position where exactly one move starts a forced surround of the enemy queen
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Puzzle {
+    /// Moves from the starting position
+    pub position: Vec<Move>,
+    /// The winning line, alternating between the winner's moves and the best defence
+    pub solution: Vec<Move>,
+}
+
+impl Puzzle {
+    /// Parses `POSITION | SOLUTION`, ignoring anything after a `#`
+    pub fn parse(s: &str) -> Result<Puzzle, String> {
+        let s = s.split('#').next().unwrap();
+        let Some((position, solution)) = s.split_once('|') else {
+            return Err("Expected 'POSITION | SOLUTION'".to_string());
+        };
+        let solution = parse_move_list(solution)?;
+        if solution.is_empty() {
+            return Err("Puzzle has no solution".to_string());
+        }
+        Ok(Puzzle { position: parse_move_list(position)?, solution })
+    }
+
+    /// Reads puzzles one per line, skipping blank and comment lines
+    pub fn load(path: &str) -> Result<Vec<Puzzle>, String> {
+        let contents = fs::read_to_string(path).map_err(|e| format!("Failed to read '{path}': {e}"))?;
+        contents.lines()
+            .enumerate()
+            .filter(|(_, line)| !line.split('#').next().unwrap().trim().is_empty())
+            .map(|(i, line)| Puzzle::parse(line).map_err(|e| format!("{path} line {}: {e}", i + 1)))
+            .collect()
+    }
+
+    /// Moves the winner needs to surround the queen when played from `start`
+    pub fn moves(&self, start: &GameState) -> Result<usize, String> {
+        let mut game = start.clone();
+        game.apply_moves(&self.position)?;
+        Ok(winner_moves(&game, &self.solution))
+    }
+}
+
+impl Display for Puzzle {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let join = |moves: &[Move]| moves.iter().map(|m| m.to_string()).collect::<Vec<_>>().join(" ");
+        write!(f, "{} | {}", join(&self.position), join(&self.solution))
+    }
+}
+
+fn win_score(team: bool) -> isize {
+    if team { isize::MAX } else { isize::MIN }
+}
+
+/// Passes for the player to move if they have no moves, as players do in games
+fn pass_if_stuck(game_state: &mut GameState) {
+    if game_state.get_possible_moves().is_empty() {
+        game_state.pass();
+    }
+}
+
+/// Counts the moves of the player to move in `line`. Passes aren't written in lines so they
+/// don't simply alternate between the players.
+fn winner_moves(game_state: &GameState, line: &[Move]) -> usize {
+    let mut game = game_state.clone();
+    pass_if_stuck(&mut game);
+    let team = game.turn();
+    let mut count = 0;
+    for m in line {
+        pass_if_stuck(&mut game);
+        count += (game.turn() == team) as usize;
+        game.apply_move(m.clone());
+    }
+    count
+}
+
+/// Plays `line`, returning its moves up to the one that wins the game for `team`, if any does
+fn winning_prefix(game_state: &GameState, line: &[Move], team: bool) -> Option<Vec<Move>> {
+    let mut game = game_state.clone();
+    for (i, m) in line.iter().enumerate() {
+        pass_if_stuck(&mut game);
+        if !game.get_possible_moves().contains(m) {
+            return None;
+        }
+        game.apply_move(m.clone());
+        let (_, w, b) = game.score();
+        if w || b {
+            return (w != b && w == team).then(|| line[..=i].to_vec());
+        }
+    }
+    None
+}
+
+/// Searches for a forced win for the player to move within `max_moves` of their moves. Returns
+/// the shortest winning line, checked by playing it out, if no other first move wins as quickly.
+pub fn unique_win(searcher: &mut Searcher, game_state: &GameState, max_moves: usize) -> Option<Vec<Move>> {
+    let team = game_state.turn();
+    let stop = AtomicBool::new(false);
+    // Searching to depth d looks d + 2 moves ahead
+    for depth in 0..=(2 * max_moves).saturating_sub(3) {
+        let lines = searcher.search(&mut game_state.clone(), depth, 2, &stop);
+        let best = lines.first()?;
+        if best.score != win_score(team) {
+            continue;
+        }
+        if lines.get(1).is_some_and(|l| l.score == win_score(team)) {
+            return None;
+        }
+        return winning_prefix(game_state, &best.moves, team).filter(|line| winner_moves(game_state, line) <= max_moves);
+    }
+    None
+}
+
+/// Finds puzzles among the positions of a game played from `start`. Positions whose canonical
+/// hash is in `seen` are skipped, and those checked are added to it.
+pub fn find_puzzles(searcher: &mut Searcher, start: &GameState, moves: &[Move], max_moves: usize, seen: &mut HashSet<u64>) -> Result<Vec<Puzzle>, String> {
+    start.clone().apply_moves(moves)?;
+
+    let mut game = start.clone();
+    let mut puzzles = Vec::new();
+    for ply in 0..=moves.len() {
+        if ply > 0 {
+            pass_if_stuck(&mut game);
+            game.apply_move(moves[ply - 1].clone());
+        }
+        let (_, w, b) = game.score();
+        if w || b {
+            break;
+        }
+
+        let mut position = game.clone();
+        pass_if_stuck(&mut position);
+        // A single legal move doesn't make much of a puzzle
+        if position.get_possible_moves().len() < 2 || !seen.insert(position.canonical_hash()) {
+            continue;
+        }
+        if let Some(solution) = unique_win(searcher, &position, max_moves) {
+            puzzles.push(Puzzle { position: moves[..ply].to_vec(), solution });
+        }
+    }
+    Ok(puzzles)
+}
+
+/// Result of trying a move in a puzzle
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Answer {
+    /// The move keeps a forced win in time, followed by the opponent's reply - `None` if they
+    /// had to pass
+    Correct(Option<Move>),
+    Solved,
+    /// Why the move doesn't work
+    Wrong(String),
+}
+
+/// Progress through a puzzle, checking the solver's moves
+pub struct Attempt {
+    game: GameState,
+    team: bool,
+    /// Expected continuation from the current position
+    line: Vec<Move>,
+    /// Moves the solver has left to win within
+    moves_left: usize,
+}
+
+impl Attempt {
+    pub fn new(start: &GameState, puzzle: &Puzzle) -> Result<Attempt, String> {
+        let mut game = start.clone();
+        game.apply_moves(&puzzle.position)?;
+        pass_if_stuck(&mut game);
+        let moves_left = winner_moves(&game, &puzzle.solution);
+        Ok(Attempt { team: game.turn(), game, line: puzzle.solution.clone(), moves_left })
+    }
+
+    pub fn game(&self) -> &GameState { &self.game }
+
+    /// The team solving the puzzle
+    pub fn team(&self) -> bool { self.team }
+
+    pub fn moves_left(&self) -> usize { self.moves_left }
+
+    /// The next move of the expected solution
+    pub fn hint(&self) -> Option<&Move> {
+        self.line.first()
+    }
+
+    /// Plays the move if it keeps a forced win within the moves left, along with the opponent's
+    /// best reply. Moves other than the expected one are checked with `searcher`.
+    pub fn answer(&mut self, searcher: &mut Searcher, m: &Move) -> Answer {
+        if let Some(reason) = rules::explain_illegal(&self.game, m) {
+            return Answer::Wrong(reason);
+        }
+        let mut after = self.game.clone();
+        after.apply_move(m.clone());
+        match after.score() {
+            (_, true, true) => return Answer::Wrong("That surrounds both queens - a draw".to_string()),
+            (_, w, b) if w || b => {
+                return if w == self.team {
+                    self.game = after;
+                    Answer::Solved
+                }
+                else {
+                    Answer::Wrong("That surrounds your own queen".to_string())
+                };
+            }
+            _ => {}
+        }
+        if self.moves_left <= 1 {
+            return Answer::Wrong("That doesn't surround the queen".to_string());
+        }
+
+        let passed = after.get_possible_moves().is_empty();
+        if passed {
+            after.pass();
+        }
+        let mut continuation = if self.line.first() == Some(m) {
+            self.line[1..].to_vec()
+        }
+        else {
+            // Looking ahead just far enough for the winner's remaining moves - searching to depth
+            // d looks d + 2 moves ahead, and after a pass the winner's next move is the first
+            let depth = if passed { (2 * self.moves_left).saturating_sub(5) } else { 2 * self.moves_left - 4 };
+            match searcher.search(&mut after.clone(), depth, 1, &AtomicBool::new(false)).into_iter().next() {
+                Some(line) if line.score == win_score(self.team) => line.moves,
+                _ => return Answer::Wrong("That lets the opponent escape".to_string()),
+            }
+        };
+
+        let reply = if passed || continuation.is_empty() { None } else { Some(continuation.remove(0)) };
+        if let Some(reply) = &reply {
+            after.apply_move(reply.clone());
+            let (_, w, b) = after.score();
+            if w || b {
+                self.game = after;
+                return if w == self.team && !b { Answer::Solved } else { Answer::Wrong("The opponent escapes".to_string()) };
+            }
+        }
+        pass_if_stuck(&mut after);
+        self.game = after;
+        self.line = continuation;
+        self.moves_left -= 1;
+        Answer::Correct(reply)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A game ending with Green surrounding Cyan's queen at 0,0
+    fn game() -> Vec<Move> {
+        parse_move_list(
+            "Q@0,0 A@1,0 A@-1,1 A@1,1 A@-1,2 Q@1,2 A@-2,3 A@2,-1 -2,3>-1,3 1,2>0,3 A@-2,2 2,-1>1,-1 \
+            A@-2,1 A@1,-2 A@-3,2 1,-2>0,-1 -2,2>-3,3 1,1>0,1 -3,3>-4,3 A@-1,-1 -4,3>-4,2 -1,-1>-1,0"
+        ).unwrap()
+    }
+
+    #[test]
+    fn puzzle_round_trip() {
+        let puzzle = Puzzle::parse("Q@0,0 Q@1,0 | A@0,-1 A@2,0 A@-1,1 # Cyan wins in 2").unwrap();
+        assert_eq!(puzzle.position.len(), 2);
+        assert_eq!(puzzle.moves(&GameState::new()), Ok(2));
+        assert_eq!(puzzle.to_string(), "Q@0,0 Q@1,0 | A@0,-1 A@2,0 A@-1,1");
+        assert_eq!(Puzzle::parse(&puzzle.to_string()).unwrap(), puzzle);
+        assert!(Puzzle::parse("Q@0,0 Q@1,0").is_err());
+        assert!(Puzzle::parse("Q@0,0 | ").is_err());
+    }
+
+    #[test]
+    fn finds_and_checks_puzzles() {
+        let moves = game();
+        let mut searcher = Searcher::new();
+        searcher.set_threads(1);
+        let mut seen = HashSet::new();
+        let puzzles = find_puzzles(&mut searcher, &GameState::new(), &moves, 2, &mut seen).unwrap();
+        assert!(puzzles.contains(&Puzzle { position: moves[..21].to_vec(), solution: moves[21..].to_vec() }));
+        // Positions already checked aren't searched again
+        assert!(find_puzzles(&mut searcher, &GameState::new(), &moves, 2, &mut seen).unwrap().is_empty());
+
+        for puzzle in &puzzles {
+            let mut attempt = Attempt::new(&GameState::new(), puzzle).unwrap();
+            let team = attempt.team();
+            let mut solution = puzzle.solution.iter();
+            loop {
+                let m = solution.next().unwrap().clone();
+                match attempt.answer(&mut searcher, &m) {
+                    Answer::Correct(reply) => assert_eq!(reply.as_ref(), solution.next()),
+                    Answer::Solved => break,
+                    Answer::Wrong(reason) => panic!("{puzzle}: {m} - {reason}"),
+                }
+            }
+            assert!(solution.next().is_none());
+            let (_, w, b) = attempt.game().score();
+            assert_eq!((w, b), (team, !team));
+
+            let wrong = Attempt::new(&GameState::new(), puzzle).unwrap().game().get_possible_moves()
+                .into_iter()
+                .find(|m| m != &puzzle.solution[0])
+                .unwrap();
+            let mut attempt = Attempt::new(&GameState::new(), puzzle).unwrap();
+            assert!(matches!(attempt.answer(&mut searcher, &wrong), Answer::Wrong(_)), "{puzzle}: {wrong}");
+        }
+    }
+
+    #[test]
+    fn solves_line_with_pass() {
+        let position = parse_move_list(
+            "A@0,0 Q@1,0 A@-1,0 1,0>1,-1 A@-1,1 1,-1>1,0 Q@-2,2 1,0>0,1 A@-2,3 0,1>-1,2 -1,0>-2,1 A@0,2 0,0>0,1 \
+            0,2>-1,3 A@-1,0 A@-1,4 A@1,1 A@0,4 -2,3>-2,4 A@1,4 -2,4>-2,3 A@2,3 -2,1>-2,0 A@2,2 1,1>2,1"
+        ).unwrap();
+        // Cyan's first move leaves Green without moves, so Green passes before Cyan's second
+        let puzzle = Puzzle { position, solution: parse_move_list("2,1>1,2 1,2>0,2").unwrap() };
+        let mut searcher = Searcher::new();
+        searcher.set_threads(1);
+
+        assert_eq!(puzzle.moves(&GameState::new()), Ok(2));
+        let mut attempt = Attempt::new(&GameState::new(), &puzzle).unwrap();
+        assert!(attempt.team());
+        assert_eq!(attempt.answer(&mut searcher, &puzzle.solution[0]), Answer::Correct(None));
+        assert_eq!(attempt.game().turn(), attempt.team());
+        assert_eq!(attempt.answer(&mut searcher, &puzzle.solution[1]), Answer::Solved);
+
+        // Checked by searching, which has the solver to move again after the pass, when the
+        // expected line isn't known
+        let mut attempt = Attempt::new(&GameState::new(), &puzzle).unwrap();
+        attempt.line.clear();
+        assert_eq!(attempt.answer(&mut searcher, &puzzle.solution[0]), Answer::Correct(None));
+        assert_eq!(attempt.moves_left(), 1);
+        assert_eq!(attempt.hint(), Some(&puzzle.solution[1]));
+    }
+}
@@ -0,0 +1,219 @@
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU8, Ordering};
+use crate::colour;
+use crate::game_state::GameState;
+use crate::hex_coord::HexCoord;
+
+/// How boards are drawn when printed
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BoardStyle {
+    /// Compact doubled grid labelled with grid coordinates - see `GameState::print`
+    Grid,
+    /// Hexagon cells drawn with ASCII characters
+    Ascii,
+    /// Hexagon cells drawn with Unicode box drawing characters
+    Unicode,
+}
+
+impl FromStr for BoardStyle {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "grid" => BoardStyle::Grid,
+            "ascii" => BoardStyle::Ascii,
+            "unicode" => BoardStyle::Unicode,
+            _ => return Err(format!("Unknown board style '{s}' - expected 'grid', 'ascii' or 'unicode'")),
+        })
+    }
+}
+
+static STYLE: AtomicU8 = AtomicU8::new(BoardStyle::Ascii as u8);
+
+pub fn set_style(style: BoardStyle) {
+    STYLE.store(style as u8, Ordering::Relaxed);
+}
+
+pub fn style() -> BoardStyle {
+    match STYLE.load(Ordering::Relaxed) {
+        0 => BoardStyle::Grid,
+        1 => BoardStyle::Ascii,
+        _ => BoardStyle::Unicode,
+    }
+}
+
+/// Prints the board in the current style
+pub fn print_board(game_state: &GameState) {
+    match style() {
+        BoardStyle::Grid => game_state.print(),
+        style => print!("{}", render_hexes(game_state, style == BoardStyle::Unicode)),
+    }
+}
+
+/// Columns between the left sides of horizontally adjacent cells
+const CELL_WIDTH: usize = 6;
+/// Lines between the tops of vertically adjacent rows, which interlock
+const ROW_HEIGHT: usize = 4;
+/// Width of the text inside a cell
+const INTERIOR_WIDTH: usize = CELL_WIDTH - 1;
+
+/// Marks drawn in a cell's second line when choosing moves interactively
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CellMarks {
+    pub cursor: bool,
+    /// The piece chosen to move
+    pub selected: bool,
+    /// Where the chosen piece can go
+    pub destination: bool,
+}
+
+/// Cells drawn by `render_hexes` - the hive and the empty cells around it, or just the origin
+/// when the board is empty
+pub fn board_cells(game_state: &GameState) -> Vec<HexCoord> {
+    let mut cells = game_state.tiles().keys().copied().collect::<Vec<_>>();
+    for loc in game_state.tiles().keys() {
+        cells.extend(loc.surrounding().into_iter().filter(|s| !game_state.tiles().contains_key(s)));
+    }
+    if cells.is_empty() {
+        cells.push(HexCoord::new(0, 0));
+    }
+    cells.sort();
+    cells.dedup();
+    cells
+}
+
+/// Draws the hive as hexagon cells along with the empty cells around it. Pieces show their label
+/// (see `HiveTile::label`) in their team's colour - upper case for Cyan and lower case for Green -
+/// with the stack height beneath when more than one high. Empty cells show their coordinate.
+///
+/// Each cell is drawn as below, with rows shifted by half a cell so that neighbouring cells share
+/// edges:
+/// ```text
+///   / \
+///  /   \
+/// | A2  |
+/// |     |
+///  \   /
+///   \ /
+/// ```
+pub fn render_hexes(game_state: &GameState, unicode: bool) -> String {
+    let mut output = String::new();
+    for row in hex_canvas(game_state, unicode, |_| CellMarks::default()) {
+        let end = row.iter().rposition(|c| c != " ").map_or(0, |i| i + 1);
+        output += &row[..end].concat();
+        output.push('\n');
+    }
+    output
+}
+
+/// Draws the board as in `render_hexes` with marks on some cells, returning each line as a list
+/// of characters. Characters are kept separately so that colour codes don't affect positions.
+pub fn hex_canvas(game_state: &GameState, unicode: bool, marks: impl Fn(HexCoord) -> CellMarks) -> Vec<Vec<String>> {
+    let (rising, falling, side) = if unicode { ('╱', '╲', '│') } else { ('/', '\\', '|') };
+
+    let cells = board_cells(game_state);
+    let squares = cells.iter().map(|c| c.to_square()).collect::<Vec<_>>();
+    let x_min = squares.iter().map(|s| s.0).min().unwrap();
+    let x_max = squares.iter().map(|s| s.0).max().unwrap();
+    let y_min = squares.iter().map(|s| s.1).min().unwrap();
+    let y_max = squares.iter().map(|s| s.1).max().unwrap();
+
+    let width = (x_max - x_min) as usize * CELL_WIDTH / 2 + CELL_WIDTH + 1;
+    let height = (y_max - y_min) as usize * ROW_HEIGHT + ROW_HEIGHT + 2;
+    let mut canvas = vec![vec![" ".to_string(); width]; height];
+
+    for (cell, (x, y)) in cells.iter().zip(squares) {
+        let left = (x - x_min) as usize * CELL_WIDTH / 2;
+        let top = (y_max - y) as usize * ROW_HEIGHT;
+
+        let mut draw = |row: usize, column: usize, c: char| canvas[top + row][left + column] = c.to_string();
+        draw(0, 2, rising);
+        draw(0, 4, falling);
+        draw(1, 1, rising);
+        draw(1, 5, falling);
+        for row in [2, 3] {
+            draw(row, 0, side);
+            draw(row, CELL_WIDTH, side);
+        }
+        draw(4, 1, falling);
+        draw(4, 5, rising);
+        draw(5, 2, falling);
+        draw(5, 4, rising);
+
+        let (first, mut second) = match game_state.tiles().get(cell) {
+            Some(tile) => {
+                let top_tile = tile.top();
+                let label = if top_tile.team() { top_tile.label() } else { top_tile.label().to_lowercase() };
+                let height = if tile.height() > 1 { format!("x{}", tile.height()) } else { String::new() };
+                (
+                    label.chars().map(|c| colour::team(top_tile.team(), c)).collect::<Vec<_>>(),
+                    height.chars().map(|c| c.to_string()).collect::<Vec<_>>(),
+                )
+            }
+            None => {
+                let coordinate = cell.to_string();
+                let text = if coordinate.len() <= INTERIOR_WIDTH { coordinate } else { String::new() };
+                (text.chars().map(colour::dim).collect(), Vec::new())
+            }
+        };
+
+        let cell_marks = marks(*cell);
+        if cell_marks.destination {
+            second = vec![colour::highlight('*')];
+        }
+        else if cell_marks.selected {
+            second = vec![colour::highlight('='); 3];
+        }
+
+        for (row, text) in [(2, first), (3, second)] {
+            // Centred, leaning left
+            let start = 1 + (INTERIOR_WIDTH - text.len()) / 2;
+            for (i, c) in text.into_iter().enumerate() {
+                canvas[top + row][left + start + i] = c;
+            }
+        }
+        if cell_marks.cursor {
+            canvas[top + 3][left + 1] = colour::highlight('>');
+            canvas[top + 3][left + INTERIOR_WIDTH] = colour::highlight('<');
+        }
+    }
+
+    canvas
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game_state::parse_move_list;
+
+    #[test]
+    fn renders_cells() {
+        colour::set_enabled(false);
+        let mut game = GameState::new();
+        game.apply_moves(&parse_move_list("Q@0,0 A@1,0").unwrap()).unwrap();
+
+        let expected = [
+            "     / \\   / \\   / \\",
+            "    /   \\ /   \\ /   \\",
+            "   |-1,1 | 0,1 | 1,1 |",
+            "   |     |     |     |",
+            "  / \\   / \\   / \\   / \\",
+            " /   \\ /   \\ /   \\ /   \\",
+            "|-1,0 |  Q  | a1  | 2,0 |",
+            "|     |     |     |     |",
+            " \\   / \\   / \\   / \\   /",
+            "  \\ /   \\ /   \\ /   \\ /",
+            "   |0,-1 |1,-1 |2,-1 |",
+            "   |     |     |     |",
+            "    \\   / \\   / \\   /",
+            "     \\ /   \\ /   \\ /",
+        ];
+        assert_eq!(render_hexes(&game, false), expected.join("\n") + "\n");
+    }
+
+    #[test]
+    fn renders_empty_board() {
+        colour::set_enabled(false);
+        assert_eq!(render_hexes(&GameState::new(), true), "  ╱ ╲\n ╱   ╲\n│ 0,0 │\n│     │\n ╲   ╱\n  ╲ ╱\n");
+    }
+}
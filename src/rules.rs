@@ -0,0 +1,152 @@
+use crate::game_state::{GameState, Move};
+use crate::hex_coord::{Direction, HexCoord};
+use crate::tile_types::TileType;
+
+/// Explains why a move is illegal in the position, or returns `None` if it's legal. Meant for
+/// players - the move generator in `GameState` is what the engine relies on.
+pub fn explain_illegal(game_state: &GameState, m: &Move) -> Option<String> {
+    let (_, white_win, black_win) = game_state.score();
+    if white_win || black_win {
+        return Some("The game is over".to_string());
+    }
+    if game_state.get_possible_moves().contains(m) {
+        return None;
+    }
+
+    let reason = match m {
+        Move::Place(tile_type, to) => explain_placement(game_state, *tile_type, *to),
+        Move::Move(from, to) => match explain_immobile(game_state, *from) {
+            Some(reason) => Some(reason),
+            None => explain_movement(game_state, *from, *to),
+        },
+    };
+    Some(reason.unwrap_or_else(|| format!("{m} is not a legal move")))
+}
+
+/// Explains why the piece at `from` can't move at all, or returns `None` if it has moves
+pub fn explain_immobile(game_state: &GameState, from: HexCoord) -> Option<String> {
+    let team = game_state.turn();
+    let Some(tile) = game_state.tiles().get(&from) else {
+        return Some(format!("There's no piece at {from}"));
+    };
+    let top = tile.top();
+    if top.team() != team {
+        return Some(format!("The piece at {from} is your opponent's"));
+    }
+    if game_state.queen_location(team).is_none() {
+        return Some("Pieces can't move until your queen has been placed".to_string());
+    }
+    if game_state.pinned().contains(&from) {
+        return Some(format!("Moving {} would split the hive in two", top.label()));
+    }
+    if !game_state.get_possible_moves().iter().any(|m| matches!(m, Move::Move(f, _) if *f == from)) {
+        return Some(format!("{} is surrounded too closely to slide out", top.label()));
+    }
+    None
+}
+
+fn explain_placement(game_state: &GameState, tile_type: TileType, to: HexCoord) -> Option<String> {
+    let team = game_state.turn();
+    let bag = game_state.turn_piece_bag();
+    if bag.count_of(tile_type) == 0 {
+        return Some(format!("You have no {} left to place", tile_type.name()));
+    }
+    if game_state.force_queen() && bag.count_of(TileType::Queen) > 0 {
+        return Some("Your queen must be placed by your fourth turn".to_string());
+    }
+    match game_state.turn_count() {
+        0 if to != HexCoord::new(0, 0) => return Some("The first piece is always placed at 0,0".to_string()),
+        1 if to != HexCoord::new(1, 0) => return Some("The second piece is always placed at 1,0".to_string()),
+        0 | 1 => return None,
+        _ => {}
+    }
+    if game_state.tiles().contains_key(&to) {
+        return Some(format!("{to} is already occupied"));
+    }
+
+    let neighbours = to.surrounding().into_iter()
+        .filter_map(|s| game_state.tiles().get(&s).map(|tile| (s, tile.top().team())))
+        .collect::<Vec<_>>();
+    if let Some((opponent, _)) = neighbours.iter().find(|(_, t)| *t != team) {
+        return Some(format!("New pieces can't touch your opponent's pieces, and {to} is next to {opponent}"));
+    }
+    if neighbours.is_empty() {
+        return Some(format!("New pieces must be placed next to one of your own pieces, and {to} isn't next to any"));
+    }
+    None
+}
+
+fn explain_movement(game_state: &GameState, from: HexCoord, to: HexCoord) -> Option<String> {
+    let tiles = game_state.tiles();
+    let tile = tiles[&from].top();
+    if from == to {
+        return Some("A piece has to move somewhere else".to_string());
+    }
+    if tiles.contains_key(&to) {
+        return Some(format!("{to} is already occupied"));
+    }
+
+    if let Some(direction) = from.direction_to(to).filter(|_| from.distance(to) == 1) {
+        let beside = [direction.rotate(5), direction.rotate(1)].map(|d: Direction| tiles.contains_key(&from.neighbor(d)));
+        if beside == [true, true] {
+            return Some(format!("The gap towards {to} is too narrow for {} to slide through", tile.label()));
+        }
+        if !to.surrounding().iter().any(|s| *s != from && tiles.contains_key(s)) {
+            return Some(format!("{} would lose contact with the hive at {to}", tile.label()));
+        }
+    }
+    else {
+        let name = tile.tile_type().name();
+        return Some(format!("{} can't reach {to} - {name}s move {}", tile.label(), tile.tile_type().movement()));
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game_state::parse_move_list;
+
+    fn explain(moves: &str, m: &str) -> Option<String> {
+        let mut game = GameState::new();
+        game.apply_moves(&parse_move_list(moves).unwrap()).unwrap();
+        explain_illegal(&game, &m.parse().unwrap())
+    }
+
+    #[test]
+    fn explains_placements() {
+        assert_eq!(explain("", "Q@0,0"), None);
+        assert!(explain("", "Q@1,1").unwrap().contains("0,0"));
+        assert!(explain("Q@0,0", "Q@0,1").unwrap().contains("1,0"));
+        assert!(explain("Q@0,0 Q@1,0", "A@0,0").unwrap().contains("occupied"));
+        assert!(explain("Q@0,0 Q@1,0", "A@0,1").unwrap().contains("opponent"));
+        assert!(explain("Q@0,0 Q@1,0", "A@-3,0").unwrap().contains("next to one of your own"));
+        assert!(explain("Q@0,0 Q@1,0", "Q@-1,0").unwrap().contains("no Queen left"));
+        assert!(explain("A@0,0 A@1,0 A@-1,0 A@2,0 A@-2,0 A@3,0", "A@-3,0").unwrap().contains("fourth turn"));
+    }
+
+    #[test]
+    fn explains_movements() {
+        let midgame = "A@0,0 A@1,0 Q@-1,0 Q@2,0 A@-1,1 A@3,-1";
+        assert!(explain("A@0,0 A@1,0", "0,0>0,1").unwrap().contains("queen has been placed"));
+        assert!(explain(midgame, "5,5>0,1").unwrap().contains("no piece"));
+        assert!(explain(midgame, "1,0>1,1").unwrap().contains("opponent's"));
+        assert!(explain(midgame, "0,0>0,1").unwrap().contains("split the hive"));
+        assert!(explain(midgame, "-1,1>-1,0").unwrap().contains("occupied"));
+        assert!(explain(midgame, "-1,1>-3,1").unwrap().contains("one space at a time"));
+        assert!(explain(midgame, "-1,1>-2,2").unwrap().contains("lose contact"));
+        assert_eq!(explain(midgame, "-1,1>0,1"), None);
+    }
+
+    #[test]
+    fn explains_finished_game() {
+        let mut game = GameState::new();
+        game.apply_moves(&parse_move_list(
+            "Q@0,0 A@1,0 A@-1,1 A@1,1 A@-1,2 Q@1,2 A@-2,3 A@2,-1 -2,3>-1,3 1,2>0,3 A@-2,2 2,-1>1,-1 \
+            A@-2,1 A@1,-2 A@-3,2 1,-2>0,-1 -2,2>-3,3 1,1>0,1 -3,3>-4,3 A@-1,-1 -4,3>-4,2 -1,-1>-1,0"
+        ).unwrap()).unwrap();
+        // The move generator doesn't stop at the end of the game
+        let m = game.get_possible_moves().into_iter().next().unwrap();
+        assert_eq!(explain_illegal(&game, &m), Some("The game is over".to_string()));
+    }
+}
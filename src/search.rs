@@ -0,0 +1,627 @@
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+use crate::evaluator::{DefaultEvaluator, Evaluator};
+use crate::game_state::{GameState, Move};
+use crate::hex_coord::HexCoord;
+use crate::session::team_name;
+use crate::transposition::{Bound, TranspositionTable, TtEntry, DEFAULT_SIZE_LOG2};
+
+const KILLER_SLOTS: usize = 2;
+
+// Ordering keys - larger is searched first
+const TT_MOVE_KEY: u64 = u64::MAX;
+const QUEEN_PRESSURE_KEY: u64 = u64::MAX - 1;
+const KILLER_KEY: u64 = u64::MAX - 1 - KILLER_SLOTS as u64;
+
+type History = HashMap<Move, u64>;
+
+#[derive(Default, Clone)]
+pub struct SearchStats {
+    pub nodes: u64,
+    pub cutoffs: u64,
+    pub first_move_cutoffs: u64,
+}
+
+impl SearchStats {
+    /// Proportion of cut-offs caused by the first move searched - the closer to 1 the better the ordering
+    pub fn first_move_cutoff_rate(&self) -> f64 {
+        if self.cutoffs == 0 {
+            return 0.0;
+        }
+        self.first_move_cutoffs as f64 / self.cutoffs as f64
+    }
+
+    pub fn print(&self) {
+        println!("Searched: {}", self.nodes);
+        println!(
+            "Cut-offs: {} ({:.1}% on first move)",
+            self.cutoffs,
+            self.first_move_cutoff_rate() * 100.0
+        );
+    }
+}
+
+pub struct Searcher {
+    tt: TranspositionTable,
+    history: History,
+    ordering: bool,
+    threads: usize,
+    evaluator: Box<dyn Evaluator>,
+    stats: SearchStats,
+}
+
+impl Default for Searcher {
+    fn default() -> Self {
+        Searcher::new()
+    }
+}
+
+impl Searcher {
+    pub fn new() -> Searcher {
+        Searcher::with_evaluator(Box::new(DefaultEvaluator::default()))
+    }
+
+    pub fn with_evaluator(evaluator: Box<dyn Evaluator>) -> Searcher {
+        Searcher {
+            tt: TranspositionTable::new(DEFAULT_SIZE_LOG2),
+            history: HashMap::new(),
+            ordering: true,
+            threads: 1,
+            evaluator,
+            stats: SearchStats::default(),
+        }
+    }
+
+    /// Enables or disables move ordering, for comparing its effect on the cut-off rate
+    pub fn set_ordering(&mut self, ordering: bool) {
+        self.ordering = ordering;
+    }
+
+    /// Sets the number of threads used to search. With one thread the search is deterministic,
+    /// with more the extra threads search the same position (Lazy SMP), sharing their results
+    /// with the main thread through the transposition table.
+    pub fn set_threads(&mut self, threads: usize) {
+        self.threads = threads.max(1);
+    }
+
+    pub fn stats(&self) -> &SearchStats { &self.stats }
+
+    /// Searches every move from the current position, returning the best along with its score.
+    /// Returns `None` if there are no moves available.
+    pub fn best_move(&mut self, game_state: &mut GameState, max_depth: usize) -> Option<(Move, isize)> {
+        let stop = AtomicBool::new(false);
+        let mut lines = self.search(game_state, max_depth, 1, &stop);
+        if lines.is_empty() {
+            return None;
+        }
+        let best = lines.swap_remove(0);
+        Some((best.moves.into_iter().next().unwrap(), best.score))
+    }
+
+    /// Searches the position to `max_depth`, returning the best `lines` root moves best first,
+    /// each with its principal variation. Returns no lines if there are no moves available or
+    /// the search is stopped through `stop` before finishing.
+    pub fn search(&mut self, game_state: &mut GameState, max_depth: usize, lines: usize, stop: &AtomicBool) -> Vec<PvLine> {
+        // Age history so that older searches don't dominate
+        for v in self.history.values_mut() {
+            *v /= 8;
+        }
+
+        // Stops the helpers once the main thread is done
+        let finished = AtomicBool::new(false);
+
+        let (result, stats, history) = thread::scope(|scope| {
+            let helpers = (1..self.threads).map(|i| {
+                let mut worker = Worker::new(self, &finished);
+                let mut game_state = game_state.clone();
+                // Vary depth and root order so helpers fill the table with different positions
+                let depth = max_depth + i % 2;
+                scope.spawn(move || {
+                    worker.search_root(&mut game_state, depth, i, 1);
+                    worker.stats.nodes
+                })
+            }).collect::<Vec<_>>();
+
+            let mut worker = Worker::new(self, stop);
+            let result = worker.search_root(game_state, max_depth, 0, lines);
+            finished.store(true, Ordering::Relaxed);
+
+            for helper in helpers {
+                worker.stats.nodes += helper.join().unwrap();
+            }
+            (result, worker.stats, worker.history)
+        });
+
+        self.stats = stats;
+        self.history = history;
+
+        result
+    }
+
+    /// Searches with increasing depth up to `max_depth`, or until stopped through `stop` if
+    /// there is no maximum, calling `report` after each depth with the best `lines` root moves
+    pub fn analyse(&mut self, game_state: &GameState, lines: usize, max_depth: Option<usize>, stop: &AtomicBool, mut report: impl FnMut(&Analysis)) {
+        let start = Instant::now();
+        let mut nodes = 0;
+        for depth in 0..=max_depth.unwrap_or(usize::MAX) {
+            let result = self.search(&mut game_state.clone(), depth, lines, stop);
+            nodes += self.stats.nodes;
+            if result.is_empty() {
+                return;
+            }
+            // Nothing deeper changes a decided game
+            let decided = score_winner(result[0].score).is_some();
+            report(&Analysis { depth, lines: result, nodes, elapsed: start.elapsed() });
+            if decided || stop.load(Ordering::Relaxed) {
+                return;
+            }
+        }
+    }
+}
+
+/// A root move with its score and the moves expected to follow it
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PvLine {
+    pub score: isize,
+    /// The root move followed by the principal variation
+    pub moves: Vec<Move>,
+}
+
+/// Results of one depth of `Searcher::analyse`
+pub struct Analysis {
+    pub depth: usize,
+    /// Best first
+    pub lines: Vec<PvLine>,
+    /// Nodes searched across all depths so far
+    pub nodes: u64,
+    pub elapsed: Duration,
+}
+
+impl Analysis {
+    pub fn nodes_per_second(&self) -> u64 {
+        (self.nodes as f64 / self.elapsed.as_secs_f64().max(1e-9)) as u64
+    }
+}
+
+/// State for a single search thread
+struct Worker<'a> {
+    tt: &'a TranspositionTable,
+    evaluator: &'a dyn Evaluator,
+    stop: &'a AtomicBool,
+    ordering: bool,
+    killers: Vec<[Option<Move>; KILLER_SLOTS]>,
+    history: History,
+    /// Principal variation found below each depth
+    pv: Vec<Vec<Move>>,
+    stats: SearchStats,
+}
+
+impl<'a> Worker<'a> {
+    fn new(searcher: &'a Searcher, stop: &'a AtomicBool) -> Worker<'a> {
+        Worker {
+            tt: &searcher.tt,
+            evaluator: searcher.evaluator.as_ref(),
+            stop,
+            ordering: searcher.ordering,
+            killers: Vec::new(),
+            history: searcher.history.clone(),
+            pv: Vec::new(),
+            stats: SearchStats::default(),
+        }
+    }
+
+    fn stopped(&self) -> bool {
+        self.stop.load(Ordering::Relaxed)
+    }
+
+    /// Clears the principal variation below `depth`, as when a node is evaluated without
+    /// searching
+    fn clear_pv(&mut self, depth: usize) {
+        if self.pv.len() <= depth {
+            self.pv.resize(depth + 1, Vec::new());
+        }
+        self.pv[depth].clear();
+    }
+
+    /// Sets the principal variation at `depth` to `m` followed by the one below it
+    fn update_pv(&mut self, depth: usize, m: &Move) {
+        let (above, below) = self.pv.split_at_mut(depth + 1);
+        let line = &mut above[depth];
+        line.clear();
+        line.push(m.clone());
+        line.extend(below.first().into_iter().flatten().cloned());
+    }
+
+    /// Searches the root moves, returning the best `lines` of them best first. `rotation`
+    /// offsets the root move order, used to diversify helper threads.
+    fn search_root(&mut self, game_state: &mut GameState, max_depth: usize, rotation: usize, lines: usize) -> Vec<PvLine> {
+        let mut moves = game_state.get_possible_moves();
+        if moves.is_empty() {
+            return Vec::new();
+        }
+        self.order_moves(game_state, &mut moves, 0);
+        let len = moves.len();
+        moves.rotate_left(rotation % len);
+
+        let turn = game_state.turn();
+        // Best first
+        let mut results: Vec<PvLine> = Vec::with_capacity(len);
+
+        for m in moves {
+            if self.stopped() {
+                return Vec::new();
+            }
+
+            game_state.apply_move(m.clone());
+
+            // Only moves beating the last of the best lines so far need exact scores
+            let bound = match results.get(lines - 1) {
+                Some(line) => line.score,
+                None => if turn { isize::MIN } else { isize::MAX },
+            };
+            self.clear_pv(0);
+            let score = if turn {
+                self.minimax(game_state, 0, max_depth, bound, isize::MAX)
+            }
+            else {
+                self.minimax(game_state, 0, max_depth, isize::MIN, bound)
+            };
+
+            game_state.undo_move(m.clone());
+
+            let mut line = vec![m];
+            line.extend(self.pv[0].iter().cloned());
+            // After any equally scored moves so that the first found stays ahead
+            let position = results.partition_point(|r| if turn { r.score >= score } else { r.score <= score });
+            results.insert(position, PvLine { score, moves: line });
+        }
+        // The last move's score is incomplete if the search stopped while it was being searched
+        if self.stopped() {
+            return Vec::new();
+        }
+        results.truncate(lines);
+
+        let best = &results[0];
+        if game_state.turn_count() > 7 {
+            // The best line is always searched with a window it falls inside
+            let entry = TtEntry { score: best.score, bound: Bound::Exact, depth: max_depth + 2, best_move: Some(best.moves[0].clone()) };
+            self.tt.insert(game_state.get_hash(), &entry);
+        }
+
+        results
+    }
+
+    fn order_moves(&self, game_state: &GameState, moves: &mut [Move], depth: usize) {
+        // Move generation order depends on hash map iteration so sort first to stay deterministic
+        moves.sort();
+
+        if !self.ordering {
+            return;
+        }
+
+        let tt_move = self.tt.get(game_state.get_hash()).and_then(|e| e.best_move);
+        let killers = self.killers.get(depth);
+        let enemy_queen = game_state.queen_location(!game_state.turn());
+
+        moves.sort_by_cached_key(|m| Reverse(self.ordering_key(m, tt_move.as_ref(), killers, enemy_queen)));
+    }
+
+    fn ordering_key(&self, m: &Move, tt_move: Option<&Move>, killers: Option<&[Option<Move>; KILLER_SLOTS]>, enemy_queen: Option<&HexCoord>) -> u64 {
+        if Some(m) == tt_move {
+            return TT_MOVE_KEY;
+        }
+
+        if let Some(queen) = enemy_queen {
+            let (from, to) = match m {
+                Move::Place(_, to) => (None, to),
+                Move::Move(from, to) => (Some(from), to)
+            };
+            let surrounding = queen.surrounding();
+            if surrounding.contains(to) && !from.is_some_and(|f| surrounding.contains(f)) {
+                return QUEEN_PRESSURE_KEY;
+            }
+        }
+
+        if let Some(slot) = killers.and_then(|k| k.iter().position(|k| k.as_ref() == Some(m))) {
+            return KILLER_KEY - slot as u64;
+        }
+
+        // Capped to stay below the killer keys
+        self.history.get(m).copied().unwrap_or(0).min(KILLER_KEY - KILLER_SLOTS as u64)
+    }
+
+    fn record_cutoff(&mut self, m: &Move, depth: usize, max_depth: usize) {
+        if self.killers.len() <= depth {
+            self.killers.resize(depth + 1, Default::default());
+        }
+        let slots = &mut self.killers[depth];
+        if slots[0].as_ref() != Some(m) {
+            slots.rotate_right(1);
+            slots[0] = Some(m.clone());
+        }
+
+        let remaining = (max_depth - depth + 1) as u64;
+        *self.history.entry(m.clone()).or_insert(0) += remaining * remaining;
+    }
+
+    fn child_score(&mut self, current_state: &mut GameState, depth: usize, max_depth: usize, alpha: isize, beta: isize) -> isize {
+        // Left empty unless the child is searched
+        self.clear_pv(depth + 1);
+        if let Some(found) = self.tt.get(current_state.get_hash()) {
+            if found.depth >= max_depth - depth && found.usable(alpha, beta) {
+                return found.score;
+            }
+        }
+
+        if depth == max_depth {
+            // Evaluate board at final depth
+            self.evaluate(current_state)
+        }
+        else {
+            self.minimax(current_state, depth + 1, max_depth, alpha, beta)
+        }
+    }
+
+    fn evaluate(&self, current_state: &GameState) -> isize {
+        let score = current_state.score();
+        if score.1 || score.2 {
+            return get_score(score);
+        }
+        self.evaluator.evaluate(current_state)
+    }
+
+    fn minimax(&mut self, current_state: &mut GameState, depth: usize, max_depth: usize, mut alpha: isize, mut beta: isize) -> isize {
+        let window = (alpha, beta);
+        // Result is discarded once stopped so value is irrelevant
+        if self.stopped() {
+            return 0;
+        }
+
+        self.stats.nodes += 1;
+        let turn = current_state.turn();
+        let mut moves = current_state.get_possible_moves();
+
+        if moves.is_empty() {
+            current_state.pass();
+            let score = self.child_score(current_state, depth, max_depth, alpha, beta);
+            current_state.unpass();
+            // Passes aren't moves so the variation carries on from the next one
+            self.pv[depth] = self.pv[depth + 1].clone();
+            return score;
+        }
+
+        self.order_moves(current_state, &mut moves, depth);
+
+        let mut best = if turn { isize::MIN } else { isize::MAX };
+        let mut best_move = None;
+        for (i, m) in moves.into_iter().enumerate() {
+            current_state.apply_move(m.clone());
+            let score = self.child_score(current_state, depth, max_depth, alpha, beta);
+            current_state.undo_move(m.clone());
+
+            if (turn && score > best) || (!turn && score < best) || best_move.is_none() {
+                best = score;
+                best_move = Some(m.clone());
+                self.update_pv(depth, &m);
+            }
+
+            if turn {
+                if score == isize::MAX { break; }
+                alpha = alpha.max(score);
+            }
+            else {
+                if score == isize::MIN { break; }
+                beta = beta.min(score);
+            }
+
+            if beta <= alpha {
+                self.stats.cutoffs += 1;
+                if i == 0 {
+                    self.stats.first_move_cutoffs += 1;
+                }
+                self.record_cutoff(&m, depth, max_depth);
+                break;
+            }
+        }
+
+        // Scores below a stopped node may be incomplete so mustn't be stored
+        if current_state.turn_count() > 7 && !self.stopped() {
+            // Scores outside the window only bound the true score, having been cut off
+            let bound = if best <= window.0 {
+                Bound::Upper
+            }
+            else if best >= window.1 {
+                Bound::Lower
+            }
+            else {
+                Bound::Exact
+            };
+            self.tt.insert(current_state.get_hash(), &TtEntry { score: best, bound, depth: max_depth - depth + 1, best_move });
+        }
+
+        best
+    }
+}
+
+pub fn get_score(score: (isize, bool, bool)) -> isize {
+    if score.1 && score.2 {
+        return 0;
+    }
+    if score.1 {
+        return isize::MAX;
+    }
+    else if score.2 {
+        return isize::MIN;
+    }
+    score.0
+}
+
+/// The team a score shows to have won, `None` if the game isn't decided
+pub fn score_winner(score: isize) -> Option<bool> {
+    match score {
+        isize::MAX => Some(true),
+        isize::MIN => Some(false),
+        _ => None,
+    }
+}
+
+/// Writes a score compactly, e.g. `+12` or `Cyan wins`
+pub fn format_score(score: isize) -> String {
+    match score_winner(score) {
+        Some(team) => format!("{} wins", team_name(team)),
+        None => format!("{score:+}"),
+    }
+}
+
+/// Describes a score from `Searcher::best_move` for players
+pub fn describe_score(score: isize) -> String {
+    match score {
+        0 => "Even".to_string(),
+        s if score_winner(s).is_none() => format!("{s:+} ({} better)", team_name(s > 0)),
+        s => format_score(s),
+    }
+}
+
+/// Threads to search with when not given - one for each core
+pub fn default_threads() -> usize {
+    thread::available_parallelism().map_or(1, |n| n.get())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::sync::atomic::AtomicUsize;
+    use super::*;
+    use crate::game_state::parse_move_list;
+
+    #[test]
+    fn analysis_reports_lines() {
+        let mut game = GameState::new();
+        game.apply_moves(&parse_move_list("Q@0,0 Q@1,0 A@-1,0 A@2,0").unwrap()).unwrap();
+        let mut searcher = Searcher::new();
+        searcher.set_threads(1);
+
+        let mut reports = Vec::new();
+        searcher.analyse(&game, 3, Some(3), &AtomicBool::new(false), |a| reports.push((a.depth, a.lines.clone())));
+        assert_eq!(reports.iter().map(|r| r.0).collect::<Vec<_>>(), [0, 1, 2, 3]);
+
+        let (_, lines) = reports.last().unwrap();
+        assert_eq!(lines.len(), 3);
+        // Best first, for Cyan to move
+        assert!(lines.windows(2).all(|w| w[0].score >= w[1].score));
+        for line in lines {
+            let mut after = game.clone();
+            for m in &line.moves {
+                if after.get_possible_moves().is_empty() {
+                    after.pass();
+                }
+                assert!(after.get_possible_moves().contains(m), "{m} in {:?}", line.moves);
+                after.apply_move(m.clone());
+            }
+        }
+        let mut stopped = Vec::new();
+        searcher.analyse(&game, 3, None, &AtomicBool::new(true), |a| stopped.push(a.depth));
+        assert!(stopped.is_empty());
+    }
+
+    #[test]
+    fn ordering_improves_cutoffs() {
+        let mut game = GameState::new();
+        game.apply_moves(&parse_move_list(
+            "Q@0,0 A@1,0 A@-1,1 A@1,1 A@-1,2 Q@1,2 A@-2,3 A@2,-1 -2,3>-1,3 1,2>0,3 A@-2,2 2,-1>1,-1 A@-2,1 A@1,-2"
+        ).unwrap()).unwrap();
+        let stats = |ordering: bool| {
+            let mut searcher = Searcher::new();
+            searcher.set_threads(1);
+            searcher.set_ordering(ordering);
+            let score = searcher.search(&mut game.clone(), 2, 1, &AtomicBool::new(false))[0].score;
+            (score, searcher.stats().clone())
+        };
+        let (unordered_score, unordered) = stats(false);
+        let (ordered_score, ordered) = stats(true);
+        assert_eq!(ordered_score, unordered_score);
+        assert!(
+            ordered.first_move_cutoff_rate() > unordered.first_move_cutoff_rate(),
+            "{:.3} ordered, {:.3} unordered", ordered.first_move_cutoff_rate(), unordered.first_move_cutoff_rate()
+        );
+        assert!(ordered.nodes < unordered.nodes, "{} ordered, {} unordered", ordered.nodes, unordered.nodes);
+    }
+
+    #[test]
+    fn threads_agree_on_score() {
+        let mut game = GameState::new();
+        game.apply_moves(&parse_move_list(
+            "Q@0,0 A@1,0 A@-1,1 A@1,1 A@-1,2 Q@1,2 A@-2,3 A@2,-1 -2,3>-1,3 1,2>0,3 A@-2,2 2,-1>1,-1 A@-2,1 A@1,-2"
+        ).unwrap()).unwrap();
+        let best_score = |threads: usize| {
+            let mut searcher = Searcher::new();
+            searcher.set_threads(threads);
+            searcher.search(&mut game.clone(), 2, 1, &AtomicBool::new(false))[0].score
+        };
+        let single = best_score(1);
+        // Helper threads share the table, so their partial results mustn't change the answer
+        for _ in 0..3 {
+            assert_eq!(best_score(4), single);
+        }
+    }
+
+    /// Stops the search it's used in after a number of evaluations
+    struct StoppingEvaluator {
+        stop: Arc<AtomicBool>,
+        remaining: AtomicUsize,
+    }
+
+    impl Evaluator for StoppingEvaluator {
+        fn evaluate(&self, game_state: &GameState) -> isize {
+            if self.remaining.fetch_sub(1, Ordering::Relaxed) == 1 {
+                self.stop.store(true, Ordering::Relaxed);
+            }
+            DefaultEvaluator::default().evaluate(game_state)
+        }
+    }
+
+    #[test]
+    fn stopped_depth_isnt_reported() {
+        // Cyan has a single move, so the search is always stopped during the last root move
+        let mut game = GameState::new();
+        game.apply_moves(&parse_move_list("Q@0,0 A@1,0 0,0>0,1 Q@1,-1 0,1>0,0 1,0>0,1").unwrap()).unwrap();
+        assert_eq!(game.get_possible_moves().len(), 1);
+
+        for evaluations in [1, 10, 100, 1000] {
+            let stop = Arc::new(AtomicBool::new(false));
+            let evaluator = StoppingEvaluator { stop: stop.clone(), remaining: AtomicUsize::new(evaluations) };
+            let mut searcher = Searcher::with_evaluator(Box::new(evaluator));
+            searcher.set_threads(1);
+            // Depths reported before the stop
+            let mut finished = Vec::new();
+            searcher.analyse(&game, 1, None, &stop, |a| {
+                assert!(!stop.load(Ordering::Relaxed), "depth {} reported after stopping at {evaluations}", a.depth);
+                finished.push(a.depth);
+            });
+            assert!(stop.load(Ordering::Relaxed));
+            assert_eq!(finished, (0..finished.len()).collect::<Vec<_>>());
+        }
+    }
+
+    #[test]
+    fn reused_table_keeps_scores() {
+        // Long enough for positions to be stored in the table
+        let mut game = GameState::new();
+        game.apply_moves(&parse_move_list(
+            "Q@0,0 A@1,0 A@-1,1 A@1,1 A@-1,2 Q@1,2 A@-2,3 A@2,-1 -2,3>-1,3 1,2>0,3 A@-2,2 2,-1>1,-1 A@-2,1 A@1,-2"
+        ).unwrap()).unwrap();
+        let scores = |searcher: &mut Searcher| {
+            searcher.search(&mut game.clone(), 2, 4, &AtomicBool::new(false)).iter().map(|l| l.score).collect::<Vec<_>>()
+        };
+        let mut fresh = Searcher::new();
+        let expected = scores(&mut fresh);
+
+        // Cut-off scores left by a narrower search mustn't be taken as exact by the next
+        let mut reused = Searcher::new();
+        reused.search(&mut game.clone(), 2, 1, &AtomicBool::new(false));
+        assert_eq!(scores(&mut reused), expected);
+    }
+}
@@ -0,0 +1,456 @@
+use std::fmt::{self, Display, Formatter};
+use crate::game_state::{GameState, Move};
+use crate::player::{Choice, Player};
+use crate::render;
+
+/// Name of a team as shown to players - `true` for Cyan (first player), `false` for Green
+pub fn team_name(team: bool) -> &'static str {
+    if team { "Cyan" } else { "Green" }
+}
+
+/// Lower case name of a team, as used in the network protocol and the HTTP API
+pub fn team_id(team: bool) -> &'static str {
+    if team { "cyan" } else { "green" }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Outcome {
+    /// Win for the given team - `true` for Cyan (first player), `false` for Green
+    Win(bool),
+    Draw,
+    /// A player stopped giving moves, e.g. a script ran out
+    Ended,
+}
+
+impl Outcome {
+    pub fn describe(&self) -> &'static str {
+        match self {
+            Outcome::Win(true) => "Cyan wins!",
+            Outcome::Win(false) => "Green wins!",
+            Outcome::Draw => "Draw!",
+            Outcome::Ended => "Game ended",
+        }
+    }
+
+    /// Result as written in game records - `1-0` for a Cyan win, `0-1` for a Green win,
+    /// `1/2-1/2` for a draw and `*` for an unfinished game
+    pub fn notation(&self) -> &'static str {
+        match self {
+            Outcome::Win(true) => "1-0",
+            Outcome::Win(false) => "0-1",
+            Outcome::Draw => "1/2-1/2",
+            Outcome::Ended => "*",
+        }
+    }
+
+    pub fn from_notation(s: &str) -> Option<Outcome> {
+        Some(match s {
+            "1-0" => Outcome::Win(true),
+            "0-1" => Outcome::Win(false),
+            "1/2-1/2" => Outcome::Draw,
+            "*" => Outcome::Ended,
+            _ => return None,
+        })
+    }
+}
+
+/// Moves that could have been played in place of part of a game, e.g. a line that was taken back
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Variation {
+    /// Index of the game's move the variation replaces
+    pub ply: usize,
+    pub moves: Vec<Move>,
+}
+
+/// The moves of a game from the starting position along with its result
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GameRecord {
+    pub moves: Vec<Move>,
+    pub outcome: Outcome,
+    /// Sorted by ply
+    pub variations: Vec<Variation>,
+}
+
+impl GameRecord {
+    /// Parses a move list optionally followed by a result (see `Outcome::notation`). Records
+    /// without a result are treated as unfinished. Variations are written in brackets after the
+    /// move they replace, e.g. `Q@0,0 Q@1,0 (A@1,0 A@-1,0) A@-1,0`. Marks such as `?` or `!!`
+    /// after moves are ignored.
+    pub fn parse(s: &str) -> Result<GameRecord, String> {
+        let s = s.split('#').next().unwrap().replace('(', " ( ").replace(')', " ) ");
+        let mut tokens = s.split(|c: char| c.is_whitespace() || c == ';').filter(|t| !t.is_empty()).collect::<Vec<_>>();
+        let outcome = match tokens.last().and_then(|t| Outcome::from_notation(t)) {
+            Some(outcome) => {
+                tokens.pop();
+                outcome
+            }
+            None => Outcome::Ended,
+        };
+
+        let mut record = GameRecord { moves: Vec::new(), outcome, variations: Vec::new() };
+        let mut variation: Option<Variation> = None;
+        for token in tokens {
+            match (token, &mut variation) {
+                ("(", Some(_)) => return Err("Nested variations aren't supported".to_string()),
+                ("(", None) if record.moves.is_empty() => return Err("Variation before the first move".to_string()),
+                ("(", None) => variation = Some(Variation { ply: record.moves.len() - 1, moves: Vec::new() }),
+                (")", Some(_)) => {
+                    let v = variation.take().unwrap();
+                    if v.moves.is_empty() {
+                        return Err("Empty variation".to_string());
+                    }
+                    record.variations.push(v);
+                }
+                (")", None) => return Err("Unmatched ')'".to_string()),
+                (m, Some(v)) => v.moves.push(m.trim_end_matches(['?', '!']).parse()?),
+                (m, None) => record.moves.push(m.trim_end_matches(['?', '!']).parse()?),
+            }
+        }
+        if variation.is_some() {
+            return Err("Unclosed variation".to_string());
+        }
+        Ok(record)
+    }
+
+    /// Writes the record with `mark(ply)` after each move, e.g. `??` for a blunder
+    pub fn annotated(&self, mark: impl Fn(usize) -> &'static str) -> String {
+        let mut out = String::new();
+        for (i, m) in self.moves.iter().enumerate() {
+            out += &format!("{m}{} ", mark(i));
+            for variation in self.variations.iter().filter(|v| v.ply == i) {
+                let moves = variation.moves.iter().map(|m| m.to_string()).collect::<Vec<_>>();
+                out += &format!("({}) ", moves.join(" "));
+            }
+        }
+        out + self.outcome.notation()
+    }
+}
+
+impl Display for GameRecord {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.annotated(|_| ""))
+    }
+}
+
+/// A game being played between two players
+pub struct Session {
+    game: GameState,
+    /// Turns played since the session started, with the team playing each - `None` for a pass
+    history: Vec<(bool, Option<Move>)>,
+    /// Turns taken back that can be replayed, most recently taken back last
+    redo: Vec<(bool, Option<Move>)>,
+    /// Lines from the start of the session that were taken back and played differently
+    abandoned: Vec<Vec<Move>>,
+    verbose: bool,
+    max_turns: Option<usize>,
+}
+
+/// The player of the side not to move, from players ordered Cyan then Green
+fn opponent<'a>(players: &'a [&mut dyn Player; 2], team: bool) -> &'a dyn Player {
+    &*players[if team { 1 } else { 0 }]
+}
+
+impl Session {
+    pub fn new(game: GameState) -> Session {
+        Session {
+            game,
+            history: Vec::new(),
+            redo: Vec::new(),
+            abandoned: Vec::new(),
+            verbose: true,
+            max_turns: None,
+        }
+    }
+
+    /// Whether to print the board and turn information as the game is played
+    pub fn set_verbose(&mut self, verbose: bool) {
+        self.verbose = verbose;
+    }
+
+    /// Declares the game a draw once this many turns have been played
+    pub fn set_max_turns(&mut self, max_turns: Option<usize>) {
+        self.max_turns = max_turns;
+    }
+
+    pub fn game(&self) -> &GameState { &self.game }
+
+    /// Moves played since the session started, excluding passes and moves taken back
+    pub fn moves(&self) -> Vec<Move> {
+        self.history.iter().filter_map(|(_, m)| m.clone()).collect()
+    }
+
+    /// Record of the moves played since the session started, with lines that were taken back
+    /// as variations
+    pub fn record(&self, outcome: Outcome) -> GameRecord {
+        let moves = self.moves();
+        let mut lines = self.abandoned.clone();
+        if !self.redo.is_empty() {
+            lines.push(self.line_with_redo());
+        }
+
+        let mut variations = Vec::new();
+        for line in lines {
+            let ply = moves.iter().zip(&line).take_while(|(a, b)| a == b).count();
+            // Lines continuing past the end of the game have nothing to replace
+            if ply < moves.len() && ply < line.len() {
+                let variation = Variation { ply, moves: line[ply..].to_vec() };
+                if !variations.contains(&variation) {
+                    variations.push(variation);
+                }
+            }
+        }
+        variations.sort_by_key(|v| v.ply);
+        GameRecord { moves, outcome, variations }
+    }
+
+    /// Moves played along with those that can be replayed
+    fn line_with_redo(&self) -> Vec<Move> {
+        self.history.iter().chain(self.redo.iter().rev()).filter_map(|(_, m)| m.clone()).collect()
+    }
+
+    /// Plays a turn, replaying it if it matches the next turn taken back
+    fn play_turn(&mut self, players: &mut [&mut dyn Player; 2], turn: Option<Move>) {
+        let team = self.game.turn();
+        match self.redo.last() {
+            Some((_, next)) if *next == turn => {
+                self.redo.pop();
+            }
+            Some(_) => {
+                self.abandoned.push(self.line_with_redo());
+                self.redo.clear();
+            }
+            None => {}
+        }
+
+        match &turn {
+            Some(m) => {
+                self.game.apply_move(m.clone());
+                for player in players.iter_mut() {
+                    player.observe_move(&self.game, m);
+                }
+            }
+            None => self.game.pass(),
+        }
+        self.history.push((team, turn));
+    }
+
+    /// Takes back turns up to and including the last move of the side to move, so that undoing
+    /// against the engine also takes back its reply. Against another interactive player only the
+    /// last move is taken back, handing the turn back to whoever made it. Returns how many moves
+    /// were taken back.
+    fn undo(&mut self, players: &mut [&mut dyn Player; 2]) -> usize {
+        let team = self.game.turn();
+        let single = opponent(players, team).is_interactive();
+        let last_taken = |t: bool, turn: &Option<Move>| turn.is_some() && (single || t == team);
+        if !self.history.iter().any(|(t, m)| last_taken(*t, m)) {
+            return 0;
+        }
+
+        let mut count = 0;
+        while let Some((t, turn)) = self.history.pop() {
+            match &turn {
+                Some(m) => {
+                    self.game.undo_move(m.clone());
+                    for player in players.iter_mut() {
+                        player.observe_undo(&self.game, m);
+                    }
+                    count += 1;
+                }
+                None => self.game.unpass(),
+            }
+            let last = last_taken(t, &turn);
+            self.redo.push((t, turn));
+            if last {
+                break;
+            }
+        }
+        count
+    }
+
+    /// Replays turns taken back - the side to move's next move and any replies to it, or just
+    /// the next move against another interactive player. Returns how many moves were replayed.
+    fn redo(&mut self, players: &mut [&mut dyn Player; 2]) -> usize {
+        let team = self.game.turn();
+        let single = opponent(players, team).is_interactive();
+        let mut count = 0;
+        let mut replayed_own = false;
+        while let Some((t, turn)) = self.redo.last().cloned() {
+            if (replayed_own && t == team) || (single && count > 0) {
+                break;
+            }
+            replayed_own |= t == team;
+            count += turn.is_some() as usize;
+            self.play_turn(players, turn);
+        }
+        count
+    }
+
+    /// Gets the outcome if the game is over
+    pub fn outcome(&self) -> Option<Outcome> {
+        let (_, w, b) = self.game.score();
+        if w && b {
+            return Some(Outcome::Draw);
+        }
+        if w || b {
+            return Some(Outcome::Win(w));
+        }
+        if self.max_turns.is_some_and(|max| self.game.turn_count() >= max) {
+            return Some(Outcome::Draw);
+        }
+        None
+    }
+
+    /// Plays the game to completion
+    pub fn play(&mut self, white: &mut dyn Player, black: &mut dyn Player) -> Outcome {
+        let mut players: [&mut dyn Player; 2] = [white, black];
+        loop {
+            if self.verbose {
+                render::print_board(&self.game);
+            }
+
+            if let Some(outcome) = self.outcome() {
+                if self.verbose {
+                    println!("{}", outcome.describe());
+                }
+                return outcome;
+            }
+
+            if self.verbose {
+                println!("Turn: {} [{}]\n", team_name(self.game.turn()), self.game.turn_count());
+            }
+
+            let moves = self.game.get_possible_moves();
+            if moves.is_empty() {
+                if self.verbose {
+                    println!("Passing turn - no moves available");
+                }
+                self.play_turn(&mut players, None);
+                continue;
+            }
+
+            let player = &mut players[if self.game.turn() { 0 } else { 1 }];
+            let name = player.name().to_string();
+            let m = match player.choose(&self.game) {
+                Some(Choice::Play(m)) => m,
+                Some(Choice::Undo) => {
+                    let taken_back = self.undo(&mut players);
+                    if self.verbose {
+                        match taken_back {
+                            0 => println!("Nothing to undo"),
+                            n => println!("Took back {n} move{}", if n == 1 { "" } else { "s" }),
+                        }
+                    }
+                    continue;
+                }
+                Some(Choice::Redo) => {
+                    let replayed = self.redo(&mut players);
+                    if self.verbose {
+                        match replayed {
+                            0 => println!("Nothing to redo"),
+                            n => println!("Replayed {n} move{}", if n == 1 { "" } else { "s" }),
+                        }
+                    }
+                    continue;
+                }
+                None => {
+                    println!("{name} ended the game");
+                    return Outcome::Ended;
+                }
+            };
+            if !moves.contains(&m) {
+                println!("{name} made an illegal move: {m}");
+                return Outcome::Ended;
+            }
+
+            self.play_turn(&mut players, Some(m));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::VecDeque;
+    use crate::game_state::parse_move_list;
+    use crate::player::ScriptedPlayer;
+    use super::*;
+
+    /// Makes a fixed list of choices, ending the game once they run out
+    struct ChoicePlayer(VecDeque<Choice>);
+
+    impl ChoicePlayer {
+        fn new(choices: &[&str]) -> ChoicePlayer {
+            ChoicePlayer(choices.iter().map(|c| match *c {
+                "undo" => Choice::Undo,
+                "redo" => Choice::Redo,
+                m => Choice::Play(m.parse().unwrap()),
+            }).collect())
+        }
+    }
+
+    impl Player for ChoicePlayer {
+        fn choose_move(&mut self, _game_state: &GameState) -> Option<Move> { None }
+
+        fn choose(&mut self, _game_state: &GameState) -> Option<Choice> { self.0.pop_front() }
+
+        fn is_interactive(&self) -> bool { true }
+
+        fn name(&self) -> &str { "Choices" }
+    }
+
+    fn play(white: &[&str], black: &str) -> Session {
+        let mut session = Session::new(GameState::new());
+        session.set_verbose(false);
+        let mut black = ScriptedPlayer::new(parse_move_list(black).unwrap());
+        assert_eq!(session.play(&mut ChoicePlayer::new(white), &mut black), Outcome::Ended);
+        session
+    }
+
+    #[test]
+    fn record_round_trip() {
+        let text = "Q@0,0 Q@1,0 (A@1,0 A@-1,0) (A@2,0) A@-1,0 A@2,0 1-0";
+        let record = GameRecord::parse(text).unwrap();
+        assert_eq!(record.moves.len(), 4);
+        assert_eq!(record.outcome, Outcome::Win(true));
+        assert_eq!(record.variations, [
+            Variation { ply: 1, moves: parse_move_list("A@1,0 A@-1,0").unwrap() },
+            Variation { ply: 1, moves: parse_move_list("A@2,0").unwrap() },
+        ]);
+        assert_eq!(record.to_string(), text);
+        assert_eq!(GameRecord::parse("Q@0,0 Q@1,0(A@1,0)").unwrap().to_string(), "Q@0,0 Q@1,0 (A@1,0) *");
+        assert_eq!(GameRecord::parse("Q@0,0 Q@1,0?? (A@1,0) A@1,0!").unwrap().annotated(|ply| if ply == 1 { "?" } else { "" }), "Q@0,0 Q@1,0? (A@1,0) A@1,0 *");
+
+        for invalid in ["(Q@0,0) Q@1,0", "Q@0,0 (Q@1,0 (A@1,0))", "Q@0,0 (Q@1,0", "Q@0,0 Q@1,0)", "Q@0,0 () Q@1,0"] {
+            assert!(GameRecord::parse(invalid).is_err(), "{invalid}");
+        }
+    }
+
+    #[test]
+    fn undo_takes_back_reply() {
+        let session = play(&["Q@0,0", "A@-1,0", "undo", "A@0,-1", "redo"], "Q@1,0 A@2,0 A@2,0");
+        assert_eq!(session.moves(), parse_move_list("Q@0,0 Q@1,0 A@0,-1 A@2,0").unwrap());
+        assert_eq!(session.game().turn_count(), 4);
+        assert_eq!(session.record(Outcome::Ended).to_string(), "Q@0,0 Q@1,0 A@0,-1 (A@-1,0 A@2,0) A@2,0 *");
+    }
+
+    #[test]
+    fn redo_replays_moves() {
+        let session = play(&["undo", "Q@0,0", "undo", "redo", "A@-1,0", "undo", "undo", "redo"], "Q@1,0 A@2,0");
+        // The last redo replays the first two moves, leaving A@-1,0 and its reply to be redone
+        assert_eq!(session.moves(), parse_move_list("Q@0,0 Q@1,0").unwrap());
+        assert_eq!(session.record(Outcome::Ended).to_string(), "Q@0,0 Q@1,0 *");
+        assert_eq!(session.line_with_redo(), parse_move_list("Q@0,0 Q@1,0 A@-1,0 A@2,0").unwrap());
+    }
+
+    #[test]
+    fn undo_against_person_takes_back_one_move() {
+        let mut session = Session::new(GameState::new());
+        session.set_verbose(false);
+        let mut white = ChoicePlayer::new(&["Q@0,0", "A@-1,0", "A@0,-1", "undo"]);
+        let mut black = ChoicePlayer::new(&["Q@1,0", "undo", "A@2,0", "redo"]);
+        assert_eq!(session.play(&mut white, &mut black), Outcome::Ended);
+        // Green took back Cyan's A@-1,0, then Cyan took back Green's A@2,0 which Green replayed
+        assert_eq!(session.moves(), parse_move_list("Q@0,0 Q@1,0 A@0,-1 A@2,0").unwrap());
+        assert_eq!(session.game().turn_count(), 4);
+        assert_eq!(session.record(Outcome::Ended).to_string(), "Q@0,0 Q@1,0 A@0,-1 (A@-1,0) A@2,0 *");
+    }
+}
@@ -0,0 +1,236 @@
+use std::fmt::Write;
+use crate::game_state::{GameState, Move};
+use crate::hex_coord::HexCoord;
+
+/// Distance from the centre of a cell to its corners, in pixels
+const HEX_SIZE: f64 = 30.0;
+/// Space around the drawn cells
+const MARGIN: f64 = 10.0;
+/// How far arrows stop short of cell centres, so that they don't cover labels
+const ARROW_INSET: f64 = HEX_SIZE * 0.45;
+/// Vertical offset between the outlines of stacked pieces
+const STACK_OFFSET: f64 = 4.0;
+
+const CYAN_FILL: &str = "#8fdce9";
+const CYAN_STROKE: &str = "#1b7f91";
+const GREEN_FILL: &str = "#98d99c";
+const GREEN_STROKE: &str = "#2e7d32";
+const EMPTY_STROKE: &str = "#c8c8c8";
+const TEXT: &str = "#1a1a1a";
+const HIGHLIGHT: &str = "#e07b00";
+
+#[derive(Clone, Debug, Default)]
+pub struct SvgOptions {
+    /// Label every cell with its coordinate, drawing the empty cells around the hive
+    pub coordinates: bool,
+    /// Move to highlight, usually the one that led to the position
+    pub highlight: Option<Move>,
+    /// Moves to draw from the position as numbered arrows, e.g. a suggested line
+    pub line: Vec<Move>,
+}
+
+fn centre(c: HexCoord) -> (f64, f64) {
+    let (x, y) = (c.x as f64, c.y as f64);
+    // Pointy-topped, with y increasing upwards as in the text board
+    (HEX_SIZE * 3f64.sqrt() * (x + y / 2.0), -HEX_SIZE * 1.5 * y)
+}
+
+fn team_colours(team: bool) -> (&'static str, &'static str) {
+    if team { (CYAN_FILL, CYAN_STROKE) } else { (GREEN_FILL, GREEN_STROKE) }
+}
+
+fn marker_id(stroke: &str) -> &'static str {
+    match stroke {
+        CYAN_STROKE => "arrow-cyan",
+        GREEN_STROKE => "arrow-green",
+        _ => "arrow-highlight",
+    }
+}
+
+fn hexagon(out: &mut String, (cx, cy): (f64, f64), attributes: &str) {
+    let points = (0..6)
+        .map(|i| {
+            let angle = (60.0 * i as f64 + 30.0).to_radians();
+            format!("{:.1},{:.1}", cx + HEX_SIZE * angle.cos(), cy + HEX_SIZE * angle.sin())
+        })
+        .collect::<Vec<_>>()
+        .join(" ");
+    writeln!(out, r#"  <polygon points="{points}" {attributes}/>"#).unwrap();
+}
+
+fn text(out: &mut String, (x, y): (f64, f64), size: f64, fill: &str, content: &str) {
+    writeln!(
+        out,
+        r#"  <text x="{x:.1}" y="{y:.1}" font-size="{size}" fill="{fill}" text-anchor="middle" dominant-baseline="central">{content}</text>"#
+    ).unwrap();
+}
+
+/// Draws an arrow between two cells, with an optional label at its middle
+fn arrow(out: &mut String, from: HexCoord, to: HexCoord, stroke: &str, label: Option<&str>) {
+    let ((x1, y1), (x2, y2)) = (centre(from), centre(to));
+    let length = ((x2 - x1).powi(2) + (y2 - y1).powi(2)).sqrt();
+    let (dx, dy) = ((x2 - x1) / length * ARROW_INSET, (y2 - y1) / length * ARROW_INSET);
+    writeln!(
+        out,
+        r#"  <line x1="{:.1}" y1="{:.1}" x2="{:.1}" y2="{:.1}" stroke="{stroke}" stroke-width="3" marker-end="url(#{})"/>"#,
+        x1 + dx, y1 + dy, x2 - dx, y2 - dy, marker_id(stroke)
+    ).unwrap();
+    if let Some(label) = label {
+        writeln!(out, r#"  <circle cx="{:.1}" cy="{:.1}" r="8" fill="white" stroke="{stroke}"/>"#, (x1 + x2) / 2.0, (y1 + y2) / 2.0).unwrap();
+        text(out, ((x1 + x2) / 2.0, (y1 + y2) / 2.0), 10.0, TEXT, label);
+    }
+}
+
+/// Draws the position as an SVG image. Pieces are coloured by team and labelled as in the text
+/// board (see `HiveTile::label`), with stacks drawn as offset outlines beneath their top piece.
+/// Fails if the moves of `options.line` can't be played in turn from the position.
+pub fn render_svg(game_state: &GameState, options: &SvgOptions) -> Result<String, String> {
+    let tiles = game_state.tiles();
+
+    game_state.clone().apply_moves(&options.line).map_err(|e| format!("Invalid line: {e}"))?;
+    // Teams making each move of the line
+    let mut line_teams = Vec::new();
+    let mut after = game_state.clone();
+    for m in &options.line {
+        if after.get_possible_moves().is_empty() {
+            after.pass();
+        }
+        line_teams.push(after.turn());
+        after.apply_move(m.clone());
+    }
+
+    let mut cells = tiles.keys().copied().collect::<Vec<_>>();
+    if options.coordinates {
+        for loc in tiles.keys() {
+            cells.extend(loc.surrounding().into_iter().filter(|s| !tiles.contains_key(s)));
+        }
+    }
+    if cells.is_empty() {
+        cells.push(HexCoord::new(0, 0));
+    }
+    cells.sort();
+    cells.dedup();
+
+    // Every cell drawn or pointed to, for the size of the image
+    let mut extent = cells.clone();
+    for m in options.line.iter().chain(&options.highlight) {
+        match m {
+            Move::Place(_, to) => extent.push(*to),
+            Move::Move(from, to) => extent.extend([*from, *to]),
+        }
+    }
+    let points = extent.iter().map(|c| centre(*c)).collect::<Vec<_>>();
+    let x_min = points.iter().map(|p| p.0).fold(f64::INFINITY, f64::min) - HEX_SIZE - MARGIN;
+    let x_max = points.iter().map(|p| p.0).fold(f64::NEG_INFINITY, f64::max) + HEX_SIZE + MARGIN;
+    let y_min = points.iter().map(|p| p.1).fold(f64::INFINITY, f64::min) - HEX_SIZE - MARGIN - STACK_OFFSET * 4.0;
+    let y_max = points.iter().map(|p| p.1).fold(f64::NEG_INFINITY, f64::max) + HEX_SIZE + MARGIN;
+
+    let mut out = String::new();
+    writeln!(
+        out,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="{x_min:.1} {y_min:.1} {:.1} {:.1}" width="{:.0}" height="{:.0}" font-family="sans-serif">"#,
+        x_max - x_min, y_max - y_min, x_max - x_min, y_max - y_min
+    ).unwrap();
+    out += "  <defs>\n";
+    for stroke in [CYAN_STROKE, GREEN_STROKE, HIGHLIGHT] {
+        writeln!(
+            out,
+            r#"    <marker id="{}" viewBox="0 0 10 10" refX="8" refY="5" markerWidth="4" markerHeight="4" orient="auto"><path d="M0,0 L10,5 L0,10 z" fill="{stroke}"/></marker>"#,
+            marker_id(stroke)
+        ).unwrap();
+    }
+    out += "  </defs>\n";
+
+    for cell in &cells {
+        let (cx, cy) = centre(*cell);
+        match tiles.get(cell) {
+            Some(tile) => {
+                let top = tile.top();
+                let (fill, stroke) = team_colours(top.team());
+                // Pieces beneath the top one, bottom first
+                let mut below = Vec::new();
+                let mut piece = Some(tile);
+                while let Some(p) = piece {
+                    below.push(p);
+                    piece = p.above();
+                }
+                below.pop();
+                let raise = below.len() as f64 * STACK_OFFSET;
+                for (i, p) in below.iter().enumerate() {
+                    let (fill, stroke) = team_colours(p.team());
+                    let offset = i as f64 * STACK_OFFSET;
+                    hexagon(&mut out, (cx, cy - offset), &format!(r#"fill="{fill}" stroke="{stroke}" stroke-width="2""#));
+                }
+                hexagon(&mut out, (cx, cy - raise), &format!(r#"fill="{fill}" stroke="{stroke}" stroke-width="2""#));
+                text(&mut out, (cx, cy - raise), 16.0, TEXT, &top.label());
+                if tile.height() > 1 {
+                    text(&mut out, (cx + HEX_SIZE * 0.45, cy - raise - HEX_SIZE * 0.45), 10.0, TEXT, &format!("×{}", tile.height()));
+                }
+            }
+            None => hexagon(&mut out, (cx, cy), &format!(r#"fill="none" stroke="{EMPTY_STROKE}" stroke-dasharray="4 3""#)),
+        }
+        if options.coordinates {
+            text(&mut out, (cx, cy + HEX_SIZE * 0.55), 8.0, TEXT, &cell.to_string());
+        }
+    }
+
+    if let Some(m) = &options.highlight {
+        match m {
+            Move::Place(_, to) => hexagon(&mut out, centre(*to), &format!(r#"fill="none" stroke="{HIGHLIGHT}" stroke-width="3""#)),
+            Move::Move(from, to) => arrow(&mut out, *from, *to, HIGHLIGHT, None),
+        }
+    }
+
+    for (i, (m, team)) in options.line.iter().zip(line_teams).enumerate() {
+        let (fill, stroke) = team_colours(team);
+        let number = (i + 1).to_string();
+        match m {
+            Move::Place(tile_type, to) => {
+                let (cx, cy) = centre(*to);
+                hexagon(&mut out, (cx, cy), &format!(r#"fill="{fill}" fill-opacity="0.4" stroke="{stroke}" stroke-width="2" stroke-dasharray="6 3""#));
+                text(&mut out, (cx, cy), 16.0, TEXT, &tile_type.character().to_string());
+                text(&mut out, (cx + HEX_SIZE * 0.45, cy - HEX_SIZE * 0.45), 10.0, stroke, &number);
+            }
+            Move::Move(from, to) => arrow(&mut out, *from, *to, stroke, Some(&number)),
+        }
+    }
+
+    out += "</svg>\n";
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game_state::parse_move_list;
+
+    #[test]
+    fn draws_pieces_and_annotations() {
+        let moves = parse_move_list("Q@0,0 Q@1,0 A@-1,0 A@2,0").unwrap();
+        let mut game = GameState::new();
+        game.apply_moves(&moves).unwrap();
+
+        let plain = render_svg(&game, &SvgOptions::default()).unwrap();
+        assert!(plain.starts_with("<svg") && plain.ends_with("</svg>\n"));
+        assert_eq!(plain.matches("<polygon").count(), 4);
+        assert_eq!(plain.matches(&format!(r#"fill="{CYAN_FILL}""#)).count(), 2);
+        assert!(plain.contains(">A1</text>"));
+        assert!(!plain.contains("<line"));
+
+        let options = SvgOptions {
+            coordinates: true,
+            highlight: moves.last().cloned(),
+            line: parse_move_list("-1,0>-1,1 A@3,-1").unwrap(),
+        };
+        let annotated = render_svg(&game, &options).unwrap();
+        // Four pieces, their twelve empty neighbours, the highlighted placement and the line's placement
+        assert_eq!(annotated.matches("<polygon").count(), 18);
+        assert!(annotated.contains(">-2,1</text>"));
+        assert_eq!(annotated.matches("<line").count(), 1);
+        assert!(annotated.contains(r#"marker-end="url(#arrow-cyan)""#));
+        assert!(annotated.contains(&format!(r#"fill="{GREEN_FILL}" fill-opacity"#)));
+
+        let illegal = SvgOptions { line: parse_move_list("Q@5,5").unwrap(), ..SvgOptions::default() };
+        assert!(render_svg(&game, &illegal).is_err());
+    }
+}
@@ -1,10 +1,8 @@
-use std::collections::HashSet;
-use itertools::Itertools;
 use variant_count::VariantCount;
 use crate::game_state::TileStore;
-use crate::hex_coord::HexCoord;
+use crate::hex_coord::{Direction, HexCoord};
 
-#[derive(VariantCount, Clone, Copy, Hash, Eq, PartialEq)]
+#[derive(VariantCount, Clone, Copy, Hash, Eq, PartialEq, Debug, PartialOrd, Ord)]
 pub enum TileType {
     Queen,
     Ant,
@@ -18,6 +16,20 @@ impl TileType {
         }
     }
 
+    pub fn name(&self) -> &'static str {
+        match self {
+            TileType::Queen => "Queen",
+            TileType::Ant => "Ant",
+        }
+    }
+
+    /// How the piece moves, as shown to players
+    pub fn movement(&self) -> &'static str {
+        match self {
+            TileType::Queen | TileType::Ant => "one space at a time, sliding around the hive",
+        }
+    }
+
     pub fn from_character(c: char) -> Option<TileType> {
         Some(match c {
             'Q' => TileType::Queen,
@@ -26,6 +38,18 @@ impl TileType {
         })
     }
 
+    pub fn index(&self) -> usize {
+        *self as usize
+    }
+
+    pub fn from_index(index: usize) -> Option<TileType> {
+        Some(match index {
+            0 => TileType::Queen,
+            1 => TileType::Ant,
+            _ => return None,
+        })
+    }
+
     pub fn get_moves(&self, location: &HexCoord, tiles: &TileStore) -> Vec<HexCoord> {
         match self {
             TileType::Queen => {
@@ -42,29 +66,16 @@ fn queen_moves(location: &HexCoord, tiles: &TileStore) -> Vec<HexCoord> {
     possible_steps(location, tiles)
 }
 
-fn ant_moves(location: &HexCoord, tiles: &TileStore) -> Vec<HexCoord> {
-    let first = possible_steps(location, tiles);
-    let mut full = HashSet::new();
-
-    for m in first {
-        for m2 in possible_steps(&m, tiles) {
-            full.insert(m2);
-        }
-        full.insert(m);
-    }
-    full.into_iter().collect_vec()
-}
-
 fn possible_steps(location: &HexCoord, tiles: &TileStore) -> Vec<HexCoord> {
     let mut steps = Vec::with_capacity(5);
 
-    let surrounding = location.surrounding();
-    let occupied = surrounding.iter().map(|l| tiles.contains_key(&l)).collect_vec();
+    let occupied = Direction::ALL.map(|d| tiles.contains_key(&location.neighbor(d)));
 
-    for (i, loc) in surrounding.into_iter().enumerate() {
-        if occupied[i] { continue }
-        if occupied[(i + 5) % 6] && occupied[(i + 1) % 6] { continue }
-        steps.push(loc);
+    for direction in Direction::ALL {
+        if occupied[direction.index()] { continue }
+        // Can't slide through a gap between the two tiles either side
+        if occupied[direction.rotate(5).index()] && occupied[direction.rotate(1).index()] { continue }
+        steps.push(location.neighbor(direction));
     }
 
     steps
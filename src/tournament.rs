@@ -0,0 +1,253 @@
+use std::collections::HashSet;
+use std::sync::Arc;
+use crate::book::OpeningBook;
+use crate::game_state::{GameState, Move};
+use crate::piece_bag::PieceBag;
+use crate::player::{create_player, PlayerOptions};
+use crate::session::{team_name, Outcome, Session};
+
+/// z-score for a 95% confidence interval
+const Z_95: f64 = 1.96;
+
+/// Sequential probability ratio test of whether the first player is `elo1` stronger than the
+/// second (accepting H1) rather than `elo0` (accepting H0)
+#[derive(Clone, Copy, Debug)]
+pub struct Sprt {
+    pub elo0: f64,
+    pub elo1: f64,
+    /// Probability of accepting H1 when H0 is true
+    pub alpha: f64,
+    /// Probability of accepting H0 when H1 is true
+    pub beta: f64,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SprtResult {
+    AcceptH0,
+    AcceptH1,
+    Continue,
+}
+
+impl Sprt {
+    pub fn bounds(&self) -> (f64, f64) {
+        ((self.beta / (1.0 - self.alpha)).ln(), ((1.0 - self.beta) / self.alpha).ln())
+    }
+
+    /// Log-likelihood ratio of H1 against H0, using the normal approximation to the trinomial
+    /// distribution of game results
+    pub fn llr(&self, results: &Results) -> f64 {
+        let n = results.games() as f64;
+        if n == 0.0 {
+            return 0.0;
+        }
+        let variance = results.variance();
+        if variance == 0.0 {
+            return 0.0;
+        }
+        let s0 = score_from_elo(self.elo0);
+        let s1 = score_from_elo(self.elo1);
+        n * (s1 - s0) * (2.0 * results.score() - s0 - s1) / (2.0 * variance)
+    }
+
+    pub fn test(&self, results: &Results) -> SprtResult {
+        let llr = self.llr(results);
+        let (lower, upper) = self.bounds();
+        if llr <= lower {
+            SprtResult::AcceptH0
+        }
+        else if llr >= upper {
+            SprtResult::AcceptH1
+        }
+        else {
+            SprtResult::Continue
+        }
+    }
+}
+
+/// Results from the perspective of the first player
+#[derive(Default, Clone, Copy, Debug)]
+pub struct Results {
+    pub wins: usize,
+    pub draws: usize,
+    pub losses: usize,
+}
+
+impl Results {
+    pub fn games(&self) -> usize {
+        self.wins + self.draws + self.losses
+    }
+
+    /// Average points per game, counting a draw as half a win
+    pub fn score(&self) -> f64 {
+        (self.wins as f64 + self.draws as f64 / 2.0) / self.games() as f64
+    }
+
+    /// Variance of the points from a single game
+    pub fn variance(&self) -> f64 {
+        let n = self.games() as f64;
+        let s = self.score();
+        (self.wins as f64 * (1.0 - s).powi(2) + self.draws as f64 * (0.5 - s).powi(2) + self.losses as f64 * s.powi(2)) / n
+    }
+
+    pub fn elo(&self) -> f64 {
+        elo_from_score(self.score())
+    }
+
+    /// 95% confidence interval of the Elo difference
+    pub fn elo_interval(&self) -> (f64, f64) {
+        let error = Z_95 * (self.variance() / self.games() as f64).sqrt();
+        let s = self.score();
+        (elo_from_score(s - error), elo_from_score(s + error))
+    }
+}
+
+pub fn elo_from_score(score: f64) -> f64 {
+    let score = score.clamp(1e-6, 1.0 - 1e-6);
+    -400.0 * (1.0 / score - 1.0).log10()
+}
+
+pub fn score_from_elo(elo: f64) -> f64 {
+    1.0 / (1.0 + 10f64.powf(-elo / 400.0))
+}
+
+pub struct TournamentConfig {
+    pub games: usize,
+    pub max_turns: Option<usize>,
+    pub pieces: PieceBag,
+    /// Starting positions, each played once with each player as Cyan
+    pub openings: Vec<Vec<Move>>,
+    pub sprt: Option<Sprt>,
+    pub seed: Option<u64>,
+    pub book: Option<Arc<OpeningBook>>,
+}
+
+/// Starting positions for the openings, skipping any equivalent to an earlier one under symmetry
+fn opening_positions(config: &TournamentConfig) -> Result<Vec<GameState>, String> {
+    let mut seen = HashSet::new();
+    let mut positions = Vec::new();
+    for (i, opening) in config.openings.iter().enumerate() {
+        let mut game = GameState::with_pieces(config.pieces.clone());
+        game.apply_moves(opening).map_err(|e| format!("Invalid opening {}: {e}", i + 1))?;
+        if seen.insert(game.canonical_hash()) {
+            positions.push(game);
+        }
+        else {
+            println!("Skipping opening {} - same position as an earlier opening", i + 1);
+        }
+    }
+    Ok(positions)
+}
+
+/// Plays games between two players, alternating colours, printing each result and the running
+/// totals. Fresh players are created for each game so that games are independent.
+pub fn run(first: &str, second: &str, config: &TournamentConfig) -> Result<Results, String> {
+    let mut results = Results::default();
+    let openings = opening_positions(config)?;
+
+    for i in 0..config.games {
+        // Vary seeds between games, otherwise seeded players would repeat the same game
+        let options = PlayerOptions {
+            seed: config.seed.map(|s| s.wrapping_add(i as u64 * 2)),
+            verbose: false,
+            book: config.book.clone(),
+        };
+        let mut a = create_player(first, &options)?;
+        let mut b = create_player(second, &PlayerOptions { seed: options.seed.map(|s| s.wrapping_add(1)), ..options.clone() })?;
+
+        let game = if openings.is_empty() {
+            GameState::with_pieces(config.pieces.clone())
+        }
+        else {
+            openings[(i / 2) % openings.len()].clone()
+        };
+
+        let mut session = Session::new(game);
+        session.set_verbose(false);
+        session.set_max_turns(config.max_turns);
+
+        let first_is_cyan = i % 2 == 0;
+        let outcome = if first_is_cyan {
+            session.play(a.as_mut(), b.as_mut())
+        }
+        else {
+            session.play(b.as_mut(), a.as_mut())
+        };
+
+        match outcome {
+            Outcome::Win(cyan) if cyan == first_is_cyan => results.wins += 1,
+            Outcome::Win(_) => results.losses += 1,
+            Outcome::Draw => results.draws += 1,
+            Outcome::Ended => return Err(format!("Game {} ended early", i + 1)),
+        }
+
+        println!(
+            "Game {}: {} as {} - {} ({} turns) | +{} ={} -{}",
+            i + 1,
+            first,
+            team_name(first_is_cyan),
+            outcome.describe(),
+            session.game().turn_count(),
+            results.wins,
+            results.draws,
+            results.losses,
+        );
+
+        if let Some(sprt) = &config.sprt {
+            match sprt.test(&results) {
+                SprtResult::Continue => {}
+                result => {
+                    println!("SPRT: {} after {} games (LLR {:.2})", if result == SprtResult::AcceptH1 { "H1 accepted" } else { "H0 accepted" }, results.games(), sprt.llr(&results));
+                    break;
+                }
+            }
+        }
+    }
+
+    Ok(results)
+}
+
+pub fn print_summary(first: &str, second: &str, results: &Results, sprt: Option<&Sprt>) {
+    println!();
+    println!("{first} vs {second}");
+    println!("Wins: {}  Draws: {}  Losses: {}", results.wins, results.draws, results.losses);
+    if results.games() == 0 {
+        return;
+    }
+    println!("Score: {:.1}%", results.score() * 100.0);
+    let (low, high) = results.elo_interval();
+    println!("Elo difference: {:+.1} (95% CI {:+.1} to {:+.1})", results.elo(), low, high);
+    if let Some(sprt) = sprt {
+        let (lower, upper) = sprt.bounds();
+        println!("SPRT [{}, {}]: LLR {:.2} (bounds {:.2}, {:.2})", sprt.elo0, sprt.elo1, sprt.llr(results), lower, upper);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn elo_score_inverse() {
+        for elo in [-400.0, -100.0, 0.0, 35.0, 200.0] {
+            assert!((elo_from_score(score_from_elo(elo)) - elo).abs() < 1e-9);
+        }
+        assert_eq!(elo_from_score(0.5), 0.0);
+        assert!((score_from_elo(400.0) - 10.0 / 11.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn elo_interval_contains_estimate() {
+        let results = Results { wins: 30, draws: 10, losses: 20 };
+        let (low, high) = results.elo_interval();
+        assert!(low < results.elo() && results.elo() < high);
+        assert!(results.elo() > 0.0);
+    }
+
+    #[test]
+    fn sprt_decides() {
+        let sprt = Sprt { elo0: 0.0, elo1: 50.0, alpha: 0.05, beta: 0.05 };
+        assert_eq!(sprt.test(&Results { wins: 3, draws: 2, losses: 3 }), SprtResult::Continue);
+        assert_eq!(sprt.test(&Results { wins: 400, draws: 100, losses: 100 }), SprtResult::AcceptH1);
+        assert_eq!(sprt.test(&Results { wins: 100, draws: 100, losses: 400 }), SprtResult::AcceptH0);
+    }
+}
@@ -0,0 +1,223 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use crate::game_state::Move;
+use crate::hex_coord::HexCoord;
+use crate::tile_types::TileType;
+
+pub const DEFAULT_SIZE_LOG2: u32 = 20;
+
+const VALID_BIT: u64 = 1 << 63;
+const NO_MOVE: u64 = 0;
+const PLACE_MOVE: u64 = 1;
+const MOVE_MOVE: u64 = 2;
+
+/// How a stored score relates to the position's true score. Searches cut off by alpha-beta only
+/// find a bound on it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Bound {
+    Exact,
+    /// The true score is at least the stored one
+    Lower,
+    /// The true score is at most the stored one
+    Upper,
+}
+
+impl Bound {
+    fn index(&self) -> u64 {
+        match self {
+            Bound::Exact => 0,
+            Bound::Lower => 1,
+            Bound::Upper => 2,
+        }
+    }
+
+    fn from_index(index: u64) -> Bound {
+        match index {
+            1 => Bound::Lower,
+            2 => Bound::Upper,
+            _ => Bound::Exact,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct TtEntry {
+    pub score: isize,
+    pub bound: Bound,
+    pub depth: usize,
+    pub best_move: Option<Move>,
+}
+
+impl TtEntry {
+    /// Whether the stored score settles the position when searched with the window `alpha`..`beta`
+    pub fn usable(&self, alpha: isize, beta: isize) -> bool {
+        match self.bound {
+            Bound::Exact => true,
+            Bound::Lower => self.score >= beta,
+            Bound::Upper => self.score <= alpha,
+        }
+    }
+}
+
+/// Fixed size transposition table that can be shared between search threads without locking.
+///
+/// Each slot holds a check word alongside two data words, where the check word is the position
+/// hash xor'd with both data words. Concurrent writes to the same slot may tear, but a torn slot
+/// fails the check on read and is treated as empty.
+pub struct TranspositionTable {
+    slots: Vec<[AtomicU64; 3]>,
+    mask: u64,
+}
+
+impl TranspositionTable {
+    pub fn new(size_log2: u32) -> TranspositionTable {
+        let size = 1usize << size_log2;
+        TranspositionTable {
+            slots: (0..size).map(|_| [AtomicU64::new(0), AtomicU64::new(0), AtomicU64::new(0)]).collect(),
+            mask: size as u64 - 1,
+        }
+    }
+
+    pub fn get(&self, hash: u64) -> Option<TtEntry> {
+        let slot = &self.slots[(hash & self.mask) as usize];
+        let check = slot[0].load(Ordering::Relaxed);
+        let info = slot[1].load(Ordering::Relaxed);
+        let coords = slot[2].load(Ordering::Relaxed);
+
+        if info & VALID_BIT == 0 || check ^ info ^ coords != hash {
+            return None;
+        }
+
+        Some(unpack(info, coords))
+    }
+
+    pub fn insert(&self, hash: u64, entry: &TtEntry) {
+        let slot = &self.slots[(hash & self.mask) as usize];
+        let (info, coords) = pack(entry);
+        slot[0].store(hash ^ info ^ coords, Ordering::Relaxed);
+        slot[1].store(info, Ordering::Relaxed);
+        slot[2].store(coords, Ordering::Relaxed);
+    }
+}
+
+fn pack_score(score: isize) -> u32 {
+    let score = match score {
+        isize::MAX => i32::MAX,
+        isize::MIN => i32::MIN,
+        s => s.clamp(i32::MIN as isize + 1, i32::MAX as isize - 1) as i32,
+    };
+    score as u32
+}
+
+fn unpack_score(score: u32) -> isize {
+    match score as i32 {
+        i32::MAX => isize::MAX,
+        i32::MIN => isize::MIN,
+        s => s as isize,
+    }
+}
+
+fn pack_coord(coord: &HexCoord) -> u64 {
+    debug_assert!(i16::try_from(coord.x).is_ok() && i16::try_from(coord.y).is_ok());
+    ((coord.x as i16 as u16 as u64) << 16) | (coord.y as i16 as u16 as u64)
+}
+
+fn unpack_coord(coord: u64) -> HexCoord {
+    HexCoord::new((coord >> 16) as u16 as i16 as isize, coord as u16 as i16 as isize)
+}
+
+/// Info word: bits 0-31 score, 32-47 depth, 48-51 move kind, 52-55 bound, 56-62 tile type, 63 valid.
+/// Coords word: the move's from coordinate in the upper 32 bits and to coordinate in the lower.
+fn pack(entry: &TtEntry) -> (u64, u64) {
+    let (kind, tile_type, coords) = match &entry.best_move {
+        None => (NO_MOVE, 0, 0),
+        Some(Move::Place(tile_type, to)) => (PLACE_MOVE, tile_type.index() as u64, pack_coord(to)),
+        Some(Move::Move(from, to)) => (MOVE_MOVE, 0, (pack_coord(from) << 32) | pack_coord(to)),
+    };
+
+    let depth = entry.depth.min(u16::MAX as usize) as u64;
+    let info = pack_score(entry.score) as u64 | (depth << 32) | (kind << 48) | (entry.bound.index() << 52) | (tile_type << 56) | VALID_BIT;
+    (info, coords)
+}
+
+fn unpack(info: u64, coords: u64) -> TtEntry {
+    let score = unpack_score(info as u32);
+    let depth = ((info >> 32) & 0xFFFF) as usize;
+    let to = unpack_coord(coords & 0xFFFF_FFFF);
+
+    let bound = Bound::from_index((info >> 52) & 0xF);
+    let best_move = match (info >> 48) & 0xF {
+        PLACE_MOVE => TileType::from_index(((info >> 56) & 0x7F) as usize).map(|t| Move::Place(t, to)),
+        MOVE_MOVE => Some(Move::Move(unpack_coord(coords >> 32), to)),
+        _ => None,
+    };
+
+    TtEntry { score, bound, depth, best_move }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entries() -> Vec<TtEntry> {
+        let moves = [
+            None,
+            Some(Move::Place(TileType::Queen, HexCoord::new(0, 0))),
+            Some(Move::Place(TileType::Ant, HexCoord::new(-3, 7))),
+            Some(Move::Move(HexCoord::new(-1, -2), HexCoord::new(2, -5))),
+            Some(Move::Move(HexCoord::new(i16::MIN as isize, i16::MAX as isize), HexCoord::new(i16::MAX as isize, i16::MIN as isize))),
+        ];
+        let mut entries = Vec::new();
+        for (i, best_move) in moves.into_iter().enumerate() {
+            for bound in [Bound::Exact, Bound::Lower, Bound::Upper] {
+                for score in [0, -17, 250, isize::MAX, isize::MIN] {
+                    entries.push(TtEntry { score, bound, depth: i * 3, best_move: best_move.clone() });
+                }
+            }
+        }
+        entries
+    }
+
+    #[test]
+    fn packs_entries() {
+        for entry in entries() {
+            let (info, coords) = pack(&entry);
+            assert_eq!(unpack(info, coords), entry);
+        }
+        // Scores next to a win are kept short of it
+        for (score, winning) in [(isize::MAX - 1, isize::MAX), (isize::MIN + 1, isize::MIN)] {
+            let entry = TtEntry { score, bound: Bound::Exact, depth: 1, best_move: None };
+            let (info, coords) = pack(&entry);
+            let unpacked = unpack(info, coords).score;
+            assert_ne!(unpacked, winning);
+            assert_eq!(unpacked.signum(), score.signum());
+            assert!(unpacked.unsigned_abs() > i32::MAX as usize - 2);
+        }
+    }
+
+    #[test]
+    fn stores_entries() {
+        let table = TranspositionTable::new(4);
+        for (i, entry) in entries().into_iter().enumerate() {
+            let hash = 0x9E37_79B9_7F4A_7C15u64.wrapping_mul(i as u64 + 1);
+            table.insert(hash, &entry);
+            assert_eq!(table.get(hash), Some(entry));
+            // Another position sharing the slot doesn't match
+            assert_eq!(table.get(hash ^ (1 << 40)), None);
+        }
+    }
+
+    #[test]
+    fn rejects_torn_slots() {
+        let table = TranspositionTable::new(4);
+        let entry = TtEntry { score: 42, bound: Bound::Lower, depth: 3, best_move: Some(Move::Move(HexCoord::new(1, 0), HexCoord::new(0, 1))) };
+        let hash = 0x1234_5678_9ABC_DEF0;
+        for word in 0..3 {
+            table.insert(hash, &entry);
+            assert_eq!(table.get(hash), Some(entry.clone()));
+            // As though another thread's write to the slot had only partly landed
+            let slot = &table.slots[(hash & table.mask) as usize];
+            slot[word].fetch_xor(1 << 20, Ordering::Relaxed);
+            assert_eq!(table.get(hash), None, "word {word}");
+        }
+    }
+}
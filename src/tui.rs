@@ -0,0 +1,466 @@
+use std::io::{stdout, Write};
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use crossterm::{cursor, execute, queue, terminal};
+use crate::colour;
+use crate::evaluator::{DefaultEvaluator, EvalWeights};
+use crate::game_state::{GameState, Move};
+use crate::hex_coord::{Direction, HexCoord};
+use crate::player::{describe_moves, hint, Choice, Player};
+use crate::rules::{explain_illegal, explain_immobile};
+use crate::render::{self, BoardStyle, CellMarks};
+use crate::search::{describe_score, Searcher};
+use crate::session::team_name;
+use crate::tile_types::TileType;
+
+pub const DEFAULT_EVAL_DEPTH: usize = 3;
+/// Most recent moves shown in the history pane
+const HISTORY_LINES: usize = 12;
+/// Columns between the board and the side pane
+const PANE_GAP: usize = 3;
+/// Widest line of the side pane, for deciding whether it fits beside the board
+const PANE_WIDTH: usize = 36;
+const HELP: &str = "Arrows: move cursor  Enter: select/play  Q/A or 1-9: piece from hand  Esc: cancel\r\nH: hint  M: show moves  U/R: undo/redo  Ctrl-C: quit";
+
+/// Restores the terminal when dropped, including on early returns and panics
+struct TerminalGuard;
+
+impl TerminalGuard {
+    fn enter() -> std::io::Result<TerminalGuard> {
+        terminal::enable_raw_mode()?;
+        let guard = TerminalGuard;
+        execute!(stdout(), terminal::EnterAlternateScreen, cursor::Hide)?;
+        Ok(guard)
+    }
+}
+
+impl Drop for TerminalGuard {
+    fn drop(&mut self) {
+        let _ = execute!(stdout(), cursor::Show, terminal::LeaveAlternateScreen);
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Selection {
+    Nothing,
+    /// A piece type to place
+    Hand(TileType),
+    /// A piece on the board to move
+    Piece(HexCoord),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Action {
+    Redraw,
+    Choose(Choice),
+    Hint,
+    Quit,
+}
+
+/// State of choosing a single move
+struct Picker<'a> {
+    game_state: &'a GameState,
+    moves: Vec<Move>,
+    /// Cells the cursor can visit - those drawn on the board
+    cells: Vec<HexCoord>,
+    cursor: HexCoord,
+    selection: Selection,
+    /// Whether to mark every piece that can move and everywhere pieces can be placed when
+    /// nothing is selected
+    show_moves: bool,
+    message: String,
+}
+
+impl<'a> Picker<'a> {
+    fn new(game_state: &'a GameState) -> Picker<'a> {
+        let moves = game_state.get_possible_moves();
+        let cells = render::board_cells(game_state);
+        // Start on the queen if she's out, otherwise somewhere a piece can be placed
+        let cursor = game_state.queen_location(game_state.turn()).copied()
+            .or_else(|| moves.first().map(|m| match m {
+                Move::Place(_, to) => *to,
+                Move::Move(from, _) => *from,
+            }))
+            .unwrap_or(cells[0]);
+        Picker { game_state, moves, cells, cursor, selection: Selection::Nothing, show_moves: false, message: String::new() }
+    }
+
+    /// Where the selection can go, or with nothing selected and `show_moves` set, every piece
+    /// that can move and everywhere pieces can be placed
+    fn destinations(&self) -> Vec<HexCoord> {
+        self.moves.iter()
+            .filter_map(|m| match (m, self.selection) {
+                (Move::Place(t, to), Selection::Hand(selected)) if *t == selected => Some(*to),
+                (Move::Move(from, to), Selection::Piece(selected)) if *from == selected => Some(*to),
+                (Move::Place(_, to), Selection::Nothing) if self.show_moves => Some(*to),
+                (Move::Move(from, _), Selection::Nothing) if self.show_moves => Some(*from),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Selects the piece for a move with the cursor on its destination
+    fn show_move(&mut self, m: &Move) {
+        let (selection, to) = match m {
+            Move::Place(t, to) => (Selection::Hand(*t), to),
+            Move::Move(from, to) => (Selection::Piece(*from), to),
+        };
+        self.selection = selection;
+        self.cursor = *to;
+    }
+
+    fn marks(&self, destinations: &[HexCoord], cell: HexCoord) -> CellMarks {
+        CellMarks {
+            cursor: cell == self.cursor,
+            selected: self.selection == Selection::Piece(cell),
+            destination: destinations.contains(&cell),
+        }
+    }
+
+    /// Piece types in the hand of the side to move, with their counts
+    fn hand(&self) -> Vec<(TileType, usize)> {
+        let bag = self.game_state.turn_piece_bag();
+        (0..TileType::VARIANT_COUNT)
+            .map(|i| TileType::from_index(i).unwrap())
+            .map(|t| (t, bag.count_of(t)))
+            .filter(|(_, count)| *count > 0)
+            .collect()
+    }
+
+    fn can_place(&self, tile_type: TileType) -> bool {
+        self.moves.iter().any(|m| matches!(m, Move::Place(t, _) if *t == tile_type))
+    }
+
+    /// Moves the cursor one cell, trying `fallback` if there's no cell in the `primary` direction
+    fn step(&mut self, primary: Direction, fallback: Direction) {
+        for direction in [primary, fallback] {
+            let next = self.cursor.neighbor(direction);
+            if self.cells.contains(&next) {
+                self.cursor = next;
+                return;
+            }
+        }
+    }
+
+    fn select_hand(&mut self, tile_type: TileType) {
+        if !self.can_place(tile_type) {
+            self.message = format!("No {} can be placed", tile_type.character());
+            return;
+        }
+        self.selection = Selection::Hand(tile_type);
+        let destinations = self.destinations();
+        if !destinations.contains(&self.cursor) {
+            self.cursor = destinations[0];
+        }
+    }
+
+    /// Selects a piece on the board, or plays the selection at the cursor
+    fn confirm(&mut self) -> Action {
+        let own_piece = self.game_state.tiles().get(&self.cursor)
+            .is_some_and(|tile| tile.top().team() == self.game_state.turn());
+
+        let chosen = match self.selection {
+            Selection::Hand(t) => Some(Move::Place(t, self.cursor)),
+            Selection::Piece(from) if from != self.cursor => Some(Move::Move(from, self.cursor)),
+            Selection::Piece(_) => {
+                self.selection = Selection::Nothing;
+                return Action::Redraw;
+            }
+            Selection::Nothing => None,
+        };
+        if let Some(m) = chosen.as_ref().filter(|m| self.moves.contains(m)) {
+            return Action::Choose(Choice::Play(m.clone()));
+        }
+
+        if own_piece {
+            match explain_immobile(self.game_state, self.cursor) {
+                Some(reason) => self.message = reason,
+                None => self.selection = Selection::Piece(self.cursor),
+            }
+        }
+        else if let Some(m) = chosen {
+            self.message = explain_illegal(self.game_state, &m).unwrap_or_default();
+        }
+        else {
+            self.message = "Choose a piece from your hand or one of your pieces to move".to_string();
+        }
+        Action::Redraw
+    }
+
+    fn handle_key(&mut self, key: KeyEvent) -> Action {
+        self.message.clear();
+        if key.modifiers.contains(KeyModifiers::CONTROL) && matches!(key.code, KeyCode::Char('c') | KeyCode::Char('d')) {
+            return Action::Quit;
+        }
+        match key.code {
+            KeyCode::Left => self.step(Direction::West, Direction::West),
+            KeyCode::Right => self.step(Direction::East, Direction::East),
+            KeyCode::Up => self.step(Direction::NorthEast, Direction::NorthWest),
+            KeyCode::Down => self.step(Direction::SouthWest, Direction::SouthEast),
+            KeyCode::Enter | KeyCode::Char(' ') => return self.confirm(),
+            KeyCode::Esc => self.selection = Selection::Nothing,
+            KeyCode::Char('h') => return Action::Hint,
+            KeyCode::Char('m') => match self.selection {
+                Selection::Piece(from) => self.message = describe_moves(self.game_state, Some(from)).join("  "),
+                _ => {
+                    self.show_moves = !self.show_moves;
+                    if self.show_moves {
+                        self.message = format!("{} legal moves - * marks pieces that can move and where pieces can be placed", self.moves.len());
+                    }
+                }
+            },
+            KeyCode::Char('u') => return Action::Choose(Choice::Undo),
+            KeyCode::Char('r') => return Action::Choose(Choice::Redo),
+            KeyCode::Char(c) if c.is_ascii_digit() => {
+                match self.hand().get((c as usize).wrapping_sub('1' as usize)) {
+                    Some(&(tile_type, _)) => self.select_hand(tile_type),
+                    None => self.message = format!("No piece {c} in hand"),
+                }
+            }
+            KeyCode::Char(c) => match TileType::from_character(c.to_ascii_uppercase()) {
+                Some(tile_type) if self.hand().iter().any(|(t, _)| *t == tile_type) => self.select_hand(tile_type),
+                Some(tile_type) => self.message = format!("No {} left in hand", tile_type.character()),
+                None => {}
+            },
+            _ => {}
+        }
+        Action::Redraw
+    }
+}
+
+/// Human player choosing moves on a full-screen board, with a cursor moved by the arrow keys.
+/// Side panes show the pieces in hand, the engine's evaluation and the moves played so far.
+pub struct TuiPlayer {
+    /// Moves played so far this game, with the team that played them
+    history: Vec<(bool, Move)>,
+    searcher: Searcher,
+    /// Depth of the search evaluating each position, 0 to disable it
+    eval_depth: usize,
+}
+
+impl TuiPlayer {
+    pub fn new(eval_depth: usize) -> TuiPlayer {
+        TuiPlayer {
+            history: Vec::new(),
+            searcher: Searcher::with_evaluator(Box::new(DefaultEvaluator::new(EvalWeights::default()))),
+            eval_depth,
+        }
+    }
+
+    fn side_pane(&self, picker: &Picker, evaluation: &[String]) -> Vec<String> {
+        let team = picker.game_state.turn();
+        let mut pane = vec![
+            format!("{} to move (turn {})", colour::team(team, team_name(team)), picker.game_state.turn_count()),
+            String::new(),
+            "Hand".to_string(),
+        ];
+        for (i, (tile_type, count)) in picker.hand().into_iter().enumerate() {
+            let line = format!("{} {} x{count}", i + 1, tile_type.character());
+            pane.push(if picker.selection == Selection::Hand(tile_type) {
+                format!("{} {}", colour::highlight('>'), colour::team(team, line))
+            }
+            else if picker.can_place(tile_type) {
+                format!("  {}", colour::team(team, line))
+            }
+            else {
+                format!("  {}", colour::dim(line))
+            });
+        }
+
+        if !evaluation.is_empty() {
+            pane.push(String::new());
+            pane.extend(evaluation.iter().cloned());
+        }
+
+        pane.push(String::new());
+        pane.push("Moves".to_string());
+        if self.history.is_empty() {
+            pane.push(colour::dim("  None yet"));
+        }
+        let skip = self.history.len().saturating_sub(HISTORY_LINES);
+        for (i, (team, m)) in self.history.iter().enumerate().skip(skip) {
+            pane.push(format!("{:>4}. {}", i + 1, colour::team(*team, m)));
+        }
+        pane
+    }
+
+    fn draw(&self, picker: &Picker, evaluation: &[String]) -> std::io::Result<()> {
+        let destinations = picker.destinations();
+        let unicode = render::style() == BoardStyle::Unicode;
+        let board = render::hex_canvas(picker.game_state, unicode, |c| picker.marks(&destinations, c));
+        let board_width = board.first().map_or(0, |row| row.len());
+        let pane = self.side_pane(picker, evaluation);
+        let (columns, _) = terminal::size()?;
+
+        let mut lines = Vec::new();
+        if board_width + PANE_GAP + PANE_WIDTH <= columns as usize {
+            for i in 0..board.len().max(pane.len()) {
+                let row = board.get(i).map_or(" ".repeat(board_width), |row| row.concat());
+                let side = pane.get(i).map_or("", |s| s.as_str());
+                lines.push(format!("{row}{}{side}", " ".repeat(PANE_GAP)));
+            }
+        }
+        // Too narrow to fit side by side
+        else {
+            lines.extend(board.iter().map(|row| row.concat()));
+            lines.push(String::new());
+            lines.extend(pane);
+        }
+        lines.push(String::new());
+        lines.push(colour::highlight(&picker.message));
+        lines.push(colour::dim(HELP));
+
+        let mut out = stdout();
+        queue!(out, terminal::Clear(terminal::ClearType::All), cursor::MoveTo(0, 0))?;
+        // Raw mode needs explicit carriage returns
+        write!(out, "{}", lines.join("\r\n"))?;
+        out.flush()
+    }
+
+    /// Searches the position, describing the engine's view of it
+    fn evaluate(&mut self, game_state: &GameState) -> Vec<String> {
+        if self.eval_depth == 0 {
+            return Vec::new();
+        }
+        match self.searcher.best_move(&mut game_state.clone(), self.eval_depth) {
+            Some((m, score)) => vec![
+                format!("Evaluation (depth {})", self.eval_depth),
+                format!("  {}", describe_score(score)),
+                format!("  Engine suggests {m}"),
+            ],
+            None => Vec::new(),
+        }
+    }
+
+    fn pick(&mut self, game_state: &GameState) -> std::io::Result<Option<Choice>> {
+        let _guard = TerminalGuard::enter()?;
+        let mut picker = Picker::new(game_state);
+        self.draw(&picker, &[colour::dim("Evaluating...")])?;
+        let evaluation = self.evaluate(game_state);
+
+        loop {
+            self.draw(&picker, &evaluation)?;
+            let action = match event::read()? {
+                // Ignores key releases on platforms that report them
+                Event::Key(key) if key.kind != KeyEventKind::Release => picker.handle_key(key),
+                _ => Action::Redraw,
+            };
+            match action {
+                Action::Redraw => {}
+                Action::Choose(choice) => return Ok(Some(choice)),
+                Action::Hint => {
+                    if let Some((m, description)) = hint(&mut self.searcher, game_state) {
+                        picker.show_move(&m);
+                        picker.message = description;
+                    }
+                }
+                Action::Quit => return Ok(None),
+            }
+        }
+    }
+}
+
+impl Player for TuiPlayer {
+    fn choose_move(&mut self, game_state: &GameState) -> Option<Move> {
+        // Taking moves back needs the history kept by a session
+        loop {
+            match self.choose(game_state)? {
+                Choice::Play(m) => return Some(m),
+                _ => println!("Moves can't be taken back here"),
+            }
+        }
+    }
+
+    fn choose(&mut self, game_state: &GameState) -> Option<Choice> {
+        match self.pick(game_state) {
+            Ok(choice) => choice,
+            Err(e) => {
+                println!("Terminal error: {e}");
+                None
+            }
+        }
+    }
+
+    fn observe_move(&mut self, game_state: &GameState, m: &Move) {
+        self.history.push((!game_state.turn(), m.clone()));
+    }
+
+    fn observe_undo(&mut self, _game_state: &GameState, _m: &Move) {
+        self.history.pop();
+    }
+
+    fn is_interactive(&self) -> bool { true }
+
+    fn name(&self) -> &str { "Human" }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game_state::parse_move_list;
+
+    fn press(picker: &mut Picker, code: KeyCode) -> Action {
+        picker.handle_key(KeyEvent::new(code, KeyModifiers::NONE))
+    }
+
+    #[test]
+    fn places_from_hand() {
+        let mut game = GameState::new();
+        game.apply_moves(&parse_move_list("Q@0,0 Q@1,0").unwrap()).unwrap();
+        let mut picker = Picker::new(&game);
+        assert_eq!(picker.cursor, HexCoord::new(0, 0));
+
+        // Nothing selected on an empty cell
+        assert_eq!(press(&mut picker, KeyCode::Left), Action::Redraw);
+        assert_eq!(picker.cursor, HexCoord::new(-1, 0));
+        assert_eq!(press(&mut picker, KeyCode::Enter), Action::Redraw);
+        assert!(!picker.message.is_empty());
+
+        assert_eq!(press(&mut picker, KeyCode::Char('a')), Action::Redraw);
+        assert_eq!(picker.selection, Selection::Hand(TileType::Ant));
+        assert_eq!(picker.destinations().len(), 3);
+        assert_eq!(press(&mut picker, KeyCode::Enter), Action::Choose(Choice::Play(Move::Place(TileType::Ant, HexCoord::new(-1, 0)))));
+
+        // Can't place next to the opponent
+        press(&mut picker, KeyCode::Right);
+        press(&mut picker, KeyCode::Up);
+        assert_eq!(picker.cursor, HexCoord::new(0, 1));
+        assert_eq!(press(&mut picker, KeyCode::Enter), Action::Redraw);
+        assert_eq!(press(&mut picker, KeyCode::Esc), Action::Redraw);
+        assert_eq!(picker.selection, Selection::Nothing);
+    }
+
+    #[test]
+    fn moves_pieces_on_board() {
+        let mut game = GameState::new();
+        game.apply_moves(&parse_move_list("Q@0,0 Q@1,0 A@-1,0 A@2,0").unwrap()).unwrap();
+        let mut picker = Picker::new(&game);
+
+        press(&mut picker, KeyCode::Left);
+        assert_eq!(press(&mut picker, KeyCode::Enter), Action::Redraw);
+        assert_eq!(picker.selection, Selection::Piece(HexCoord::new(-1, 0)));
+        let mut destinations = picker.destinations();
+        destinations.sort();
+        assert_eq!(destinations, [HexCoord::new(-1, 1), HexCoord::new(0, -1)]);
+
+        press(&mut picker, KeyCode::Up);
+        assert_eq!(picker.cursor, HexCoord::new(-1, 1));
+        assert_eq!(press(&mut picker, KeyCode::Char(' ')), Action::Choose(Choice::Play(Move::Move(HexCoord::new(-1, 0), HexCoord::new(-1, 1)))));
+
+        // Showing moves marks the movable pieces and placement locations
+        let mut picker = Picker::new(&game);
+        assert!(picker.destinations().is_empty());
+        press(&mut picker, KeyCode::Char('m'));
+        let shown = picker.destinations();
+        assert!(shown.contains(&HexCoord::new(-1, 0)) && shown.contains(&HexCoord::new(-1, 1)));
+        assert!(!shown.contains(&HexCoord::new(0, 0)));
+
+        // Opponent's pieces can't be selected
+        let mut picker = Picker::new(&game);
+        press(&mut picker, KeyCode::Right);
+        press(&mut picker, KeyCode::Enter);
+        assert_eq!(picker.selection, Selection::Nothing);
+        assert_eq!(press(&mut picker, KeyCode::Char('c')), Action::Redraw);
+        assert_eq!(picker.handle_key(KeyEvent::new(KeyCode::Char('c'), KeyModifiers::CONTROL)), Action::Quit);
+    }
+}